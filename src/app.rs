@@ -1,31 +1,122 @@
-use crate::{database::Database, model::Model, ui::UserInterface};
+use crate::{
+    command::Command,
+    database::{Database, Version},
+    history::History,
+    import,
+    model::{MaintenanceView, Model, Overlay, ReportKind, SchemaView, Update, ViewState},
+    settings::Settings,
+    ui::{Hit, UserInterface},
+};
 use anyhow::Result;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    clipboard::CopyToClipboard,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyCode, KeyEvent,
+        KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use futures::{Future, FutureExt, StreamExt};
 use ratatui::prelude::*;
-use std::io;
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const TICK_RATE: Duration = Duration::from_millis(100);
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Results sent back to the event loop by background tasks.
+#[derive(Debug)]
+enum Message {
+    Loaded(Result<Update>),
+    /// Partial result of a load that is still running.
+    Progress(Update),
+    /// Data version read by a watch poll.
+    Polled(Result<Version>),
+}
+
+/// Where the UI starts instead of the main list, as given on the command
+/// line.
+#[derive(Debug, Clone, Default)]
+pub struct Startup {
+    pub table: Option<String>,
+    pub query: Option<String>,
+    pub row: Option<usize>,
+    pub condition: Option<String>,
+}
 
 #[derive(Debug)]
 pub struct App<D: Database> {
     ui: UserInterface,
     model: Model<D>,
+    sender: mpsc::UnboundedSender<Message>,
+    receiver: mpsc::UnboundedReceiver<Message>,
+    /// A watch poll is reading the data version.
+    polling: bool,
+    last_click: Option<(Instant, Hit)>,
     exit: bool,
 }
 
 impl<D: Database> App<D> {
-    pub async fn new(db: D) -> Result<Self> {
+    pub async fn new(db: D, startup: &Startup) -> Result<Self> {
         let mut model = Model::new(db);
         model.initialize().await?;
 
+        if let Some(table) = &startup.table {
+            model.open_table(table).await?;
+        } else if let Some(sql) = &startup.query {
+            model.run_query(sql).await?;
+        }
+        if let Some(condition) = &startup.condition {
+            model.select_where(condition).await?;
+        }
+        if let Some(row) = startup.row {
+            model.goto(row);
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+
         Ok(App {
             ui: UserInterface::new(),
             model,
+            sender,
+            receiver,
+            polling: false,
+            last_click: None,
             exit: false,
         })
     }
 
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.model.set_wrap(wrap);
+        self
+    }
+
+    pub fn with_watch(mut self, interval: Option<Duration>) -> Self {
+        self.model.set_watch(interval);
+        self
+    }
+
+    pub fn with_history(mut self, history: History) -> Self {
+        self.model.set_history(history);
+        self
+    }
+
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.model.set_settings(settings);
+        self
+    }
+
+    /// Shows `message` in the status line on startup.
+    pub fn with_message(mut self, message: Option<String>) -> Self {
+        if let Some(message) = message {
+            self.model.set_message(message);
+        }
+        self
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         let panic_hook = std::panic::take_hook();
 
@@ -35,10 +126,31 @@ impl<D: Database> App<D> {
         }));
 
         let mut terminal = Self::init_terminal()?;
+        let mut events = EventStream::new();
+        let mut ticks = tokio::time::interval(TICK_RATE);
+        let mut watch = tokio::time::interval(WATCH_INTERVAL);
 
         while !self.exit {
             terminal.draw(|frame| self.ui.run(frame, &self.model))?;
-            self.handle_events().await?;
+            self.model.set_page_size(self.ui.page_size());
+            if let Some(interval) = self.model.watch_interval() {
+                if watch.period() != interval {
+                    watch = tokio::time::interval(interval);
+                }
+            }
+
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => self.handle_event(event?)?,
+                    None => self.exit(),
+                },
+                Some(message) = self.receiver.recv() => self.handle_message(message),
+                // Redraws the loading indicator while a task is running.
+                _ = ticks.tick(), if self.model.is_loading() => {}
+                _ = watch.tick(), if self.model.watch_interval().is_some() && !self.model.is_loading() && !self.polling => {
+                    self.poll_changes()
+                }
+            }
         }
 
         Self::reset_terminal()?;
@@ -46,16 +158,204 @@ impl<D: Database> App<D> {
         Ok(())
     }
 
-    async fn handle_events(&mut self) -> Result<()> {
-        match event::read()? {
+    fn handle_event(&mut self, event: Event) -> Result<()> {
+        match event {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.handle_key_event(key_event).await
+                self.handle_key_event(key_event)
             }
+            Event::Mouse(mouse_event) => self.handle_mouse_event(mouse_event),
             _ => Ok(()),
         }
     }
 
-    async fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+    fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::Loaded(result) => {
+                self.model.set_loading(false);
+                match result {
+                    Ok(update) => self.model.apply(update),
+                    Err(err) => self.model.set_message(err.to_string()),
+                }
+            }
+            Message::Progress(update) => self.model.apply(update),
+            Message::Polled(result) => {
+                self.polling = false;
+                match result {
+                    // A load started meanwhile shows the data as it is now.
+                    Ok(_) if self.model.is_loading() => {}
+                    Ok(version) => self.spawn(|model| model.watch_version(version)),
+                    Err(err) => self.model.set_message(err.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Runs the database load built by `load` on a background task,
+    /// reporting back through the message channel. Only one load runs at a
+    /// time: while one is running, `load` is not called and the user is
+    /// told so.
+    fn spawn<F>(&mut self, load: impl FnOnce(&mut Model<D>) -> Option<F>)
+    where
+        F: Future<Output = Result<Update>> + Send + 'static,
+    {
+        if self.model.is_loading() {
+            return self.model.set_message("busy, press Esc to cancel".into());
+        }
+        let Some(load) = load(&mut self.model) else {
+            return;
+        };
+        self.model.set_loading(true);
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let _ = sender.send(Message::Loaded(load.await));
+        });
+    }
+
+    /// Sender of partial results of the running load.
+    fn progress(&self) -> impl Fn(Update) + Send + Sync + 'static {
+        let sender = self.sender.clone();
+        move |update| {
+            let _ = sender.send(Message::Progress(update));
+        }
+    }
+
+    /// Loads the report `kind` in the background, showing check problems as
+    /// they are found.
+    fn show_report(&mut self, kind: ReportKind) {
+        let progress = self.progress();
+        self.spawn(|model| Some(model.load_report(kind, progress)));
+    }
+
+    /// Reads the data version in the background, to reload the current
+    /// view when another process changed the database.
+    fn poll_changes(&mut self) {
+        self.polling = true;
+        let sender = self.sender.clone();
+        let load = self.model.load_version();
+        tokio::spawn(async move {
+            let _ = sender.send(Message::Polled(load.await));
+        });
+    }
+
+    fn cancel(&mut self) {
+        if self.model.is_loading() {
+            self.model.interrupt();
+        }
+    }
+
+    /// Drops cached data and reloads the open table, or the main list and
+    /// every table when in main view.
+    fn refresh(&mut self) {
+        if let Some(kind) = self.model.report().cloned() {
+            return self.show_report(kind);
+        }
+        self.spawn(|model| match model.selected_table().map(str::to_string) {
+            Some(table) => Some(model.load_table(&table).boxed()),
+            None => {
+                model.invalidate(None);
+                Some(model.load_overview().boxed())
+            }
+        });
+    }
+
+    /// Switches the disk usage columns of the main list and reloads it.
+    fn toggle_storage(&mut self) {
+        if self.model.view_state() != ViewState::Main {
+            return;
+        }
+        self.spawn(|model| {
+            model.toggle_storage();
+            Some(model.load_overview())
+        });
+    }
+
+    /// Reports an error building a load in the status line.
+    fn or_message<F>(model: &mut Model<D>, load: Result<F>) -> Option<F> {
+        load.map_err(|err| model.set_message(err.to_string())).ok()
+    }
+
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) -> Result<()> {
+        match mouse_event.kind {
+            MouseEventKind::ScrollDown => self.model.next(),
+            MouseEventKind::ScrollUp => self.model.previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(hit) = self.ui.hit_test(mouse_event.column, mouse_event.row) else {
+                    return Ok(());
+                };
+                let now = Instant::now();
+                let double_click = self.last_click.is_some_and(|(time, last)| {
+                    last == hit && now.duration_since(time) <= DOUBLE_CLICK_INTERVAL
+                });
+                self.last_click = (!double_click).then_some((now, hit));
+                self.handle_click(hit, double_click)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_click(&mut self, hit: Hit, double_click: bool) -> Result<()> {
+        match hit {
+            Hit::Header(column) => self.model.sort_by_column(column),
+            Hit::Cell { row, column } => {
+                self.model.select(row);
+                self.model.set_active_column(column);
+                if double_click {
+                    match self.model.view_state() {
+                        ViewState::Main => self.spawn(Model::enter_table_view),
+                        ViewState::Table | ViewState::Query | ViewState::Report => {
+                            self.model.toggle_record()
+                        }
+                    }
+                }
+            }
+            Hit::Scrollbar { position, length } => self.model.select_fraction(position, length),
+        }
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        self.model.clear_message();
+        if self.model.prompt().is_some() {
+            return self.handle_prompt_key_event(key_event);
+        }
+        let Some(overlay) = self.model.overlay() else {
+            return self.handle_view_key_event(key_event);
+        };
+        match overlay {
+            Overlay::Picker { .. } => self.handle_picker_key_event(key_event),
+            Overlay::Editor { .. } => self.handle_editor_key_event(key_event),
+            Overlay::Filter(_) => self.handle_filter_key_event(key_event),
+            Overlay::Plan(_) => self.handle_plan_key_event(key_event),
+            Overlay::ColumnStats(_) => {
+                if let KeyCode::Esc | KeyCode::Char('q' | 'p') = key_event.code {
+                    self.model.close_column_stats();
+                }
+            }
+            Overlay::Json(_) => self.handle_json_key_event(key_event)?,
+            Overlay::Maintenance(_) => self.handle_maintenance_key_event(key_event),
+            Overlay::SchemaEditor(_) => self.handle_schema_editor_key_event(key_event),
+        }
+        Ok(())
+    }
+
+    /// Keys of the current view when no overlay is open.
+    fn handle_view_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        if self.model.is_loading() {
+            if let KeyEvent {
+                code: KeyCode::Esc, ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: event::KeyModifiers::CONTROL,
+                ..
+            } = key_event
+            {
+                self.cancel();
+                return Ok(());
+            }
+        }
+
         match key_event {
             KeyEvent {
                 code: KeyCode::Char('q') | KeyCode::Esc,
@@ -76,12 +376,12 @@ impl<D: Database> App<D> {
                 code: KeyCode::Char('l') | KeyCode::Right,
                 modifiers: event::KeyModifiers::NONE,
                 ..
-            } => self.model.switch_to_table_view().await?,
+            } => self.spawn(Model::enter_table_view),
             KeyEvent {
                 code: KeyCode::Char('h') | KeyCode::Left,
                 modifiers: event::KeyModifiers::NONE,
                 ..
-            } => self.model.switch_to_main_view().await?,
+            } => self.model.switch_to_main_view(),
             KeyEvent {
                 code: KeyCode::Char('s' | ' '),
                 modifiers: event::KeyModifiers::NONE,
@@ -102,8 +402,401 @@ impl<D: Database> App<D> {
                 modifiers: event::KeyModifiers::SHIFT,
                 ..
             } => self.model.toggle_column(),
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } if self.model.grouping().is_some() => self.spawn(|model| model.load_drill_down()),
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.toggle_record(),
+            KeyEvent {
+                code: KeyCode::PageDown,
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.next_page(),
+            KeyEvent {
+                code: KeyCode::PageUp,
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.previous_page(),
+            KeyEvent {
+                code: KeyCode::Char('g') | KeyCode::Home,
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.first(),
+            KeyEvent {
+                code: KeyCode::Char('G'),
+                modifiers: event::KeyModifiers::SHIFT,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::End,
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.last(),
+            KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.refresh(),
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.toggle_storage(),
+            KeyEvent {
+                code: KeyCode::Char('i'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.show_report(ReportKind::Info),
+            KeyEvent {
+                code: KeyCode::Char('m'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.open_maintenance(),
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.open_filter(),
+            KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.spawn(|model| model.load_group()),
+            KeyEvent {
+                code: KeyCode::Char('v'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.spawn(|model| model.load_pivot()),
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.spawn(|model| model.load_column_stats()),
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.spawn(|model| model.load_json()),
+            KeyEvent {
+                code: KeyCode::Char('T'),
+                modifiers: event::KeyModifiers::SHIFT,
+                ..
+            } => self.model.cycle_time_zone(),
+            KeyEvent {
+                code: KeyCode::Char('x'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.toggle_mark_column(),
+            KeyEvent {
+                code: KeyCode::Char('I'),
+                modifiers: event::KeyModifiers::SHIFT,
+                ..
+            } => self.spawn(|model| model.open_index_form()),
+            KeyEvent {
+                code: KeyCode::Char('t'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.spawn(|model| model.open_schema_editor()),
+            KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.spawn(|model| model.load_import_write()),
+            KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            } => self.model.open_editor(),
+            KeyEvent {
+                code: KeyCode::Char(':'),
+                ..
+            } => self.model.open_prompt(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_prompt_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        match key_event.code {
+            KeyCode::Esc => self.model.close_prompt(),
+            KeyCode::Backspace => self.model.pop_prompt(),
+            KeyCode::Char(c) => self.model.push_prompt(c),
+            KeyCode::Enter => {
+                let input = self.model.take_prompt().unwrap_or_default();
+                match input.parse::<Command>() {
+                    Ok(command) => self.execute(command)?,
+                    Err(err) => self.model.set_message(err.to_string()),
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_editor_key_event(&mut self, key_event: KeyEvent) {
+        if key_event.modifiers.contains(event::KeyModifiers::CONTROL) {
+            if key_event.code == KeyCode::Char('r') {
+                self.model.open_picker();
+            }
+            return;
+        }
+        if self.model.completion().is_some() {
+            match key_event.code {
+                KeyCode::Esc => return self.model.close_completion(),
+                KeyCode::Enter | KeyCode::Tab => return self.model.accept_completion(),
+                KeyCode::Up | KeyCode::BackTab => return self.model.completion_previous(),
+                KeyCode::Down => return self.model.completion_next(),
+                _ => {}
+            }
+        }
+        match key_event.code {
+            KeyCode::Esc => self.model.close_editor(),
+            KeyCode::Tab => self.model.open_completion(),
+            KeyCode::Enter => self.spawn(|model| {
+                let sql = model.submit_editor()?;
+                Some(model.load_query(&sql))
+            }),
+            KeyCode::Up => self.model.recall_previous(),
+            KeyCode::Down => self.model.recall_next(),
+            code => {
+                let Some(editor) = self.model.editor_mut() else {
+                    return;
+                };
+                match code {
+                    KeyCode::Char(c) => editor.insert(c),
+                    KeyCode::Backspace => editor.backspace(),
+                    KeyCode::Delete => editor.delete(),
+                    KeyCode::Left => editor.left(),
+                    KeyCode::Right => editor.right(),
+                    KeyCode::Home => editor.home(),
+                    KeyCode::End => editor.end(),
+                    _ => {}
+                }
+                self.model.update_completion();
+            }
+        }
+    }
+
+    fn handle_picker_key_event(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Esc => self.model.close_picker(),
+            KeyCode::Enter => self.model.choose_picker(),
+            KeyCode::Up => self.model.picker_previous(),
+            KeyCode::Down => self.model.picker_next(),
+            KeyCode::Backspace => self.model.pop_picker(),
+            KeyCode::Char(c) => self.model.push_picker(c),
+            _ => {}
+        }
+    }
+
+    fn handle_plan_key_event(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.model.close_plan(),
+            KeyCode::Tab | KeyCode::Char('b') => self.model.toggle_bytecode(),
+            KeyCode::Down | KeyCode::Char('j') => self.model.scroll_plan(1),
+            KeyCode::Up | KeyCode::Char('k') => self.model.scroll_plan(-1),
+            KeyCode::PageDown => self.model.scroll_plan(10),
+            KeyCode::PageUp => self.model.scroll_plan(-10),
+            _ => {}
+        }
+    }
+
+    fn handle_json_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        let Some(view) = self.model.json_mut() else {
+            return Ok(());
+        };
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q' | 'o') => self.model.close_json(),
+            KeyCode::Down | KeyCode::Char('j') => view.next(),
+            KeyCode::Up | KeyCode::Char('k') => view.previous(),
+            KeyCode::Right | KeyCode::Char('l') => view.expand(),
+            KeyCode::Left | KeyCode::Char('h') => view.collapse(),
+            KeyCode::Enter | KeyCode::Char(' ') => view.toggle(),
+            KeyCode::Char('y') => {
+                if let Some(node) = view.selected_node() {
+                    let path = node.path.clone();
+                    crossterm::execute!(io::stdout(), CopyToClipboard::to_clipboard_from(&path))?;
+                    self.model.set_message(format!("copied {path}"));
+                }
+            }
+            KeyCode::Char('f') => self.model.filter_json(),
+            KeyCode::Char('v') => self.spawn(|model| model.json_column_form()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_maintenance_key_event(&mut self, key_event: KeyEvent) {
+        match (self.model.maintenance(), key_event.code) {
+            (Some(MaintenanceView::Menu { .. }), KeyCode::Down | KeyCode::Char('j')) => {
+                self.model.maintenance_next()
+            }
+            (Some(MaintenanceView::Menu { .. }), KeyCode::Up | KeyCode::Char('k')) => {
+                self.model.maintenance_previous()
+            }
+            (Some(MaintenanceView::Menu { .. }), KeyCode::Enter) => self.model.choose_maintenance(),
+            (Some(MaintenanceView::Confirm(_)), KeyCode::Char('y')) => {
+                let progress = self.progress();
+                self.spawn(|model| model.load_maintenance(progress));
+            }
+            (_, KeyCode::Esc | KeyCode::Char('q' | 'n')) => self.model.close_maintenance(),
+            (Some(MaintenanceView::Done(_)), KeyCode::Enter) => self.model.close_maintenance(),
+            _ => {}
+        }
+    }
+
+    fn handle_schema_editor_key_event(&mut self, key_event: KeyEvent) {
+        if let Some(SchemaView::Form(_)) = self.model.schema_editor() {
+            return self.handle_schema_form_key_event(key_event);
+        }
+        match (self.model.schema_editor(), key_event.code) {
+            (Some(SchemaView::Menu { .. }), KeyCode::Down | KeyCode::Char('j')) => {
+                self.model.schema_editor_next()
+            }
+            (Some(SchemaView::Menu { .. }), KeyCode::Up | KeyCode::Char('k')) => {
+                self.model.schema_editor_previous()
+            }
+            (Some(SchemaView::Menu { .. }), KeyCode::Enter) => self.model.choose_schema_action(),
+            (Some(SchemaView::Review { .. }), KeyCode::Char('y')) => {
+                self.spawn(|model| model.load_schema_change())
+            }
+            (Some(SchemaView::Review { .. }), KeyCode::Char('n')) => {
+                self.model.back_schema_editor()
+            }
+            (_, KeyCode::Esc | KeyCode::Char('q')) => self.model.close_schema_editor(),
+            _ => {}
+        }
+    }
+
+    fn handle_schema_form_key_event(&mut self, key_event: KeyEvent) {
+        if key_event.code == KeyCode::Enter {
+            return self.spawn(|model| {
+                model.review_schema_change();
+                model.load_index_plans()
+            });
+        }
+        if key_event.code == KeyCode::Esc {
+            return self.model.close_schema_editor();
+        }
+        let Some(form) = self.model.schema_form_mut() else {
+            return;
+        };
+        if key_event.modifiers.contains(event::KeyModifiers::CONTROL) {
+            match key_event.code {
+                KeyCode::Char('n') => form.add_column(),
+                KeyCode::Char('d') => form.remove_column(),
+                _ => {}
+            }
+            return;
+        }
+        match key_event.code {
+            KeyCode::Tab | KeyCode::Down => form.next(),
+            KeyCode::BackTab | KeyCode::Up => form.previous(),
+            KeyCode::Left => form.cycle(-1),
+            KeyCode::Right => form.cycle(1),
+            KeyCode::Backspace => form.pop(),
+            KeyCode::Char(c) => form.push(c),
             _ => {}
         }
+    }
+
+    fn handle_filter_key_event(&mut self, key_event: KeyEvent) {
+        let control = key_event.modifiers.contains(event::KeyModifiers::CONTROL);
+        match key_event.code {
+            KeyCode::Esc => return self.model.close_filter(),
+            KeyCode::Enter => return self.apply_filter(false),
+            KeyCode::Char('s') if control => return self.apply_filter(true),
+            _ => {}
+        }
+        let Some(form) = self.model.filter_form_mut() else {
+            return;
+        };
+        if control {
+            match key_event.code {
+                KeyCode::Char('n') => form.add_condition(),
+                KeyCode::Char('d') => form.remove_condition(),
+                _ => {}
+            }
+            return;
+        }
+        match key_event.code {
+            KeyCode::Tab => form.next(),
+            KeyCode::BackTab => form.previous(),
+            KeyCode::Down => form.move_condition(1),
+            KeyCode::Up => form.move_condition(-1),
+            KeyCode::Left => form.cycle(-1),
+            KeyCode::Right => form.cycle(1),
+            KeyCode::Backspace => form.pop(),
+            KeyCode::Char(c) => form.push(c),
+            _ => {}
+        }
+    }
+
+    /// Reloads the table of the filter panel with its conditions, saving
+    /// them when `save` is set.
+    fn apply_filter(&mut self, save: bool) {
+        self.spawn(|model| {
+            let load = model.apply_filter(save);
+            Self::or_message(model, load)
+        });
+    }
+
+    fn execute(&mut self, command: Command) -> Result<()> {
+        let result = match command {
+            Command::Goto(row) => {
+                self.model.goto(row);
+                Ok(())
+            }
+            Command::Wrap(wrap) => {
+                self.model.set_wrap(wrap);
+                Ok(())
+            }
+            Command::Save(name) => self.model.save_query(&name),
+            Command::Unsave(name) => self.model.remove_query(&name),
+            Command::Explain => {
+                self.spawn(|model| {
+                    let load = model.load_plan();
+                    Self::or_message(model, load)
+                });
+                Ok(())
+            }
+            Command::Stats(path) => self.model.export_stats(&path),
+            Command::Watch(interval) => {
+                self.model.set_watch(interval);
+                Ok(())
+            }
+            Command::Backup { schema, path } => {
+                let progress = self.progress();
+                self.spawn(|model| Some(model.load_backup(schema, path, progress)));
+                Ok(())
+            }
+            Command::Import { path, table } => {
+                let table = table.unwrap_or_else(|| import::table_name(&path));
+                self.show_report(ReportKind::Import { path, table });
+                Ok(())
+            }
+            Command::Maintain(action) => {
+                self.model.confirm_maintenance(action);
+                Ok(())
+            }
+            Command::Check(check, table) => {
+                self.show_report(ReportKind::Check { check, table });
+                Ok(())
+            }
+            Command::TimeZone(zone) => {
+                self.model.set_time_zone(zone);
+                Ok(())
+            }
+            Command::TimeFormat { column, format } => self.model.set_time_format(&column, format),
+        };
+        if let Err(err) = result {
+            self.model.set_message(err.to_string());
+        }
         Ok(())
     }
 
@@ -112,7 +805,7 @@ impl<D: Database> App<D> {
     }
 
     fn init_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
-        crossterm::execute!(io::stdout(), EnterAlternateScreen)?;
+        crossterm::execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
         enable_raw_mode()?;
 
         let backend = CrosstermBackend::new(io::stdout());
@@ -125,7 +818,7 @@ impl<D: Database> App<D> {
 
     fn reset_terminal() -> Result<()> {
         disable_raw_mode()?;
-        crossterm::execute!(io::stdout(), LeaveAlternateScreen)?;
+        crossterm::execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
 
         Ok(())
     }
@@ -134,7 +827,10 @@ impl<D: Database> App<D> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::ViewState;
+    use crate::database::{
+        Check, ColumnStats, DatabaseInfo, JsonNode, Maintenance, QueryStats, ResultSet, Storage,
+        TableSchema, WhereClause,
+    };
 
     #[derive(Clone)]
     struct MockDb;
@@ -157,43 +853,169 @@ mod tests {
             Ok(Vec::new())
         }
 
-        async fn rows(&self, _: &str, _: &str) -> Result<Vec<Vec<serde_json::Value>>> {
+        async fn column_types(&self, _table: &str) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+
+        async fn rows(&self, _: &str, _: Option<&WhereClause>) -> Result<ResultSet> {
+            Ok(ResultSet::default())
+        }
+
+        async fn table_schema(&self, _table: &str) -> Result<TableSchema> {
+            Ok(TableSchema::default())
+        }
+
+        async fn count(&self, table: &str) -> Result<usize> {
+            Ok(self.rows(table, None).await?.rows.len())
+        }
+
+        async fn position(
+            &self,
+            _: &str,
+            _: &[serde_json::Value],
+            _: &str,
+        ) -> Result<Option<usize>> {
+            Ok(Some(2))
+        }
+
+        async fn head(&self, _: &str, _: usize) -> Result<ResultSet> {
+            Ok(ResultSet::default())
+        }
+
+        async fn query_values(&self, _: &str) -> Result<ResultSet> {
+            Ok(ResultSet::default())
+        }
+
+        async fn query(&self, _: &str) -> Result<ResultSet> {
+            Ok(ResultSet::default())
+        }
+
+        async fn reads_only(&self, _: &str) -> Result<bool> {
+            Ok(true)
+        }
+
+        fn interrupt(&self) {}
+
+        fn stats(&self) -> Vec<QueryStats> {
+            Vec::new()
+        }
+
+        async fn info(&self) -> Result<DatabaseInfo> {
+            Ok(DatabaseInfo::default())
+        }
+
+        async fn column_stats(&self, _table: &str, _column: &str) -> Result<ColumnStats> {
+            Ok(ColumnStats::default())
+        }
+
+        async fn json_tree(&self, _json: &str) -> Result<Vec<JsonNode>> {
+            Ok(Vec::new())
+        }
+
+        async fn check(
+            &self,
+            _check: Check,
+            _table: Option<&str>,
+            _report: impl FnMut(String) + Send,
+        ) -> Result<usize> {
+            Ok(0)
+        }
+
+        async fn backup(
+            &self,
+            _schema: &str,
+            _path: &str,
+            _progress: impl FnMut(u64, u64) + Send,
+        ) -> Result<u64> {
+            Ok(0)
+        }
+
+        async fn version(&self) -> Result<Version> {
+            Ok(Version::default())
+        }
+
+        async fn create_table(
+            &self,
+            _table: &str,
+            _columns: &[(String, &str)],
+            rows: &[Vec<serde_json::Value>],
+        ) -> Result<u64> {
+            Ok(rows.len() as u64)
+        }
+
+        async fn insert(
+            &self,
+            _table: &str,
+            _columns: &[String],
+            rows: &[Vec<serde_json::Value>],
+        ) -> Result<u64> {
+            Ok(rows.len() as u64)
+        }
+
+        async fn execute(&self, _statements: &[String]) -> Result<()> {
+            Ok(())
+        }
+
+        async fn explain_with(
+            &self,
+            _statements: &[String],
+            _sql: &str,
+            _params: &[serde_json::Value],
+        ) -> Result<ResultSet> {
+            Ok(ResultSet::default())
+        }
+
+        fn is_read_only(&self) -> bool {
+            false
+        }
+
+        async fn maintain(&self, _action: &Maintenance) -> Result<DatabaseInfo> {
+            Ok(DatabaseInfo::default())
+        }
+
+        async fn storage(&self) -> Result<Vec<Storage>> {
             Ok(Vec::new())
         }
     }
 
+    impl<D: Database> App<D> {
+        /// Waits for the running background task and applies its result.
+        async fn settle(&mut self) {
+            while self.model.is_loading() {
+                let message = self.receiver.recv().await.unwrap();
+                self.handle_message(message);
+            }
+        }
+    }
+
     #[tokio::test]
     async fn handle_key_events() {
         let db = MockDb::new();
-        let mut app = App::new(db).await.unwrap();
+        let mut app = App::new(db, &Startup::default()).await.unwrap();
 
         app.handle_key_event(KeyEvent::new(KeyCode::Char('q'), event::KeyModifiers::NONE))
-            .await
             .unwrap();
         assert!(app.exit);
 
         app.handle_key_event(KeyEvent::new(KeyCode::Char('j'), event::KeyModifiers::NONE))
-            .await
             .unwrap();
         assert_eq!(app.model.state().selected().unwrap(), 1);
 
         app.handle_key_event(KeyEvent::new(KeyCode::Char('k'), event::KeyModifiers::NONE))
-            .await
             .unwrap();
         assert_eq!(app.model.state().selected().unwrap(), 0);
 
         app.handle_key_event(KeyEvent::new(KeyCode::Char('l'), event::KeyModifiers::NONE))
-            .await
             .unwrap();
+        assert!(app.model.is_loading());
+        app.settle().await;
         assert_eq!(app.model.view_state(), ViewState::Table);
 
         app.handle_key_event(KeyEvent::new(KeyCode::Char('h'), event::KeyModifiers::NONE))
-            .await
             .unwrap();
         assert_eq!(app.model.view_state(), ViewState::Main);
 
         app.handle_key_event(KeyEvent::new(KeyCode::Char('s'), event::KeyModifiers::NONE))
-            .await
             .unwrap();
         assert!(app.model.is_schema_enabled());
 
@@ -201,7 +1023,6 @@ mod tests {
             KeyCode::Char('S'),
             event::KeyModifiers::SHIFT,
         ))
-        .await
         .unwrap();
         assert!(app.model.is_column_enabled());
 
@@ -209,7 +1030,6 @@ mod tests {
             KeyCode::Char('L'),
             event::KeyModifiers::SHIFT,
         ))
-        .await
         .unwrap();
         assert_eq!(app.model.active_column(), 1);
 
@@ -217,8 +1037,223 @@ mod tests {
             KeyCode::Char('H'),
             event::KeyModifiers::SHIFT,
         ))
-        .await
         .unwrap();
         assert_eq!(app.model.active_column(), 0);
     }
+
+    #[tokio::test]
+    async fn report_busy_loads() {
+        let mut app = App::new(MockDb::new(), &Startup::default()).await.unwrap();
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('l'), event::KeyModifiers::NONE))
+            .unwrap();
+        assert!(app.model.is_loading());
+        for c in ":check".chars() {
+            app.handle_key_event(KeyEvent::new(KeyCode::Char(c), event::KeyModifiers::NONE))
+                .unwrap();
+        }
+        app.handle_key_event(KeyEvent::new(KeyCode::Enter, event::KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.model.message(), Some("busy, press Esc to cancel"));
+        app.settle().await;
+        assert_eq!(app.model.view_state(), ViewState::Table);
+        assert!(app.model.report().is_none());
+    }
+
+    #[tokio::test]
+    async fn poll_changes_in_background() {
+        let mut app = App::new(MockDb::new(), &Startup::default())
+            .await
+            .unwrap()
+            .with_watch(Some(WATCH_INTERVAL));
+
+        app.poll_changes();
+        assert!(app.polling);
+        assert!(!app.model.is_loading());
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('j'), event::KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.model.state().selected().unwrap(), 1);
+
+        let message = app.receiver.recv().await.unwrap();
+        app.handle_message(message);
+        assert!(!app.polling);
+        assert!(!app.model.is_loading());
+    }
+
+    #[tokio::test]
+    async fn handle_clicks() {
+        let db = MockDb::new();
+        let mut app = App::new(db, &Startup::default()).await.unwrap();
+
+        app.handle_click(Hit::Cell { row: 1, column: 0 }, false)
+            .unwrap();
+        assert_eq!(app.model.state().selected().unwrap(), 1);
+        assert_eq!(app.model.view_state(), ViewState::Main);
+
+        app.handle_click(
+            Hit::Scrollbar {
+                position: 0,
+                length: 10,
+            },
+            false,
+        )
+        .unwrap();
+        assert_eq!(app.model.state().selected().unwrap(), 0);
+
+        app.handle_click(
+            Hit::Scrollbar {
+                position: 9,
+                length: 10,
+            },
+            false,
+        )
+        .unwrap();
+        assert_eq!(app.model.state().selected().unwrap(), 1);
+
+        app.handle_click(Hit::Header(1), false).unwrap();
+        assert!(app.model.sort().is_some());
+
+        app.handle_click(Hit::Cell { row: 0, column: 0 }, true)
+            .unwrap();
+        app.settle().await;
+        assert_eq!(app.model.view_state(), ViewState::Table);
+    }
+
+    #[tokio::test]
+    async fn handle_navigation_key_events() {
+        let db = MockDb::new();
+        let mut app = App::new(db, &Startup::default())
+            .await
+            .unwrap()
+            .with_wrap(false);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::End, event::KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.model.state().selected().unwrap(), 1);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('j'), event::KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.model.state().selected().unwrap(), 1);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Home, event::KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.model.state().selected().unwrap(), 0);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::PageDown, event::KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.model.state().selected().unwrap(), 1);
+
+        for code in [KeyCode::Char(':'), KeyCode::Char('1'), KeyCode::Enter] {
+            app.handle_key_event(KeyEvent::new(code, event::KeyModifiers::NONE))
+                .unwrap();
+        }
+        assert_eq!(app.model.state().selected().unwrap(), 0);
+        assert!(app.model.prompt().is_none());
+
+        for code in [KeyCode::Char(':'), KeyCode::Char('x'), KeyCode::Enter] {
+            app.handle_key_event(KeyEvent::new(code, event::KeyModifiers::NONE))
+                .unwrap();
+        }
+        assert_eq!(app.model.message(), Some("unknown command: x"));
+    }
+
+    #[tokio::test]
+    async fn startup_in_table_view() {
+        let startup = Startup {
+            table: Some("test2".into()),
+            row: Some(1),
+            ..Startup::default()
+        };
+        let app = App::new(MockDb::new(), &startup).await.unwrap();
+        assert_eq!(app.model.view_state(), ViewState::Table);
+        assert_eq!(app.model.selected_table(), Some("test2"));
+        assert_eq!(app.model.state().selected(), Some(0));
+
+        let startup = Startup {
+            query: Some("SELECT 1".into()),
+            ..Startup::default()
+        };
+        let app = App::new(MockDb::new(), &startup).await.unwrap();
+        assert_eq!(app.model.view_state(), ViewState::Query);
+        assert_eq!(app.model.query(), Some("SELECT 1"));
+
+        let startup = Startup {
+            table: Some("missing".into()),
+            ..Startup::default()
+        };
+        assert!(App::new(MockDb::new(), &startup).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn run_and_save_queries() {
+        let db = MockDb::new();
+        let mut app = App::new(db, &Startup::default()).await.unwrap();
+        let keys = |text: &str| {
+            text.chars()
+                .map(|c| KeyEvent::new(KeyCode::Char(c), event::KeyModifiers::NONE))
+                .collect::<Vec<_>>()
+        };
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('e'), event::KeyModifiers::NONE))
+            .unwrap();
+        for key in keys("SELECT 1") {
+            app.handle_key_event(key).unwrap();
+        }
+        assert_eq!(app.model.editor().unwrap().text(), "SELECT 1");
+        app.handle_key_event(KeyEvent::new(KeyCode::Enter, event::KeyModifiers::NONE))
+            .unwrap();
+        app.settle().await;
+        assert_eq!(app.model.view_state(), ViewState::Query);
+        assert_eq!(app.model.query(), Some("SELECT 1"));
+
+        for key in keys(":save one") {
+            app.handle_key_event(key).unwrap();
+        }
+        app.handle_key_event(KeyEvent::new(KeyCode::Enter, event::KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.model.tables().len(), 3);
+        assert_eq!(app.model.tables()[2].schema(), "SELECT 1");
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('e'), event::KeyModifiers::NONE))
+            .unwrap();
+        app.handle_key_event(KeyEvent::new(
+            KeyCode::Char('r'),
+            event::KeyModifiers::CONTROL,
+        ))
+        .unwrap();
+        assert!(app.model.picker().is_some());
+        for key in keys("s1") {
+            app.handle_key_event(key).unwrap();
+        }
+        assert_eq!(app.model.picker_matches(), vec!["SELECT 1"]);
+        app.handle_key_event(KeyEvent::new(KeyCode::Enter, event::KeyModifiers::NONE))
+            .unwrap();
+        assert!(app.model.picker().is_none());
+        assert_eq!(app.model.editor().unwrap().text(), "SELECT 1");
+    }
+
+    #[tokio::test]
+    async fn explain_selected_table() {
+        let db = MockDb::new();
+        let mut app = App::new(db, &Startup::default()).await.unwrap();
+
+        for c in ":explain".chars() {
+            app.handle_key_event(KeyEvent::new(KeyCode::Char(c), event::KeyModifiers::NONE))
+                .unwrap();
+        }
+        app.handle_key_event(KeyEvent::new(KeyCode::Enter, event::KeyModifiers::NONE))
+            .unwrap();
+        app.settle().await;
+        let plan = app.model.plan().unwrap();
+        assert_eq!(plan.plan().sql, "SELECT * FROM \"test\"");
+        assert!(!plan.is_bytecode());
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Tab, event::KeyModifiers::NONE))
+            .unwrap();
+        assert!(app.model.plan().unwrap().is_bytecode());
+        app.handle_key_event(KeyEvent::new(KeyCode::Esc, event::KeyModifiers::NONE))
+            .unwrap();
+        assert!(app.model.plan().is_none());
+        assert!(!app.exit);
+    }
 }
@@ -1,4 +1,19 @@
-use clap::Parser;
+use std::{
+    io::{IsTerminal, Write},
+    process::ExitCode,
+    time::Duration,
+};
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+use crate::{
+    app::Startup,
+    command::parse_interval,
+    database::{Check, Database, ResultSet, Storage},
+    import::{self, Import, ImportFormat},
+    output::{self, Format},
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "sqliters", long_about = None)]
@@ -6,10 +21,367 @@ pub struct Args {
     /// Input sqlite file
     #[clap(long, short)]
     pub input: String,
+
+    /// Stop at the first and last row instead of wrapping around
+    #[clap(long)]
+    pub no_wrap: bool,
+
+    /// Open this table on startup
+    #[clap(long, group = "open")]
+    pub table: Option<String>,
+
+    /// Open the result of this SQL statement on startup
+    #[clap(long, group = "open")]
+    pub query: Option<String>,
+
+    /// Select this row (1-based) on startup
+    #[clap(long)]
+    pub row: Option<usize>,
+
+    /// Select the first row of --table or --query matching this SQL
+    /// condition on startup
+    #[clap(long = "where", requires = "open", value_name = "CONDITION")]
+    pub condition: Option<String>,
+
+    /// Reload the shown data when another process changes the database,
+    /// polling every SECONDS (2 by default)
+    #[clap(long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "2", value_parser = parse_interval)]
+    pub watch: Option<Duration>,
+
+    /// Open the database read-only, which disables maintenance actions
+    #[clap(long)]
+    pub read_only: bool,
+
+    /// Output format of non-interactive commands
+    #[clap(long, short, value_enum, default_value_t, global = true)]
+    pub format: Format,
+
+    /// Run a command and print its result instead of starting the UI
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum Commands {
+    /// List tables
+    Tables,
+    /// Print the CREATE statements of a table and its indexes
+    Schema { table: String },
+    /// Print the number of rows in a table
+    Count { table: String },
+    /// Print the first rows of a table
+    Head {
+        table: String,
+        /// Number of rows
+        #[clap(short, default_value_t = 10)]
+        n: usize,
+    },
+    /// Run an SQL statement and print its result
+    Query { sql: String },
+    /// Print properties of the database file and the SQLite library
+    Info,
+    /// Print the disk usage of every table and index
+    Storage,
+    /// Check the database, or one table, for corruption or foreign key
+    /// violations. Exits with status 1 when problems are found
+    Check {
+        table: Option<String>,
+        #[clap(long, value_enum, default_value = "integrity")]
+        kind: Check,
+    },
+    /// Copy the database to a new file with the online backup API, safe
+    /// while other processes write to it
+    Backup {
+        path: String,
+        /// Back up this attached schema instead of the main database
+        #[clap(long, default_value = "main")]
+        schema: String,
+    },
+    /// Import a CSV, JSON or NDJSON file into a new table, or append it to
+    /// an existing one, in one transaction
+    Import {
+        path: String,
+        /// Table to create or append to, named after the file by default
+        #[clap(long)]
+        table: Option<String>,
+        /// Format of the file, detected from its extension by default
+        #[clap(long, value_enum)]
+        kind: Option<ImportFormat>,
+        /// Field delimiter of a CSV file, sniffed by default
+        #[clap(long)]
+        delimiter: Option<char>,
+        /// Whether the first CSV record holds column names, detected by
+        /// default
+        #[clap(long)]
+        header: Option<bool>,
+    },
 }
 
 impl Args {
     pub fn from() -> Args {
         Args::parse()
     }
+
+    pub fn startup(&self) -> Startup {
+        Startup {
+            table: self.table.clone(),
+            query: self.query.clone(),
+            row: self.row,
+            condition: self.condition.clone(),
+        }
+    }
+}
+
+impl Commands {
+    pub async fn run<D: Database>(
+        &self,
+        db: &D,
+        format: Format,
+        out: &mut impl Write,
+    ) -> Result<ExitCode> {
+        let mut code = ExitCode::SUCCESS;
+        let result = match self {
+            Commands::Tables => single_column("name", db.tables().await?),
+            Commands::Schema { table } => single_column("sql", vec![db.schema(table).await?]),
+            Commands::Count { table } => ResultSet {
+                columns: vec!["count".to_string()],
+                rows: vec![vec![db.count(table).await?.into()]],
+                stats: None,
+            },
+            Commands::Head { table, n } => db.head(table, *n).await?,
+            Commands::Query { sql } => db.query_values(sql).await?,
+            Commands::Info => db.info().await?.to_result_set(),
+            Commands::Storage => Storage::to_result_set(&db.storage().await?),
+            Commands::Check { table, kind } => {
+                let mut problems = Vec::new();
+                db.check(*kind, table.as_deref(), |problem| problems.push(problem))
+                    .await?;
+                if problems.is_empty() {
+                    problems.push("ok".to_string());
+                } else {
+                    code = ExitCode::FAILURE;
+                }
+                single_column("problem", problems)
+            }
+            Commands::Backup { path, schema } => {
+                let terminal = std::io::stderr().is_terminal();
+                let pages = db
+                    .backup(schema, path, |copied, total| {
+                        if terminal {
+                            eprint!("\r{copied} of {total} pages");
+                        }
+                    })
+                    .await?;
+                if terminal {
+                    eprintln!();
+                }
+                ResultSet {
+                    columns: ["schema", "path", "pages"].map(String::from).to_vec(),
+                    rows: vec![vec![
+                        schema.as_str().into(),
+                        path.as_str().into(),
+                        pages.into(),
+                    ]],
+                    stats: None,
+                }
+            }
+            Commands::Import {
+                path,
+                table,
+                kind,
+                delimiter,
+                header,
+            } => {
+                let options = import::Options {
+                    format: *kind,
+                    delimiter: *delimiter,
+                    header: *header,
+                };
+                let table = table.clone().unwrap_or_else(|| import::table_name(path));
+                let (rows, created) = Import::read(path, &options)?.write(db, &table).await?;
+                ResultSet {
+                    columns: ["table", "rows", "created"].map(String::from).to_vec(),
+                    rows: vec![vec![table.into(), rows.into(), created.into()]],
+                    stats: None,
+                }
+            }
+        };
+        output::write(out, &result, format)?;
+        Ok(code)
+    }
+}
+
+fn single_column(name: &str, values: Vec<String>) -> ResultSet {
+    ResultSet {
+        columns: vec![name.to_string()],
+        rows: values.into_iter().map(|value| vec![value.into()]).collect(),
+        stats: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SqliteDb;
+
+    #[test]
+    fn parse_commands() {
+        let args = Args::try_parse_from(["sqliters", "-i", "db.sqlite"]).unwrap();
+        assert_eq!(args.command, None);
+        assert_eq!(args.format, Format::Table);
+
+        let args =
+            Args::try_parse_from(["sqliters", "-i", "db.sqlite", "head", "users", "-n", "5"])
+                .unwrap();
+        assert_eq!(
+            args.command,
+            Some(Commands::Head {
+                table: "users".into(),
+                n: 5
+            })
+        );
+
+        let args = Args::try_parse_from([
+            "sqliters",
+            "-i",
+            "db.sqlite",
+            "query",
+            "SELECT 1",
+            "--format",
+            "json",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.command,
+            Some(Commands::Query {
+                sql: "SELECT 1".into()
+            })
+        );
+        assert_eq!(args.format, Format::Json);
+
+        let args = Args::try_parse_from([
+            "sqliters",
+            "-i",
+            "db.sqlite",
+            "--table",
+            "users",
+            "--where",
+            "id = 3",
+        ])
+        .unwrap();
+        assert_eq!(args.startup().table.as_deref(), Some("users"));
+        assert_eq!(args.startup().condition.as_deref(), Some("id = 3"));
+        assert!(
+            Args::try_parse_from(["sqliters", "-i", "db.sqlite", "--where", "id = 3"]).is_err()
+        );
+        let args = Args::try_parse_from([
+            "sqliters",
+            "-i",
+            "db.sqlite",
+            "--query",
+            "SELECT * FROM users",
+            "--where",
+            "id = 3",
+        ])
+        .unwrap();
+        assert_eq!(args.startup().condition.as_deref(), Some("id = 3"));
+        assert!(Args::try_parse_from([
+            "sqliters",
+            "-i",
+            "db.sqlite",
+            "--table",
+            "users",
+            "--query",
+            "SELECT 1"
+        ])
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn run_commands() {
+        let db = SqliteDb::memory().await.unwrap();
+        db.query("CREATE TABLE users (id INTEGER, name TEXT)")
+            .await
+            .unwrap();
+        db.query("INSERT INTO users VALUES (1, 'alice'), (2, 'bob')")
+            .await
+            .unwrap();
+
+        let run = |command: Commands| {
+            let db = db.clone();
+            async move {
+                let mut out = Vec::new();
+                let code = command.run(&db, Format::Csv, &mut out).await.unwrap();
+                (String::from_utf8(out).unwrap(), code)
+            }
+        };
+
+        assert_eq!(run(Commands::Tables).await.0, "name\nusers\n");
+        assert_eq!(
+            run(Commands::Count {
+                table: "users".into()
+            })
+            .await
+            .0,
+            "count\n2\n"
+        );
+        assert_eq!(
+            run(Commands::Head {
+                table: "users".into(),
+                n: 1
+            })
+            .await
+            .0,
+            "id,name\n1,alice\n"
+        );
+        assert_eq!(
+            run(Commands::Query {
+                sql: "SELECT name FROM users ORDER BY id DESC".into()
+            })
+            .await
+            .0,
+            "name\nbob\nalice\n"
+        );
+        let query = Commands::Query {
+            sql: "SELECT id, name, NULL AS team FROM users WHERE id = 1".into(),
+        };
+        let mut out = Vec::new();
+        query.run(&db, Format::Json, &mut out).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([{"id": 1, "name": "alice", "team": null}])
+        );
+        assert_eq!(run(query).await.0, "id,name,team\n1,alice,\n");
+
+        let check = |kind| Commands::Check { table: None, kind };
+        assert_eq!(
+            run(check(Check::Integrity)).await,
+            ("problem\nok\n".to_string(), ExitCode::SUCCESS)
+        );
+        db.query("CREATE TABLE teams (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        db.query("CREATE TABLE orders (team_id REFERENCES teams (id))")
+            .await
+            .unwrap();
+        db.query("PRAGMA foreign_keys = OFF").await.unwrap();
+        db.query("INSERT INTO orders VALUES (3)").await.unwrap();
+        let (out, code) = run(check(Check::ForeignKeys)).await;
+        assert_eq!(code, ExitCode::FAILURE);
+        assert_eq!(out.lines().count(), 2);
+
+        let path = std::env::temp_dir().join(format!("sqliters-import-{}.csv", std::process::id()));
+        std::fs::write(&path, "id;name\n3;carol\n").unwrap();
+        let import = Commands::Import {
+            path: path.to_str().unwrap().into(),
+            table: Some("users".into()),
+            kind: None,
+            delimiter: None,
+            header: None,
+        };
+        assert_eq!(run(import).await.0, "table,rows,created\nusers,1,false\n");
+        assert_eq!(db.count("users").await.unwrap(), 3);
+        let _ = std::fs::remove_file(path);
+    }
 }
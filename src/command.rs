@@ -0,0 +1,233 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{bail, Error, Result};
+
+use crate::{
+    app::WATCH_INTERVAL,
+    database::{Check, Maintenance},
+    datetime::{TimeFormat, Zone},
+};
+
+/// Command entered in the `:` prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Goto(usize),
+    Wrap(bool),
+    Save(String),
+    Unsave(String),
+    Explain,
+    /// Exports the statement statistics of the session to a file.
+    Stats(String),
+    /// Runs a consistency check on the database or one table.
+    Check(Check, Option<String>),
+    /// Polls the database for changes every interval, `None` stops.
+    Watch(Option<Duration>),
+    /// Copies a schema of the database to a new file.
+    Backup {
+        schema: String,
+        path: String,
+    },
+    /// Previews a CSV, JSON or NDJSON file before importing it into a
+    /// table, by default named after the file.
+    Import {
+        path: String,
+        table: Option<String>,
+    },
+    /// Asks to confirm a maintenance action.
+    Maintain(Maintenance),
+    /// Shows timestamps in a zone, `None` as stored.
+    TimeZone(Option<Zone>),
+    /// Sets how a column of the open table stores timestamps, `None`
+    /// detects it.
+    TimeFormat {
+        column: String,
+        format: Option<TimeFormat>,
+    },
+}
+
+impl FromStr for Command {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let input = input.trim();
+        if let Ok(row) = input.parse::<usize>() {
+            return Ok(Command::Goto(row));
+        }
+
+        let words: Vec<&str> = input.split_whitespace().collect();
+        match words.as_slice() {
+            ["set", "wrap"] => Ok(Command::Wrap(true)),
+            ["set", "nowrap"] => Ok(Command::Wrap(false)),
+            ["save", name] => Ok(Command::Save(name.to_string())),
+            ["unsave", name] => Ok(Command::Unsave(name.to_string())),
+            ["explain"] => Ok(Command::Explain),
+            ["stats", path] => Ok(Command::Stats(path.to_string())),
+            ["watch"] => Ok(Command::Watch(Some(WATCH_INTERVAL))),
+            ["watch", "off"] => Ok(Command::Watch(None)),
+            ["watch", seconds] => Ok(Command::Watch(Some(parse_interval(seconds)?))),
+            ["backup", path] => Ok(Command::Backup {
+                schema: "main".to_string(),
+                path: path.to_string(),
+            }),
+            ["backup", schema, path] => Ok(Command::Backup {
+                schema: schema.to_string(),
+                path: path.to_string(),
+            }),
+            ["import", path, table @ ..] if table.len() <= 1 => Ok(Command::Import {
+                path: path.to_string(),
+                table: table.first().map(|table| table.to_string()),
+            }),
+            ["time", "local"] => Ok(Command::TimeZone(Some(Zone::Local))),
+            ["time", "utc"] => Ok(Command::TimeZone(Some(Zone::Utc))),
+            ["time", "off"] => Ok(Command::TimeZone(None)),
+            ["time", column, format] => Ok(Command::TimeFormat {
+                column: column.to_string(),
+                format: match *format {
+                    "auto" => None,
+                    format => Some(format.parse()?),
+                },
+            }),
+            ["vacuum"] => Ok(Command::Maintain(Maintenance::Vacuum)),
+            ["vacuum", "into", _, ..] => {
+                let path = input["vacuum".len()..].trim_start()["into".len()..].trim();
+                Ok(Command::Maintain(Maintenance::VacuumInto(path.to_string())))
+            }
+            ["analyze"] => Ok(Command::Maintain(Maintenance::Analyze)),
+            ["reindex"] => Ok(Command::Maintain(Maintenance::Reindex)),
+            ["optimize"] => Ok(Command::Maintain(Maintenance::Optimize)),
+            ["checkpoint"] => Ok(Command::Maintain(Maintenance::Checkpoint)),
+            ["check", rest @ ..] if rest.len() <= 2 => {
+                let (check, table) = match rest {
+                    [] => (Check::Integrity, None),
+                    ["integrity", table @ ..] => (Check::Integrity, table.first()),
+                    ["quick", table @ ..] => (Check::Quick, table.first()),
+                    ["fk", table @ ..] => (Check::ForeignKeys, table.first()),
+                    [table] => (Check::Integrity, Some(table)),
+                    _ => bail!("usage: check [integrity|quick|fk] [TABLE]"),
+                };
+                Ok(Command::Check(check, table.map(|table| table.to_string())))
+            }
+            _ => bail!("unknown command: {input}"),
+        }
+    }
+}
+
+/// Parses a positive number of seconds, e.g. `0.5`.
+pub fn parse_interval(seconds: &str) -> Result<Duration> {
+    match seconds.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(Duration::from_secs_f64(seconds)),
+        _ => bail!("invalid interval: {seconds}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!("42".parse::<Command>().unwrap(), Command::Goto(42));
+        assert_eq!(" 7 ".parse::<Command>().unwrap(), Command::Goto(7));
+        assert_eq!("set wrap".parse::<Command>().unwrap(), Command::Wrap(true));
+        assert_eq!(
+            "set  nowrap".parse::<Command>().unwrap(),
+            Command::Wrap(false)
+        );
+        assert_eq!(
+            "save users".parse::<Command>().unwrap(),
+            Command::Save("users".into())
+        );
+        assert_eq!(
+            "unsave users".parse::<Command>().unwrap(),
+            Command::Unsave("users".into())
+        );
+        assert_eq!("explain".parse::<Command>().unwrap(), Command::Explain);
+        assert_eq!(
+            "stats log.csv".parse::<Command>().unwrap(),
+            Command::Stats("log.csv".into())
+        );
+        assert_eq!(
+            "check".parse::<Command>().unwrap(),
+            Command::Check(Check::Integrity, None)
+        );
+        assert_eq!(
+            "check fk orders".parse::<Command>().unwrap(),
+            Command::Check(Check::ForeignKeys, Some("orders".into()))
+        );
+        assert_eq!(
+            "check users".parse::<Command>().unwrap(),
+            Command::Check(Check::Integrity, Some("users".into()))
+        );
+        assert!("check users orders".parse::<Command>().is_err());
+        assert_eq!(
+            "watch".parse::<Command>().unwrap(),
+            Command::Watch(Some(WATCH_INTERVAL))
+        );
+        assert_eq!(
+            "watch 0.5".parse::<Command>().unwrap(),
+            Command::Watch(Some(Duration::from_millis(500)))
+        );
+        assert_eq!(
+            "watch off".parse::<Command>().unwrap(),
+            Command::Watch(None)
+        );
+        assert!("watch 0".parse::<Command>().is_err());
+        assert_eq!(
+            "backup aux copy.db".parse::<Command>().unwrap(),
+            Command::Backup {
+                schema: "aux".into(),
+                path: "copy.db".into()
+            }
+        );
+        assert_eq!(
+            "import users.csv".parse::<Command>().unwrap(),
+            Command::Import {
+                path: "users.csv".into(),
+                table: None
+            }
+        );
+        assert_eq!(
+            "import users.json people".parse::<Command>().unwrap(),
+            Command::Import {
+                path: "users.json".into(),
+                table: Some("people".into())
+            }
+        );
+        assert!("import".parse::<Command>().is_err());
+        assert_eq!(
+            "vacuum".parse::<Command>().unwrap(),
+            Command::Maintain(Maintenance::Vacuum)
+        );
+        assert_eq!(
+            "vacuum into  /tmp/my copy.db ".parse::<Command>().unwrap(),
+            Command::Maintain(Maintenance::VacuumInto("/tmp/my copy.db".into()))
+        );
+        assert!("vacuum into".parse::<Command>().is_err());
+        assert_eq!(
+            "time utc".parse::<Command>().unwrap(),
+            Command::TimeZone(Some(Zone::Utc))
+        );
+        assert_eq!(
+            "time off".parse::<Command>().unwrap(),
+            Command::TimeZone(None)
+        );
+        assert_eq!(
+            "time created_at ms".parse::<Command>().unwrap(),
+            Command::TimeFormat {
+                column: "created_at".into(),
+                format: Some(TimeFormat::Millis)
+            }
+        );
+        assert_eq!(
+            "time created_at auto".parse::<Command>().unwrap(),
+            Command::TimeFormat {
+                column: "created_at".into(),
+                format: None
+            }
+        );
+        assert!("time created_at days".parse::<Command>().is_err());
+        assert!("save".parse::<Command>().is_err());
+        assert!("-1".parse::<Command>().is_err());
+        assert!("bogus".parse::<Command>().is_err());
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt;
+
+/// An SQL identifier such as a table or column name.
+///
+/// Formats as a double-quoted identifier with embedded quotes doubled, so
+/// names with spaces, quotes, reserved words or unicode are safe to splice
+/// into a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ident<'a>(&'a str);
+
+impl<'a> Ident<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Self(name)
+    }
+}
+
+impl fmt::Display for Ident<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.0.replace('"', "\"\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_identifiers() {
+        assert_eq!(Ident::new("users").to_string(), r#""users""#);
+        assert_eq!(Ident::new("order").to_string(), r#""order""#);
+        assert_eq!(Ident::new("my table").to_string(), r#""my table""#);
+        assert_eq!(Ident::new(r#"a"b"#).to_string(), r#""a""b""#);
+        assert_eq!(Ident::new(r#"""#).to_string(), r#""""""#);
+        assert_eq!(Ident::new("tábla").to_string(), r#""tábla""#);
+        assert_eq!(Ident::new("").to_string(), r#""""#);
+    }
+}
@@ -1,16 +1,556 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicPtr},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
 use anyhow::Result;
+use libsqlite3_sys::sqlite3;
+use serde_json::Value;
 use sqlx::SqlitePool;
 
+mod ident;
+mod pages;
 mod sqlite;
 
+pub use ident::Ident;
+
 #[derive(Debug, Clone)]
 pub struct SqliteDb {
     pool: SqlitePool,
+    handle: Arc<AtomicPtr<sqlite3>>,
+    /// Set by [`Database::interrupt`] to stop a running backup, which runs
+    /// no statement to interrupt.
+    cancelled: Arc<AtomicBool>,
+    /// Statistics of the last [`STATS_LOG_SIZE`] statements run through
+    /// [`Database::query`], oldest first.
+    log: Arc<Mutex<VecDeque<QueryStats>>>,
+    read_only: bool,
+}
+
+/// Statements kept in the statistics log of a session.
+const STATS_LOG_SIZE: usize = 1000;
+
+/// Column names and rows returned by a statement.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Statistics of the statement that returned the rows, `None` for rows
+    /// that were not read by a logged statement.
+    pub stats: Option<QueryStats>,
+}
+
+/// `WHERE` condition with `?` placeholders and the values bound to them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WhereClause {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+impl WhereClause {
+    /// Query of the rows of `table` shown in table view, narrowed by
+    /// `filter` when given.
+    pub fn select(table: &str, filter: Option<&Self>) -> String {
+        match filter {
+            Some(filter) => format!("SELECT * FROM {} WHERE {}", Ident::new(table), filter.sql),
+            None => format!("SELECT * FROM {}", Ident::new(table)),
+        }
+    }
+}
+
+/// Execution statistics of a statement, with the SQLite counters summed
+/// over the statements it consists of.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryStats {
+    pub sql: String,
+    pub elapsed: Duration,
+    pub rows_returned: usize,
+    pub rows_changed: u64,
+    pub vm_steps: u64,
+    pub fullscan_steps: u64,
+    pub sorts: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+impl QueryStats {
+    const COLUMNS: [&str; 9] = [
+        "sql",
+        "elapsed_ms",
+        "rows_returned",
+        "rows_changed",
+        "vm_steps",
+        "fullscan_steps",
+        "sorts",
+        "cache_hits",
+        "cache_misses",
+    ];
+
+    /// The session log as a result set, one row per statement.
+    pub fn to_result_set(log: &[QueryStats]) -> ResultSet {
+        let rows = log
+            .iter()
+            .map(|stats| {
+                [
+                    stats.sql.clone(),
+                    format!("{:.3}", stats.elapsed.as_secs_f64() * 1000.0),
+                    stats.rows_returned.to_string(),
+                    stats.rows_changed.to_string(),
+                    stats.vm_steps.to_string(),
+                    stats.fullscan_steps.to_string(),
+                    stats.sorts.to_string(),
+                    stats.cache_hits.to_string(),
+                    stats.cache_misses.to_string(),
+                ]
+                .into_iter()
+                .map(serde_json::Value::from)
+                .collect()
+            })
+            .collect();
+
+        ResultSet {
+            columns: Self::COLUMNS.map(String::from).to_vec(),
+            rows,
+            stats: None,
+        }
+    }
+}
+
+/// Properties of the database file and the SQLite library.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatabaseInfo {
+    pub path: String,
+    pub file_size: u64,
+    pub page_size: u64,
+    pub page_count: u64,
+    pub freelist_count: u64,
+    pub journal_mode: String,
+    /// Size of the `-wal` file, `None` when there is none.
+    pub wal_size: Option<u64>,
+    pub encoding: String,
+    pub user_version: i64,
+    pub application_id: i64,
+    pub auto_vacuum: String,
+    pub sqlite_version: String,
+    pub compile_options: Vec<String>,
+}
+
+impl DatabaseInfo {
+    /// The properties as a two column result set, one compile option per
+    /// row at the end.
+    pub fn to_result_set(&self) -> ResultSet {
+        let wal = match (&self.wal_size, self.journal_mode.as_str()) {
+            (Some(size), _) => format!("active, {size} bytes"),
+            (None, "wal") => "enabled, no WAL file".to_string(),
+            (None, _) => "off".to_string(),
+        };
+        let properties = [
+            ("path", self.path.clone()),
+            ("file size", format!("{} bytes", self.file_size)),
+            ("page size", format!("{} bytes", self.page_size)),
+            ("page count", self.page_count.to_string()),
+            ("freelist count", self.freelist_count.to_string()),
+            ("journal mode", self.journal_mode.clone()),
+            ("WAL", wal),
+            ("encoding", self.encoding.clone()),
+            ("user_version", self.user_version.to_string()),
+            ("application_id", self.application_id.to_string()),
+            ("auto_vacuum", self.auto_vacuum.clone()),
+            ("SQLite version", self.sqlite_version.clone()),
+        ];
+        let options = self
+            .compile_options
+            .iter()
+            .map(|option| ("compile option", option.clone()));
+
+        ResultSet {
+            columns: vec!["property".to_string(), "value".to_string()],
+            rows: properties
+                .into_iter()
+                .chain(options)
+                .map(|(property, value)| vec![property.into(), value.into()])
+                .collect(),
+            stats: None,
+        }
+    }
+}
+
+/// Disk usage of a table or index b-tree, including its overflow pages.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Storage {
+    pub name: String,
+    /// Table the b-tree belongs to, its own name for tables.
+    pub table: String,
+    pub index: bool,
+    pub pages: u64,
+    pub bytes: u64,
+    pub payload: u64,
+    pub unused: u64,
+    /// Pages not directly following the previous page of the b-tree.
+    pub fragmented: u64,
+}
+
+impl Storage {
+    /// Share of out of sequence pages, in percent.
+    pub fn fragmentation(&self) -> f64 {
+        if self.pages <= 1 {
+            return 0.0;
+        }
+        100.0 * self.fragmented as f64 / (self.pages - 1) as f64
+    }
+
+    /// One row per table and index, largest first.
+    pub fn to_result_set(storage: &[Storage]) -> ResultSet {
+        let mut storage: Vec<&Storage> = storage.iter().collect();
+        storage.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.name.cmp(&b.name)));
+        let rows = storage
+            .into_iter()
+            .map(|s| {
+                [
+                    s.name.clone(),
+                    if s.index { "index" } else { "table" }.to_string(),
+                    s.table.clone(),
+                    s.pages.to_string(),
+                    s.bytes.to_string(),
+                    s.payload.to_string(),
+                    s.unused.to_string(),
+                    format!("{:.1}", s.fragmentation()),
+                ]
+                .into_iter()
+                .map(serde_json::Value::from)
+                .collect()
+            })
+            .collect();
+
+        ResultSet {
+            columns: [
+                "name",
+                "type",
+                "table",
+                "pages",
+                "bytes",
+                "payload",
+                "unused",
+                "fragmentation",
+            ]
+            .map(String::from)
+            .to_vec(),
+            rows,
+            stats: None,
+        }
+    }
+}
+
+/// A column as described by `PRAGMA table_xinfo`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// Declared type, empty when there is none.
+    pub kind: String,
+    pub not_null: bool,
+    /// Default value as SQL text.
+    pub default: Option<String>,
+    /// Position in the primary key, 0 when not part of it.
+    pub primary_key: usize,
+    /// Generated column, stored or virtual, or hidden column of a virtual
+    /// table.
+    pub hidden: bool,
+}
+
+/// An index of a table from `PRAGMA index_list`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexInfo {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+    /// `CREATE INDEX` statement, `None` for the indexes SQLite creates for
+    /// `UNIQUE` and `PRIMARY KEY` constraints.
+    pub sql: Option<String>,
+    /// Created by `PRIMARY KEY` rather than `UNIQUE` or `CREATE INDEX`.
+    pub primary_key: bool,
+    /// Covers only the rows matching its `WHERE` clause.
+    pub partial: bool,
+}
+
+/// A foreign key of a table from `PRAGMA foreign_key_list`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ForeignKey {
+    pub table: String,
+    pub from: Vec<String>,
+    /// Referenced columns, empty for the primary key of `table`.
+    pub to: Vec<String>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+/// Structure of a table, enough to recreate it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableSchema {
+    pub name: String,
+    /// `CREATE TABLE` statement.
+    pub sql: String,
+    pub columns: Vec<ColumnInfo>,
+    pub indexes: Vec<IndexInfo>,
+    pub foreign_keys: Vec<ForeignKey>,
+    /// `CREATE TRIGGER` statements.
+    pub triggers: Vec<String>,
+}
+
+/// Profile of the values of one column.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnStats {
+    pub table: String,
+    pub column: String,
+    pub count: u64,
+    pub distinct: u64,
+    pub nulls: u64,
+    /// Smallest and largest value in SQLite's sort order, `None` when all
+    /// values are NULL.
+    pub min: Option<String>,
+    pub max: Option<String>,
+    /// Average of the integer and real values.
+    pub average: Option<f64>,
+    /// Distribution of the integer and real values.
+    pub values: Option<Histogram>,
+    /// Distribution of the lengths of the text values.
+    pub lengths: Option<Histogram>,
+    /// Most frequent values other than NULL with their counts, most
+    /// frequent first.
+    pub top: Vec<(String, u64)>,
+}
+
+impl ColumnStats {
+    /// Number of buckets of a histogram.
+    pub const BUCKETS: usize = 16;
+    /// Number of most frequent values.
+    pub const TOP: usize = 5;
+}
+
+/// Counts of values in equally wide buckets from `low` to `high`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    pub low: f64,
+    pub high: f64,
+    pub buckets: Vec<u64>,
+}
+
+/// Element of a JSON document as listed by SQLite's `json_tree`, in
+/// document order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonNode {
+    pub id: i64,
+    pub parent: Option<i64>,
+    /// Object key or array index, `None` for the root.
+    pub key: Option<String>,
+    /// `object`, `array`, `text`, `integer`, `real`, `true`, `false` or
+    /// `null`.
+    pub kind: String,
+    /// The text of an object or array, or of a value other than `null`.
+    pub value: Option<String>,
+    /// Path of the element from the root, e.g. `$.tags[0]`.
+    pub path: String,
+}
+
+impl JsonNode {
+    pub fn is_container(&self) -> bool {
+        matches!(self.kind.as_str(), "object" | "array")
+    }
+}
+
+/// Consistency check run by [`Database::check`].
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Check {
+    /// `PRAGMA integrity_check`
+    Integrity,
+    /// `PRAGMA quick_check`, skipping the index consistency checks
+    Quick,
+    /// `PRAGMA foreign_key_check`
+    ForeignKeys,
+}
+
+impl Check {
+    pub fn pragma(&self) -> &'static str {
+        match self {
+            Check::Integrity => "integrity_check",
+            Check::Quick => "quick_check",
+            Check::ForeignKeys => "foreign_key_check",
+        }
+    }
+}
+
+/// Maintenance statement run by [`Database::maintain`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Maintenance {
+    Vacuum,
+    /// `VACUUM INTO` a new file, leaving the database untouched.
+    VacuumInto(String),
+    Analyze,
+    Reindex,
+    Optimize,
+    /// `PRAGMA wal_checkpoint(TRUNCATE)`
+    Checkpoint,
+}
+
+impl Maintenance {
+    /// Actions offered in the maintenance menu, `VACUUM INTO` with an
+    /// empty path to be filled in.
+    pub fn all() -> [Maintenance; 6] {
+        [
+            Maintenance::Vacuum,
+            Maintenance::VacuumInto(String::new()),
+            Maintenance::Analyze,
+            Maintenance::Reindex,
+            Maintenance::Optimize,
+            Maintenance::Checkpoint,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Maintenance::Vacuum => "VACUUM",
+            Maintenance::VacuumInto(_) => "VACUUM INTO",
+            Maintenance::Analyze => "ANALYZE",
+            Maintenance::Reindex => "REINDEX",
+            Maintenance::Optimize => "PRAGMA optimize",
+            Maintenance::Checkpoint => "PRAGMA wal_checkpoint(TRUNCATE)",
+        }
+    }
+
+    pub fn sql(&self) -> String {
+        match self {
+            Maintenance::VacuumInto(path) => format!("VACUUM INTO '{}'", path.replace('\'', "''")),
+            _ => self.name().to_string(),
+        }
+    }
+
+    /// What the action does, shown in the menu and the confirmation.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Maintenance::Vacuum => "rebuild the file, reclaiming free pages",
+            Maintenance::VacuumInto(_) => "write a compacted copy to a new file",
+            Maintenance::Analyze => "gather statistics for the query planner",
+            Maintenance::Reindex => "rebuild every index",
+            Maintenance::Optimize => "run ANALYZE where the statistics are stale",
+            Maintenance::Checkpoint => "copy the WAL into the database and truncate it",
+        }
+    }
+}
+
+/// Marker of the database contents that changes whenever another connection
+/// commits: SQLite's `data_version` and the modification time of the file and
+/// its WAL, which also catches the file being replaced.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Version {
+    pub data_version: i64,
+    pub modified: Option<SystemTime>,
 }
 
-pub trait Database: Clone {
-    async fn tables(&self) -> Result<Vec<String>>;
-    async fn schema(&self, table: &str) -> Result<String>;
-    async fn columns(&self, table: &str) -> Result<Vec<String>>;
-    async fn rows(&self, table: &str, column: &str) -> Result<Vec<Vec<serde_json::Value>>>;
+pub trait Database: Clone + Send + Sync + 'static {
+    fn tables(&self) -> impl Future<Output = Result<Vec<String>>> + Send;
+    fn schema(&self, table: &str) -> impl Future<Output = Result<String>> + Send;
+    fn columns(&self, table: &str) -> impl Future<Output = Result<Vec<String>>> + Send;
+    /// Declared types of the columns of `table`, empty for none.
+    fn column_types(&self, table: &str) -> impl Future<Output = Result<Vec<String>>> + Send;
+    /// Columns, indexes, foreign keys and triggers of `table`.
+    fn table_schema(&self, table: &str) -> impl Future<Output = Result<TableSchema>> + Send;
+    fn count(&self, table: &str) -> impl Future<Output = Result<usize>> + Send;
+    /// Rows of `table`, only those matching `filter` if given.
+    fn rows(
+        &self,
+        table: &str,
+        filter: Option<&WhereClause>,
+    ) -> impl Future<Output = Result<ResultSet>> + Send;
+    /// Position of the first row of the result of `query`, with `params`
+    /// bound to its placeholders, that matches the SQL `condition`.
+    fn position(
+        &self,
+        query: &str,
+        params: &[Value],
+        condition: &str,
+    ) -> impl Future<Output = Result<Option<usize>>> + Send;
+    /// First `limit` rows of `table`, typed as by [`Database::query_values`].
+    fn head(&self, table: &str, limit: usize) -> impl Future<Output = Result<ResultSet>> + Send;
+    fn query(&self, sql: &str) -> impl Future<Output = Result<ResultSet>> + Send;
+    /// Result of `sql` with the values as stored rather than as text: NULL
+    /// as JSON null, numbers as numbers and BLOBs as hex, for output.
+    fn query_values(&self, sql: &str) -> impl Future<Output = Result<ResultSet>> + Send;
+    /// Whether `sql` leaves the database unchanged, as reported by
+    /// `sqlite3_stmt_readonly` for each of its statements.
+    fn reads_only(&self, sql: &str) -> impl Future<Output = Result<bool>> + Send;
+    fn info(&self) -> impl Future<Output = Result<DatabaseInfo>> + Send;
+    /// Profile of `column` of `table`.
+    fn column_stats(
+        &self,
+        table: &str,
+        column: &str,
+    ) -> impl Future<Output = Result<ColumnStats>> + Send;
+    /// Elements of the JSON document `json`, failing when it is malformed.
+    fn json_tree(&self, json: &str) -> impl Future<Output = Result<Vec<JsonNode>>> + Send;
+    /// Runs `check` on the whole database or one table, handing each
+    /// problem to `report` as it is found. Returns the number of problems.
+    fn check(
+        &self,
+        check: Check,
+        table: Option<&str>,
+        report: impl FnMut(String) + Send,
+    ) -> impl Future<Output = Result<usize>> + Send;
+    /// Copies `schema` to a new database file at `path` with the online
+    /// backup API, reporting the pages copied so far and the total to
+    /// `progress`. Returns the number of pages copied. Stops on
+    /// [`Database::interrupt`], or when another connection keeps the
+    /// database locked.
+    fn backup(
+        &self,
+        schema: &str,
+        path: &str,
+        progress: impl FnMut(u64, u64) + Send,
+    ) -> impl Future<Output = Result<u64>> + Send;
+    /// Creates `table` with `columns`, name and declared type, and inserts
+    /// `rows` in one transaction. Returns the number of rows inserted.
+    fn create_table(
+        &self,
+        table: &str,
+        columns: &[(String, &str)],
+        rows: &[Vec<Value>],
+    ) -> impl Future<Output = Result<u64>> + Send;
+    /// Appends `rows` with values for `columns` to `table` in one
+    /// transaction. Returns the number of rows inserted.
+    fn insert(
+        &self,
+        table: &str,
+        columns: &[String],
+        rows: &[Vec<Value>],
+    ) -> impl Future<Output = Result<u64>> + Send;
+    /// Runs schema changing `statements` in one transaction, with foreign
+    /// key enforcement off and a foreign key check before committing.
+    fn execute(&self, statements: &[String]) -> impl Future<Output = Result<()>> + Send;
+    /// `EXPLAIN QUERY PLAN` of `sql`, with `params` bound to its
+    /// placeholders, as if `statements`, e.g. a `CREATE INDEX`, had run.
+    /// They run in a transaction that is rolled back, and need write access
+    /// only when there are any.
+    fn explain_with(
+        &self,
+        statements: &[String],
+        sql: &str,
+        params: &[Value],
+    ) -> impl Future<Output = Result<ResultSet>> + Send;
+    /// Whether the database was opened read-only.
+    fn is_read_only(&self) -> bool;
+    /// Runs `action` and returns the properties of the resulting file: the
+    /// database itself, or the new file of `VACUUM INTO`.
+    fn maintain(&self, action: &Maintenance) -> impl Future<Output = Result<DatabaseInfo>> + Send;
+    /// Disk usage of every table and index.
+    fn storage(&self) -> impl Future<Output = Result<Vec<Storage>>> + Send;
+    /// Current [`Version`] of the database, polled by watch mode.
+    fn version(&self) -> impl Future<Output = Result<Version>> + Send;
+    /// Aborts the statement currently running on the connection, if any.
+    fn interrupt(&self);
+    /// Statistics of the most recent statements run in this session,
+    /// oldest first.
+    fn stats(&self) -> Vec<QueryStats>;
 }
@@ -0,0 +1,267 @@
+//! Storage analysis by walking the b-tree pages of the database file, used
+//! when SQLite is built without the `dbstat` virtual table.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use anyhow::{bail, Result};
+
+use crate::database::{DatabaseInfo, Storage};
+
+const HEADER_SIZE: usize = 100;
+
+/// A b-tree to analyze: name, owning table, whether it is an index, and its
+/// root page.
+pub struct Root {
+    pub name: String,
+    pub table: String,
+    pub index: bool,
+    pub page: u32,
+}
+
+struct Pager {
+    file: File,
+    header: [u8; HEADER_SIZE],
+    page_size: usize,
+    /// Usable bytes per page, the page size minus the reserved bytes.
+    usable: usize,
+}
+
+impl Pager {
+    fn open(path: &str) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        if &header[..16] != b"SQLite format 3\0" {
+            bail!("{path} is not an SQLite database");
+        }
+        let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+            1 => 65536,
+            size => usize::from(size),
+        };
+        Ok(Self {
+            file,
+            header,
+            page_size,
+            usable: page_size - usize::from(header[20]),
+        })
+    }
+
+    fn read(&mut self, page: u32) -> Result<Vec<u8>> {
+        if page == 0 {
+            bail!("invalid page number 0");
+        }
+        let mut data = vec![0; self.page_size];
+        self.file
+            .seek(SeekFrom::Start(u64::from(page - 1) * self.page_size as u64))?;
+        self.file.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Totals of one b-tree, accumulated in the order pages are visited.
+struct Walk<'a> {
+    pager: &'a mut Pager,
+    storage: Storage,
+    last_page: Option<u32>,
+}
+
+impl Walk<'_> {
+    fn visit(&mut self, page: u32, payload: u64, unused: u64) {
+        self.storage.pages += 1;
+        self.storage.bytes += self.pager.page_size as u64;
+        self.storage.payload += payload;
+        self.storage.unused += unused;
+        if self.last_page.is_some_and(|last| page != last + 1) {
+            self.storage.fragmented += 1;
+        }
+        self.last_page = Some(page);
+    }
+
+    fn btree(&mut self, page: u32, depth: usize) -> Result<()> {
+        if depth > 64 {
+            bail!("b-tree too deep at page {page}, the file may be corrupt");
+        }
+        let data = self.pager.read(page)?;
+        let start = if page == 1 { HEADER_SIZE } else { 0 };
+        let [kind] = bytes(&data, start)?;
+        let interior = matches!(kind, 0x02 | 0x05);
+        if !matches!(kind, 0x02 | 0x05 | 0x0a | 0x0d) {
+            bail!("page {page} is not a b-tree page");
+        }
+        let cells = usize::from(u16_at(&data, start + 3)?);
+        let content = match u16_at(&data, start + 5)? {
+            0 => 65536,
+            offset => usize::from(offset),
+        };
+        let header = if interior { 12 } else { 8 };
+
+        // Free space: the gap before the cell content, the free blocks in
+        // it and fragmented bytes.
+        let pointers = start + header + 2 * cells;
+        let [fragmented] = bytes(&data, start + 7)?;
+        let mut unused = content.saturating_sub(pointers) + usize::from(fragmented);
+        let mut free_block = usize::from(u16_at(&data, start + 1)?);
+        while free_block != 0 {
+            unused += usize::from(u16_at(&data, free_block + 2)?);
+            let next = usize::from(u16_at(&data, free_block)?);
+            // Free blocks are kept in ascending order, anything else is a
+            // loop in a corrupt page.
+            if next <= free_block && next != 0 {
+                bail!("free block list of page {page} is corrupt");
+            }
+            free_block = next;
+        }
+
+        let mut payload = 0;
+        let mut children = Vec::new();
+        let mut overflows = Vec::new();
+        for i in 0..cells {
+            let mut offset = usize::from(u16_at(&data, pointers - 2 * cells + 2 * i)?);
+            if interior {
+                children.push(u32_at(&data, offset)?);
+                offset += 4;
+            }
+            if kind == 0x05 {
+                // Interior table cells hold only a rowid.
+                continue;
+            }
+            let (size, len) = varint(data.get(offset..).unwrap_or_default());
+            offset += len;
+            if kind == 0x0d {
+                offset += varint(data.get(offset..).unwrap_or_default()).1;
+            }
+            let size = usize::try_from(size)?;
+            let local = self.local_payload(size, kind == 0x0d);
+            payload += local;
+            if local < size {
+                overflows.push((u32_at(&data, offset + local)?, size - local));
+            }
+        }
+        if interior {
+            children.push(u32_at(&data, start + 8)?);
+        }
+        self.visit(page, payload as u64, unused as u64);
+
+        for child in children {
+            self.btree(child, depth + 1)?;
+        }
+        for (page, size) in overflows {
+            self.overflow(page, size)?;
+        }
+        Ok(())
+    }
+
+    /// Bytes of a cell's payload stored on the b-tree page itself, as laid
+    /// out in the file format documentation.
+    fn local_payload(&self, size: usize, table_leaf: bool) -> usize {
+        let usable = self.pager.usable;
+        let max = if table_leaf {
+            usable - 35
+        } else {
+            (usable - 12) * 64 / 255 - 23
+        };
+        if size <= max {
+            return size;
+        }
+        let min = (usable - 12) * 32 / 255 - 23;
+        let local = min + (size - min) % (usable - 4);
+        if local <= max {
+            local
+        } else {
+            min
+        }
+    }
+
+    fn overflow(&mut self, mut page: u32, mut remaining: usize) -> Result<()> {
+        let capacity = self.pager.usable - 4;
+        while page != 0 && remaining > 0 {
+            let data = self.pager.read(page)?;
+            let stored = remaining.min(capacity);
+            self.visit(page, stored as u64, (capacity - stored) as u64);
+            remaining -= stored;
+            page = u32_at(&data, 0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Walks the b-trees of `roots` in the database file at `path`.
+pub fn analyze(path: &str, roots: Vec<Root>) -> Result<Vec<Storage>> {
+    let mut pager = Pager::open(path)?;
+    roots
+        .into_iter()
+        .map(|root| {
+            let mut walk = Walk {
+                pager: &mut pager,
+                storage: Storage {
+                    name: root.name,
+                    table: root.table,
+                    index: root.index,
+                    ..Storage::default()
+                },
+                last_page: None,
+            };
+            walk.btree(root.page, 0)?;
+            Ok(walk.storage)
+        })
+        .collect()
+}
+
+/// Size, page and free page counts of the database file at `path`, read
+/// from its header.
+pub fn header(path: &str) -> Result<DatabaseInfo> {
+    let pager = Pager::open(path)?;
+    Ok(DatabaseInfo {
+        path: path.to_string(),
+        file_size: pager.file.metadata()?.len(),
+        page_size: pager.page_size as u64,
+        page_count: u64::from(u32_at(&pager.header, 28)?),
+        freelist_count: u64::from(u32_at(&pager.header, 36)?),
+        ..DatabaseInfo::default()
+    })
+}
+
+fn bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    match data.get(offset..offset + N) {
+        Some(bytes) => Ok(bytes.try_into()?),
+        None => bail!("offset {offset} is out of the page, the file may be corrupt"),
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16> {
+    Ok(u16::from_be_bytes(bytes(data, offset)?))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_be_bytes(bytes(data, offset)?))
+}
+
+/// Decodes an SQLite varint, returning the value and its length in bytes.
+fn varint(data: &[u8]) -> (u64, usize) {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(9).enumerate() {
+        if i == 8 {
+            return ((value << 8) | u64::from(byte), 9);
+        }
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return (value, i + 1);
+        }
+    }
+    (value, data.len().min(9))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_varints() {
+        assert_eq!(varint(&[0x05]), (5, 1));
+        assert_eq!(varint(&[0x81, 0x00]), (128, 2));
+        assert_eq!(varint(&[0xff; 9]), (u64::MAX, 9));
+    }
+}
@@ -1,11 +1,31 @@
-use anyhow::Result;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ffi::{CStr, CString},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicPtr, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use futures::TryStreamExt;
 use serde_json::{json, Value};
 use sqlx::{
-    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
-    AssertSqlSafe, Column, Row, TypeInfo,
+    query::Query,
+    sqlite::{
+        SqliteArguments, SqliteConnectOptions, SqliteConnection, SqlitePoolOptions, SqliteRow,
+    },
+    AssertSqlSafe, Column, Executor, Row, SqlSafeStr, Sqlite, Statement, TypeInfo, ValueRef,
 };
 
-use crate::database::{Database, SqliteDb};
+use crate::database::{
+    pages::{self, Root},
+    Check, ColumnInfo, ColumnStats, Database, DatabaseInfo, ForeignKey, Histogram, Ident,
+    IndexInfo, JsonNode, Maintenance, QueryStats, ResultSet, SqliteDb, Storage, TableSchema,
+    Version, WhereClause, STATS_LOG_SIZE,
+};
 
 impl SqliteDb {
     pub async fn connect(path: &str, create_if_missing: bool) -> Result<Self> {
@@ -13,24 +33,55 @@ impl SqliteDb {
             .filename(path)
             .create_if_missing(create_if_missing);
 
-        let pool = SqlitePoolOptions::new()
-            .max_connections(1)
-            .connect_with(options)
-            .await?;
+        Self::open(options).await
+    }
 
-        Ok(Self { pool })
+    /// Opens an existing database without write access.
+    pub async fn connect_read_only(path: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::new().filename(path).read_only(true);
+
+        Ok(Self {
+            read_only: true,
+            ..Self::open(options).await?
+        })
     }
 
     #[cfg(test)]
     pub async fn memory() -> Result<Self> {
         let options = SqliteConnectOptions::new().in_memory(true);
 
+        Self::open(options).await
+    }
+
+    /// Opens a pool holding a single long-lived connection whose raw handle is
+    /// kept around so running statements can be interrupted.
+    async fn open(options: SqliteConnectOptions) -> Result<Self> {
+        let handle = Arc::new(AtomicPtr::new(ptr::null_mut()));
+        let after_connect_handle = handle.clone();
+
         let pool = SqlitePoolOptions::new()
             .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .after_connect(move |conn, _| {
+                let handle = after_connect_handle.clone();
+                Box::pin(async move {
+                    let raw = conn.lock_handle().await?.as_raw_handle();
+                    handle.store(raw.as_ptr(), Ordering::SeqCst);
+                    Ok(())
+                })
+            })
             .connect_with(options)
             .await?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            handle,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            log: Arc::new(Mutex::new(VecDeque::with_capacity(STATS_LOG_SIZE))),
+            read_only: false,
+        })
     }
 }
 
@@ -66,7 +117,7 @@ impl Database for SqliteDb {
     }
 
     async fn columns(&self, table: &str) -> Result<Vec<String>> {
-        let query = format!("PRAGMA table_info({table})");
+        let query = format!("PRAGMA table_info({})", Ident::new(table));
 
         let rows = sqlx::query(AssertSqlSafe(query.as_str()))
             .fetch_all(&self.pool)
@@ -78,44 +129,1173 @@ impl Database for SqliteDb {
             .collect::<Result<_, _>>()?)
     }
 
-    async fn rows(&self, column: &str, table: &str) -> Result<Vec<Vec<Value>>> {
-        let query = format!("SELECT {column} FROM {table}");
+    async fn column_types(&self, table: &str) -> Result<Vec<String>> {
+        let query = format!("PRAGMA table_info({})", Ident::new(table));
 
-        let result: Vec<_> = sqlx::query(AssertSqlSafe(query.as_str()))
+        let rows = sqlx::query(AssertSqlSafe(query.as_str()))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| r.try_get::<String, _>("type"))
+            .collect::<Result<_, _>>()?)
+    }
+
+    async fn table_schema(&self, table: &str) -> Result<TableSchema> {
+        let sql = sqlx::query_scalar::<_, String>(
+            "SELECT sql FROM sqlite_schema WHERE type = 'table' AND name = ?1",
+        )
+        .bind(table)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(sql) = sql else {
+            bail!("no such table: {table}");
+        };
+
+        let query = format!("PRAGMA table_xinfo({})", Ident::new(table));
+        let columns = sqlx::query(AssertSqlSafe(query.as_str()))
             .fetch_all(&self.pool)
             .await?
             .into_iter()
             .map(|row| {
-                row.columns()
-                    .iter()
-                    .map(|column| {
-                        let ordinal = column.ordinal();
-                        let type_name = column.type_info().name();
-                        match type_name {
-                            "NULL" => json!("null".to_string()),
-                            "INTEGER" => json!(row.get::<i64, _>(ordinal).to_string()),
-                            "REAL" => json!(row.get::<f64, _>(ordinal).to_string()),
-                            "TEXT" | "DATETIME" => {
-                                json!(row.get::<String, _>(ordinal).to_string())
-                            }
-                            "BLOB" => {
-                                json!(hex::encode(row.get::<Vec<u8>, _>(ordinal)).to_string())
-                            }
-                            _ => {
-                                panic!("not supported type: {type_name}");
-                            }
-                        }
-                    })
-                    .collect::<Vec<_>>()
+                Ok(ColumnInfo {
+                    name: row.try_get("name")?,
+                    kind: row.try_get("type")?,
+                    not_null: row.try_get::<i64, _>("notnull")? != 0,
+                    default: row.try_get("dflt_value")?,
+                    primary_key: usize::try_from(row.try_get::<i64, _>("pk")?)?,
+                    hidden: row.try_get::<i64, _>("hidden")? != 0,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let query = format!("PRAGMA index_list({})", Ident::new(table));
+        let mut indexes = Vec::new();
+        for row in sqlx::query(AssertSqlSafe(query.as_str()))
+            .fetch_all(&self.pool)
+            .await?
+        {
+            let name: String = row.try_get("name")?;
+            let origin: String = row.try_get("origin")?;
+            let query = format!("PRAGMA index_info({})", Ident::new(&name));
+            let columns = sqlx::query(AssertSqlSafe(query.as_str()))
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                // Expressions have no name.
+                .map(|row| row.try_get::<Option<String>, _>("name"))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .flatten()
+                .collect();
+            let sql = sqlx::query_scalar::<_, Option<String>>(
+                "SELECT sql FROM sqlite_schema WHERE type = 'index' AND name = ?1",
+            )
+            .bind(&name)
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+            indexes.push(IndexInfo {
+                name,
+                columns,
+                unique: row.try_get::<i64, _>("unique")? != 0,
+                sql,
+                primary_key: origin == "pk",
+                partial: row.try_get::<i64, _>("partial")? != 0,
+            });
+        }
+        indexes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let query = format!("PRAGMA foreign_key_list({})", Ident::new(table));
+        let mut foreign_keys: Vec<(i64, ForeignKey)> = Vec::new();
+        for row in sqlx::query(AssertSqlSafe(query.as_str()))
+            .fetch_all(&self.pool)
+            .await?
+        {
+            let id: i64 = row.try_get("id")?;
+            if foreign_keys.last().is_none_or(|(last, _)| *last != id) {
+                foreign_keys.push((
+                    id,
+                    ForeignKey {
+                        table: row.try_get("table")?,
+                        on_update: row.try_get("on_update")?,
+                        on_delete: row.try_get("on_delete")?,
+                        ..ForeignKey::default()
+                    },
+                ));
+            }
+            let (_, key) = foreign_keys.last_mut().expect("pushed above");
+            key.from.push(row.try_get("from")?);
+            if let Some(to) = row.try_get::<Option<String>, _>("to")? {
+                key.to.push(to);
+            }
+        }
+
+        let triggers = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT sql
+            FROM sqlite_schema
+            WHERE type = 'trigger'
+              AND tbl_name = ?1
+              AND sql IS NOT NULL
+            ORDER BY name
+            "#,
+        )
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(TableSchema {
+            name: table.to_string(),
+            sql,
+            columns,
+            indexes,
+            foreign_keys: foreign_keys.into_iter().map(|(_, key)| key).collect(),
+            triggers,
+        })
+    }
+
+    async fn count(&self, table: &str) -> Result<usize> {
+        let query = format!("SELECT COUNT(*) FROM {}", Ident::new(table));
+
+        let mut conn = self.pool.acquire().await?;
+        let probe = Probe::start(&mut conn, &query).await?;
+        let count = sqlx::query_scalar::<_, i64>(AssertSqlSafe(query.as_str()))
+            .fetch_one(&mut *conn)
+            .await?;
+        self.record(&probe.finish(&mut conn, 1).await?);
+
+        Ok(usize::try_from(count)?)
+    }
+
+    async fn rows(&self, table: &str, filter: Option<&WhereClause>) -> Result<ResultSet> {
+        let query = WhereClause::select(table, filter);
+        let params = filter.map_or(&[][..], |filter| filter.params.as_slice());
+
+        self.run(&query, params, value).await
+    }
+
+    async fn position(
+        &self,
+        query: &str,
+        params: &[Value],
+        condition: &str,
+    ) -> Result<Option<usize>> {
+        let query = format!(
+            "SELECT position FROM (SELECT row_number() OVER () - 1 AS position, * FROM ({})) \
+             WHERE {condition} LIMIT 1",
+            query.trim().trim_end_matches(';')
+        );
+
+        let mut conn = self.pool.acquire().await?;
+        let probe = Probe::start(&mut conn, &query).await?;
+        let position: Option<i64> = params
+            .iter()
+            .fold(sqlx::query(AssertSqlSafe(query.as_str())), bind)
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(|row| row.try_get(0))
+            .transpose()?;
+        let stats = probe
+            .finish(&mut conn, usize::from(position.is_some()))
+            .await?;
+        self.record(&stats);
+
+        Ok(position.map(usize::try_from).transpose()?)
+    }
+
+    async fn head(&self, table: &str, limit: usize) -> Result<ResultSet> {
+        let query = format!("SELECT * FROM {} LIMIT {limit}", Ident::new(table));
+
+        self.query_values(&query).await
+    }
+
+    async fn query(&self, sql: &str) -> Result<ResultSet> {
+        self.run(sql, &[], value).await
+    }
+
+    async fn query_values(&self, sql: &str) -> Result<ResultSet> {
+        self.run(sql, &[], typed_value).await
+    }
+
+    async fn reads_only(&self, sql: &str) -> Result<bool> {
+        use libsqlite3_sys::*;
+
+        let sql = CString::new(sql)?;
+        let mut conn = self.pool.acquire().await?;
+        let mut handle = conn.lock_handle().await?;
+        let db = handle.as_raw_handle().as_ptr();
+        let mut tail = sql.as_ptr();
+        // SAFETY: the locked handle gives exclusive access to the connection,
+        // `tail` points into `sql` up to its terminating nul, and every
+        // prepared statement is finalized before the next one.
+        unsafe {
+            while *tail != 0 {
+                let mut stmt = ptr::null_mut();
+                if sqlite3_prepare_v2(db, tail, -1, &mut stmt, &mut tail) != SQLITE_OK {
+                    bail!("{}", CStr::from_ptr(sqlite3_errmsg(db)).to_string_lossy());
+                }
+                // Trailing whitespace and comments compile to no statement.
+                if stmt.is_null() {
+                    break;
+                }
+                let read_only = sqlite3_stmt_readonly(stmt) != 0;
+                sqlite3_finalize(stmt);
+                if !read_only {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    async fn info(&self) -> Result<DatabaseInfo> {
+        let text = |pragma: &'static str| {
+            sqlx::query_scalar::<_, String>(AssertSqlSafe(format!("PRAGMA {pragma}")))
+                .fetch_one(&self.pool)
+        };
+        let number = |pragma: &'static str| {
+            sqlx::query_scalar::<_, i64>(AssertSqlSafe(format!("PRAGMA {pragma}")))
+                .fetch_one(&self.pool)
+        };
+        let unsigned = |value: i64| u64::try_from(value).unwrap_or(0);
+
+        // The third column of `PRAGMA database_list` is the file name, empty
+        // for in-memory and temporary databases.
+        let path: String = sqlx::query("PRAGMA database_list")
+            .fetch_one(&self.pool)
+            .await?
+            .try_get(2)?;
+        let page_size = unsigned(number("page_size").await?);
+        let page_count = unsigned(number("page_count").await?);
+        let file_size = std::fs::metadata(&path).map_or(page_size * page_count, |m| m.len());
+        let wal_size = std::fs::metadata(format!("{path}-wal"))
+            .ok()
+            .filter(|_| !path.is_empty())
+            .map(|m| m.len());
+        let auto_vacuum = match number("auto_vacuum").await? {
+            0 => "none",
+            1 => "full",
+            2 => "incremental",
+            _ => "unknown",
+        };
+
+        Ok(DatabaseInfo {
+            file_size,
+            page_size,
+            page_count,
+            freelist_count: unsigned(number("freelist_count").await?),
+            journal_mode: text("journal_mode").await?,
+            wal_size,
+            encoding: text("encoding").await?,
+            user_version: number("user_version").await?,
+            application_id: number("application_id").await?,
+            auto_vacuum: auto_vacuum.to_string(),
+            sqlite_version: sqlx::query_scalar("SELECT sqlite_version()")
+                .fetch_one(&self.pool)
+                .await?,
+            compile_options: sqlx::query_scalar("PRAGMA compile_options")
+                .fetch_all(&self.pool)
+                .await?,
+            path,
+        })
+    }
+
+    async fn column_stats(&self, table: &str, column: &str) -> Result<ColumnStats> {
+        let from = Ident::new(table);
+        let c = Ident::new(column);
+        let number = format!("CASE WHEN typeof({c}) IN ('integer', 'real') THEN {c} END");
+        let length = format!("CASE WHEN typeof({c}) = 'text' THEN length({c}) END");
+        let query = format!(
+            "SELECT count(*), count(DISTINCT {c}), count(*) - count({c}), min({c}), max({c}), \
+             avg({number}), CAST(min({number}) AS REAL), CAST(max({number}) AS REAL), \
+             CAST(min({length}) AS REAL), CAST(max({length}) AS REAL) FROM {from}"
+        );
+        let row = sqlx::query(AssertSqlSafe(query))
+            .fetch_one(&self.pool)
+            .await?;
+        let unsigned =
+            |ordinal: usize| -> Result<u64> { Ok(u64::try_from(row.try_get::<i64, _>(ordinal)?)?) };
+        let text = |ordinal: usize| match row.try_get_raw(ordinal) {
+            Ok(raw) if !raw.is_null() => value(&row, ordinal).as_str().map(String::from),
+            _ => None,
+        };
+        let range = |low: usize, high: usize| -> Result<Option<(f64, f64)>> {
+            let low: Option<f64> = row.try_get(low)?;
+            let high: Option<f64> = row.try_get(high)?;
+            Ok(low.zip(high))
+        };
+
+        let values = match range(6, 7)? {
+            Some((low, high)) => Some(
+                self.histogram(table, &number, low, high, ColumnStats::BUCKETS)
+                    .await?,
+            ),
+            None => None,
+        };
+        let lengths = match range(8, 9)? {
+            // One bucket per length when there are only a few.
+            Some((low, high)) => {
+                let buckets = ColumnStats::BUCKETS.min((high - low) as usize + 1);
+                Some(self.histogram(table, &length, low, high, buckets).await?)
+            }
+            None => None,
+        };
+
+        let query = format!(
+            "SELECT {c}, count(*) AS n FROM {from} WHERE {c} IS NOT NULL \
+             GROUP BY {c} ORDER BY n DESC, {c} LIMIT {}",
+            ColumnStats::TOP
+        );
+        let top = sqlx::query(AssertSqlSafe(query))
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| {
+                let value = value(row, 0).as_str().unwrap_or_default().to_string();
+                Ok((value, u64::try_from(row.try_get::<i64, _>(1)?)?))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(ColumnStats {
+            table: table.to_string(),
+            column: column.to_string(),
+            count: unsigned(0)?,
+            distinct: unsigned(1)?,
+            nulls: unsigned(2)?,
+            min: text(3),
+            max: text(4),
+            average: row.try_get(5)?,
+            values,
+            lengths,
+            top,
+        })
+    }
+
+    async fn json_tree(&self, json: &str) -> Result<Vec<JsonNode>> {
+        let query = "SELECT id, parent, CAST(key AS TEXT), type, \
+                     CASE WHEN type IN ('object', 'array') THEN value ELSE CAST(atom AS TEXT) END, \
+                     fullkey FROM json_tree(?)";
+        sqlx::query(query)
+            .bind(json)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(JsonNode {
+                    id: row.try_get(0)?,
+                    parent: row.try_get(1)?,
+                    key: row.try_get(2)?,
+                    kind: row.try_get(3)?,
+                    value: row.try_get(4)?,
+                    path: row.try_get(5)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn check(
+        &self,
+        check: Check,
+        table: Option<&str>,
+        mut report: impl FnMut(String) + Send,
+    ) -> Result<usize> {
+        let sql = match table {
+            Some(table) => format!("PRAGMA {}({})", check.pragma(), Ident::new(table)),
+            None => format!("PRAGMA {}", check.pragma()),
+        };
+        let mut rows = sqlx::query(AssertSqlSafe(sql)).fetch(&self.pool);
+        let mut problems = 0;
+        while let Some(row) = rows.try_next().await? {
+            let problem = match check {
+                Check::Integrity | Check::Quick => {
+                    let message: String = row.try_get(0)?;
+                    if message == "ok" {
+                        continue;
+                    }
+                    message
+                }
+                Check::ForeignKeys => {
+                    let table: String = row.try_get("table")?;
+                    let rowid: Option<i64> = row.try_get("rowid")?;
+                    let parent: String = row.try_get("parent")?;
+                    let fkid: i64 = row.try_get("fkid")?;
+                    let row = rowid.map_or_else(|| "row".to_string(), |id| format!("rowid {id}"));
+                    format!("{table} {row}: no matching row in {parent} (foreign key {fkid})")
+                }
+            };
+            problems += 1;
+            report(problem);
+        }
+        Ok(problems)
+    }
+
+    async fn backup(
+        &self,
+        schema: &str,
+        path: &str,
+        progress: impl FnMut(u64, u64) + Send,
+    ) -> Result<u64> {
+        if std::path::Path::new(path).exists() {
+            bail!("{path} already exists");
+        }
+        let result = self.copy_pages(schema, path, progress).await;
+        if result.is_err() {
+            // Leave no partial copy behind.
+            let _ = std::fs::remove_file(path);
+        }
+        result
+    }
+
+    async fn create_table(
+        &self,
+        table: &str,
+        columns: &[(String, &str)],
+        rows: &[Vec<Value>],
+    ) -> Result<u64> {
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(name, kind)| format!("{} {kind}", Ident::new(name)))
+            .collect();
+        let create = format!(
+            "CREATE TABLE {} ({})",
+            Ident::new(table),
+            definitions.join(", ")
+        );
+        let names: Vec<String> = columns.iter().map(|(name, _)| name.clone()).collect();
+        self.write_rows(Some(create), table, &names, rows).await
+    }
+
+    async fn insert(&self, table: &str, columns: &[String], rows: &[Vec<Value>]) -> Result<u64> {
+        self.write_rows(None, table, columns, rows).await
+    }
+
+    async fn execute(&self, statements: &[String]) -> Result<()> {
+        if self.read_only {
+            bail!("the database is opened read-only");
+        }
+        let mut conn = self.pool.acquire().await?;
+        // Foreign keys cannot be switched inside a transaction, and
+        // dropping a rebuilt table would otherwise cascade to its children.
+        let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&mut *conn)
+            .await?;
+        // Rebuilds switch the legacy rename behaviour on for themselves.
+        let legacy_alter_table: i64 = sqlx::query_scalar("PRAGMA legacy_alter_table")
+            .fetch_one(&mut *conn)
+            .await?;
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut *conn)
+            .await?;
+        let result = async {
+            let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+            for statement in statements {
+                let probe = Probe::start(&mut tx, statement).await?;
+                sqlx::query(AssertSqlSafe(statement.as_str()))
+                    .execute(&mut *tx)
+                    .await?;
+                self.record(&probe.finish(&mut tx, 0).await?);
+            }
+            if sqlx::query("PRAGMA foreign_key_check")
+                .fetch_optional(&mut *tx)
+                .await?
+                .is_some()
+            {
+                bail!("the change would violate foreign key constraints");
+            }
+            tx.commit().await?;
+            Ok(())
+        }
+        .await;
+        // A rebuild, also a failed one, may leave the legacy rename
+        // behaviour switched.
+        let legacy = if legacy_alter_table != 0 { "ON" } else { "OFF" };
+        sqlx::query(AssertSqlSafe(format!(
+            "PRAGMA legacy_alter_table = {legacy}"
+        )))
+        .execute(&mut *conn)
+        .await?;
+        if foreign_keys != 0 {
+            sqlx::query("PRAGMA foreign_keys = ON")
+                .execute(&mut *conn)
+                .await?;
+        }
+        result
+    }
+
+    async fn explain_with(
+        &self,
+        statements: &[String],
+        sql: &str,
+        params: &[Value],
+    ) -> Result<ResultSet> {
+        if self.read_only && !statements.is_empty() {
+            bail!("the database is opened read-only");
+        }
+        let mut tx = self.pool.begin().await?;
+        for statement in statements {
+            sqlx::query(AssertSqlSafe(statement.as_str()))
+                .execute(&mut *tx)
+                .await?;
+        }
+        let explain = format!("EXPLAIN QUERY PLAN {sql}");
+        let statement = (&mut *tx)
+            .prepare(AssertSqlSafe(explain).into_sql_str())
+            .await?;
+        let columns = statement
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect();
+        let rows = params
+            .iter()
+            .fold(statement.query(), bind)
+            .fetch_all(&mut *tx)
+            .await?
+            .iter()
+            .map(|row| (0..row.len()).map(|ordinal| value(row, ordinal)).collect())
+            .collect();
+        tx.rollback().await?;
+
+        Ok(ResultSet {
+            columns,
+            rows,
+            stats: None,
+        })
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    async fn maintain(&self, action: &Maintenance) -> Result<DatabaseInfo> {
+        if self.read_only {
+            bail!("the database is opened read-only");
+        }
+        let sql = action.sql();
+        let mut conn = self.pool.acquire().await?;
+        let probe = Probe::start(&mut conn, &sql).await?;
+        let row = sqlx::query(AssertSqlSafe(sql.as_str()))
+            .fetch_optional(&mut *conn)
+            .await?;
+        self.record(&probe.finish(&mut conn, usize::from(row.is_some())).await?);
+        drop(conn);
+        match action {
+            // The checkpoint reports whether it was blocked by a reader or
+            // writer on another connection.
+            Maintenance::Checkpoint => {
+                if row.map(|row| row.try_get::<i64, _>(0)).transpose()? == Some(1) {
+                    bail!("checkpoint blocked by another connection");
+                }
+                self.info().await
+            }
+            Maintenance::VacuumInto(path) => {
+                let path = path.clone();
+                tokio::task::spawn_blocking(move || pages::header(&path)).await?
+            }
+            _ => self.info().await,
+        }
+    }
+
+    async fn storage(&self) -> Result<Vec<Storage>> {
+        // The dbstat table is only there when SQLite is built with it.
+        let dbstat = sqlx::query("SELECT 1 FROM dbstat LIMIT 0")
+            .fetch_optional(&self.pool)
+            .await;
+        if dbstat.is_err() {
+            return self.walk_pages().await;
+        }
+        let mut storage: BTreeMap<String, (Storage, Vec<Page>)> = BTreeMap::new();
+        let mut rows = sqlx::query(
+            r#"
+            SELECT d.name, coalesce(s.tbl_name, d.name) AS tbl_name,
+                   coalesce(s.type = 'index', 0) AS is_index,
+                   d.path, d.pageno, d.pgsize, d.payload, d.unused
+            FROM dbstat d LEFT JOIN sqlite_schema s ON s.name = d.name
+            "#,
+        )
+        .fetch(&self.pool);
+        let unsigned = |row: &SqliteRow, column: &str| -> Result<u64> {
+            Ok(u64::try_from(row.try_get::<i64, _>(column)?)?)
+        };
+        while let Some(row) = rows.try_next().await? {
+            let name: String = row.try_get("name")?;
+            let (totals, pages) = match storage.get_mut(&name) {
+                Some(entry) => entry,
+                None => storage.entry(name.clone()).or_insert((
+                    Storage {
+                        name,
+                        table: row.try_get("tbl_name")?,
+                        index: row.try_get("is_index")?,
+                        ..Storage::default()
+                    },
+                    Vec::new(),
+                )),
+            };
+            totals.pages += 1;
+            totals.bytes += unsigned(&row, "pgsize")?;
+            totals.payload += unsigned(&row, "payload")?;
+            totals.unused += unsigned(&row, "unused")?;
+            let path: String = row.try_get("path")?;
+            pages.push((
+                btree_position(&path),
+                u32::try_from(row.try_get::<i64, _>("pageno")?)?,
+            ));
+        }
+        // A jump in page numbers between neighbours in b-tree order is a
+        // fragmented page.
+        Ok(storage
+            .into_values()
+            .map(|(mut totals, mut pages)| {
+                pages.sort_unstable();
+                totals.fragmented = pages
+                    .windows(2)
+                    .filter(|pair| pair[1].1 != pair[0].1 + 1)
+                    .count() as u64;
+                totals
             })
+            .collect())
+    }
+
+    async fn version(&self) -> Result<Version> {
+        let data_version = sqlx::query_scalar("PRAGMA data_version")
+            .fetch_one(&self.pool)
+            .await?;
+        let path: String = sqlx::query("PRAGMA database_list")
+            .fetch_one(&self.pool)
+            .await?
+            .try_get(2)?;
+        let modified = [path.clone(), format!("{path}-wal")]
+            .iter()
+            .filter(|_| !path.is_empty())
+            .filter_map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+            .max();
+        Ok(Version {
+            data_version,
+            modified,
+        })
+    }
+
+    fn interrupt(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let handle = self.handle.load(Ordering::SeqCst);
+        if !handle.is_null() {
+            // SAFETY: the pool never closes its only connection while `self`
+            // is alive, and sqlite3_interrupt may be called from any thread.
+            unsafe { libsqlite3_sys::sqlite3_interrupt(handle) };
+        }
+    }
+
+    fn stats(&self) -> Vec<QueryStats> {
+        self.log
+            .lock()
+            .expect("stats log poisoned")
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+impl SqliteDb {
+    /// Runs `sql` with `params` bound to its placeholders, converting each
+    /// cell with `cell` and logging its [`QueryStats`].
+    async fn run(
+        &self,
+        sql: &str,
+        params: &[Value],
+        cell: fn(&SqliteRow, usize) -> Value,
+    ) -> Result<ResultSet> {
+        let mut conn = self.pool.acquire().await?;
+        let probe = Probe::start(&mut conn, sql).await?;
+
+        let statement = (&mut *conn)
+            .prepare(AssertSqlSafe(sql).into_sql_str())
+            .await?;
+        let columns = statement
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
             .collect();
-        Ok(result)
+
+        let query = params.iter().fold(statement.query(), bind);
+        let rows: Vec<Vec<Value>> = query
+            .fetch_all(&mut *conn)
+            .await?
+            .iter()
+            .map(|row| (0..row.len()).map(|ordinal| cell(row, ordinal)).collect())
+            .collect();
+
+        let stats = probe.finish(&mut conn, rows.len()).await?;
+        self.record(&stats);
+
+        Ok(ResultSet {
+            columns,
+            rows,
+            stats: Some(stats),
+        })
+    }
+
+    /// Adds `stats` to the session log, dropping the oldest entry when full.
+    fn record(&self, stats: &QueryStats) {
+        let mut log = self.log.lock().expect("stats log poisoned");
+        if log.len() == STATS_LOG_SIZE {
+            log.pop_front();
+        }
+        log.push_back(stats.clone());
+    }
+
+    /// Runs `create`, if any, and inserts `rows` in one transaction, rolled
+    /// back when any of them fails.
+    async fn write_rows(
+        &self,
+        create: Option<String>,
+        table: &str,
+        columns: &[String],
+        rows: &[Vec<Value>],
+    ) -> Result<u64> {
+        if self.read_only {
+            bail!("the database is opened read-only");
+        }
+        let names: Vec<String> = columns.iter().map(|c| Ident::new(c).to_string()).collect();
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            Ident::new(table),
+            names.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+
+        let mut tx = self.pool.begin().await?;
+        if let Some(create) = create {
+            let probe = Probe::start(&mut tx, &create).await?;
+            sqlx::query(AssertSqlSafe(create)).execute(&mut *tx).await?;
+            self.record(&probe.finish(&mut tx, 0).await?);
+        }
+        // Logged once for all rows.
+        let probe = Probe::start(&mut tx, &insert).await?;
+        for row in rows {
+            let query = sqlx::query(AssertSqlSafe(insert.as_str()));
+            let query = row.iter().take(columns.len()).fold(query, bind);
+            query.execute(&mut *tx).await?;
+        }
+        self.record(&probe.finish(&mut tx, 0).await?);
+        tx.commit().await?;
+        Ok(rows.len() as u64)
+    }
+
+    /// Counts the values of the SQL expression `value` over `table` in
+    /// `buckets` equally wide buckets from `low` to `high`, ignoring NULL.
+    async fn histogram(
+        &self,
+        table: &str,
+        value: &str,
+        low: f64,
+        high: f64,
+        buckets: usize,
+    ) -> Result<Histogram> {
+        // A single distinct value all falls into the first bucket.
+        let width = if high > low { high - low } else { 1.0 };
+        let query = format!(
+            "SELECT min(CAST(({value} - ?1) * ?2 / ?3 AS INTEGER), ?2 - 1) AS bucket, count(*) \
+             FROM {} WHERE {value} IS NOT NULL GROUP BY bucket",
+            Ident::new(table)
+        );
+        let rows = sqlx::query(AssertSqlSafe(query))
+            .bind(low)
+            .bind(i64::try_from(buckets)?)
+            .bind(width)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut counts = vec![0; buckets];
+        for row in rows {
+            let bucket = usize::try_from(row.try_get::<i64, _>(0)?)?;
+            if let Some(count) = counts.get_mut(bucket) {
+                *count = u64::try_from(row.try_get::<i64, _>(1)?)?;
+            }
+        }
+        Ok(Histogram {
+            low,
+            high,
+            buckets: counts,
+        })
+    }
+
+    /// Runs the steps of an online backup, see [`Database::backup`].
+    async fn copy_pages(
+        &self,
+        schema: &str,
+        path: &str,
+        mut progress: impl FnMut(u64, u64) + Send,
+    ) -> Result<u64> {
+        use libsqlite3_sys::*;
+
+        let mut conn = self.pool.acquire().await?;
+        let mut handle = conn.lock_handle().await?;
+        let mut backup = Backup::start(handle.as_raw_handle().as_ptr(), schema, path)?;
+        self.cancelled.store(false, Ordering::SeqCst);
+        let mut busy_since = None;
+        loop {
+            if self.cancelled.swap(false, Ordering::SeqCst) {
+                bail!("backup cancelled");
+            }
+            // SAFETY: the source connection stays locked by `handle` and the
+            // backup is alive until `backup` is dropped.
+            let (rc, remaining, total) = unsafe {
+                let rc = sqlite3_backup_step(backup.backup, BACKUP_STEP_PAGES);
+                (
+                    rc,
+                    sqlite3_backup_remaining(backup.backup),
+                    sqlite3_backup_pagecount(backup.backup),
+                )
+            };
+            let total = u64::try_from(total).unwrap_or(0);
+            progress(total - u64::try_from(remaining).unwrap_or(0), total);
+            match rc {
+                SQLITE_DONE => break backup.finish().map(|()| total),
+                SQLITE_OK => {
+                    busy_since = None;
+                    tokio::task::yield_now().await
+                }
+                // Another process holds a lock on the database, try again
+                // for a while.
+                SQLITE_BUSY | SQLITE_LOCKED => {
+                    let since = *busy_since.get_or_insert_with(Instant::now);
+                    if since.elapsed() >= BACKUP_BUSY_TIMEOUT {
+                        bail!(
+                            "backup failed: the database stayed locked by another connection \
+                             for {} seconds",
+                            BACKUP_BUSY_TIMEOUT.as_secs()
+                        );
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await
+                }
+                _ => bail!("backup failed: {}", backup.error()),
+            }
+        }
+    }
+
+    /// Storage analysis reading the database file directly, for SQLite
+    /// builds without `dbstat`. Refused while changes are in the WAL, which
+    /// it does not read.
+    async fn walk_pages(&self) -> Result<Vec<Storage>> {
+        let path: String = sqlx::query("PRAGMA database_list")
+            .fetch_one(&self.pool)
+            .await?
+            .try_get(2)?;
+        if path.is_empty() {
+            bail!("storage analysis of in-memory databases needs the dbstat table");
+        }
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&self.pool)
+            .await?;
+        let wal = std::fs::metadata(format!("{path}-wal")).map_or(0, |m| m.len());
+        if journal_mode.eq_ignore_ascii_case("wal") && wal > 0 {
+            bail!(
+                "storage analysis without the dbstat table reads the database file, but \
+                 {wal} bytes of changes are still in the WAL: run a checkpoint first"
+            );
+        }
+        let mut roots = vec![Root {
+            name: "sqlite_schema".to_string(),
+            table: "sqlite_schema".to_string(),
+            index: false,
+            page: 1,
+        }];
+        let rows = sqlx::query(
+            "SELECT name, tbl_name, type = 'index' AS is_index, rootpage FROM sqlite_schema \
+             WHERE rootpage > 0",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            roots.push(Root {
+                name: row.try_get("name")?,
+                table: row.try_get("tbl_name")?,
+                index: row.try_get("is_index")?,
+                page: u32::try_from(row.try_get::<i64, _>("rootpage")?)?,
+            });
+        }
+
+        tokio::task::spawn_blocking(move || pages::analyze(&path, roots)).await?
+    }
+}
+
+/// Pages copied per backup step. Between steps the source is unlocked, so
+/// other processes can keep writing while a large database is copied.
+const BACKUP_STEP_PAGES: i32 = 256;
+/// How long a backup waits for the lock of another connection to go away.
+const BACKUP_BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running online backup into a connection of its own to the new file.
+struct Backup {
+    dest: *mut libsqlite3_sys::sqlite3,
+    backup: *mut libsqlite3_sys::sqlite3_backup,
+}
+
+// SAFETY: both handles are only used by the task owning the `Backup`, and
+// SQLite is built in serialized threading mode.
+unsafe impl Send for Backup {}
+
+impl Backup {
+    fn start(source: *mut libsqlite3_sys::sqlite3, schema: &str, path: &str) -> Result<Self> {
+        use libsqlite3_sys::*;
+
+        let path = CString::new(path)?;
+        let schema = CString::new(schema)?;
+        let mut backup = Backup {
+            dest: ptr::null_mut(),
+            backup: ptr::null_mut(),
+        };
+        // SAFETY: the strings outlive the calls, and a destination handle
+        // is closed by `Drop` even when opening it failed.
+        unsafe {
+            let flags = SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE;
+            if sqlite3_open_v2(path.as_ptr(), &mut backup.dest, flags, ptr::null()) != SQLITE_OK {
+                bail!(
+                    "cannot create {}: {}",
+                    path.to_string_lossy(),
+                    backup.error()
+                );
+            }
+            backup.backup =
+                sqlite3_backup_init(backup.dest, c"main".as_ptr(), source, schema.as_ptr());
+        }
+        if backup.backup.is_null() {
+            bail!("cannot start backup: {}", backup.error());
+        }
+        Ok(backup)
+    }
+
+    /// Releases the backup, returning the error that stopped it, if any.
+    fn finish(&mut self) -> Result<()> {
+        // SAFETY: `backup` is alive and is not used after being finished.
+        let rc = unsafe { libsqlite3_sys::sqlite3_backup_finish(self.backup) };
+        self.backup = ptr::null_mut();
+        if rc != libsqlite3_sys::SQLITE_OK {
+            bail!("backup failed: {}", self.error());
+        }
+        Ok(())
+    }
+
+    /// Last error of the destination connection.
+    fn error(&self) -> String {
+        // SAFETY: sqlite3_errmsg accepts a null handle and returns a string
+        // owned by SQLite that stays valid until the next call.
+        unsafe { CStr::from_ptr(libsqlite3_sys::sqlite3_errmsg(self.dest)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Drop for Backup {
+    fn drop(&mut self) {
+        // SAFETY: both calls accept null pointers, and neither handle is
+        // used afterwards.
+        unsafe {
+            libsqlite3_sys::sqlite3_backup_finish(self.backup);
+            libsqlite3_sys::sqlite3_close(self.dest);
+        }
+    }
+}
+
+/// Counters of one statement, read from its prepared statement, and of
+/// the connection, compared before and after the statement runs.
+#[derive(Debug, Default)]
+struct Counters {
+    changes: i64,
+    vm_steps: i64,
+    fullscan_steps: i64,
+    sorts: i64,
+    cache_hits: i64,
+    cache_misses: i64,
+}
+
+impl Counters {
+    /// Reads the counters of the prepared statement of `sql`, resetting
+    /// them, and those of the connection. A statement not prepared yet
+    /// starts from zero, one dropped from the statement cache meanwhile
+    /// takes its counts with it.
+    async fn read(conn: &mut SqliteConnection, sql: &str) -> Result<Self> {
+        use libsqlite3_sys::*;
+
+        let sql = normalize(sql);
+        let mut handle = conn.lock_handle().await?;
+        let db = handle.as_raw_handle().as_ptr();
+        let mut counters = Counters::default();
+        // SAFETY: the locked handle gives exclusive access to the connection,
+        // sqlite3_next_stmt only yields statements that are still alive, and
+        // their text lives as long as they do.
+        unsafe {
+            counters.changes = sqlite3_total_changes64(db);
+            let mut stmt = sqlite3_next_stmt(db, ptr::null_mut());
+            while !stmt.is_null() {
+                let text = sqlite3_sql(stmt);
+                if !text.is_null() && normalize(&CStr::from_ptr(text).to_string_lossy()) == sql {
+                    let status = |op| i64::from(sqlite3_stmt_status(stmt, op, 1));
+                    counters.vm_steps += status(SQLITE_STMTSTATUS_VM_STEP);
+                    counters.fullscan_steps += status(SQLITE_STMTSTATUS_FULLSCAN_STEP);
+                    counters.sorts += status(SQLITE_STMTSTATUS_SORT);
+                }
+                stmt = sqlite3_next_stmt(db, stmt);
+            }
+            let (mut current, mut highwater) = (0, 0);
+            sqlite3_db_status(
+                db,
+                SQLITE_DBSTATUS_CACHE_HIT,
+                &mut current,
+                &mut highwater,
+                0,
+            );
+            counters.cache_hits = i64::from(current);
+            sqlite3_db_status(
+                db,
+                SQLITE_DBSTATUS_CACHE_MISS,
+                &mut current,
+                &mut highwater,
+                0,
+            );
+            counters.cache_misses = i64::from(current);
+        }
+        Ok(counters)
+    }
+
+    /// Statistics of the statement that ran between `before` and `self`.
+    /// The statement's own counters were reset by `before`.
+    fn since(&self, before: &Self, sql: &str, elapsed: Duration, rows: usize) -> QueryStats {
+        let delta = |after: i64, before: i64| u64::try_from(after - before).unwrap_or(0);
+        QueryStats {
+            sql: sql.to_string(),
+            elapsed,
+            rows_returned: rows,
+            rows_changed: delta(self.changes, before.changes),
+            vm_steps: self.vm_steps.unsigned_abs(),
+            fullscan_steps: self.fullscan_steps.unsigned_abs(),
+            sorts: self.sorts.unsigned_abs(),
+            cache_hits: delta(self.cache_hits, before.cache_hits),
+            cache_misses: delta(self.cache_misses, before.cache_misses),
+        }
+    }
+}
+
+/// Text of a statement as compared with that of a prepared one, which
+/// keeps the semicolon ending it.
+fn normalize(sql: &str) -> &str {
+    sql.trim().trim_end_matches(';').trim_end()
+}
+
+/// Statement being measured, with the counters it started from.
+struct Probe {
+    sql: String,
+    start: Instant,
+    before: Counters,
+}
+
+impl Probe {
+    async fn start(conn: &mut SqliteConnection, sql: &str) -> Result<Self> {
+        let before = Counters::read(conn, sql).await?;
+        Ok(Self {
+            sql: sql.to_string(),
+            start: Instant::now(),
+            before,
+        })
+    }
+
+    /// Statistics of the statement since it started, having returned
+    /// `rows` rows.
+    async fn finish(self, conn: &mut SqliteConnection, rows: usize) -> Result<QueryStats> {
+        let elapsed = self.start.elapsed();
+        let after = Counters::read(conn, &self.sql).await?;
+        Ok(after.since(&self.before, &self.sql, elapsed, rows))
+    }
+}
+
+/// Step from a b-tree page to a child, or to an overflow page of a cell.
+/// Children sort before overflow pages, as the page walk visits them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PathStep {
+    Child(u32),
+    Overflow { cell: u32, page: u32 },
+}
+
+/// Position of a page in its b-tree and its page number.
+type Page = (Vec<PathStep>, u32);
+
+/// Position of a page in its b-tree from its dbstat `path`, e.g.
+/// `/01a/003+000002`: hexadecimal child cell numbers, ending in a cell and
+/// the number of an overflow page of it. Ordered by position, the pages are
+/// in the order the page walk visits them.
+fn btree_position(path: &str) -> Vec<PathStep> {
+    let hex = |digits: &str| u32::from_str_radix(digits, 16).unwrap_or(u32::MAX);
+    path.split('/')
+        .filter(|step| !step.is_empty())
+        .map(|step| match step.split_once('+') {
+            Some((cell, page)) => PathStep::Overflow {
+                cell: hex(cell),
+                page: hex(page),
+            },
+            None => PathStep::Child(hex(step)),
+        })
+        .collect()
+}
+
+/// Binds `value` to the next placeholder of `query` as NULL, integer, real
+/// or text.
+fn bind<'q>(
+    query: Query<'q, Sqlite, SqliteArguments>,
+    value: &'q Value,
+) -> Query<'q, Sqlite, SqliteArguments> {
+    match value {
+        Value::Null => query.bind(None::<String>),
+        Value::Bool(b) => query.bind(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => query.bind(i),
+            None => query.bind(n.as_f64()),
+        },
+        Value::String(s) => query.bind(s.as_str()),
+        value => query.bind(value.to_string()),
+    }
+}
+
+/// Renders a cell as text based on the storage class of the stored value.
+fn value(row: &SqliteRow, ordinal: usize) -> Value {
+    let Ok(raw) = row.try_get_raw(ordinal) else {
+        return json!("null");
+    };
+    if raw.is_null() {
+        return json!("null");
+    }
+    let type_name = raw.type_info().name().to_string();
+    match type_name.as_str() {
+        "INTEGER" => json!(row.get::<i64, _>(ordinal).to_string()),
+        "REAL" => json!(row.get::<f64, _>(ordinal).to_string()),
+        "BLOB" => json!(hex::encode(row.get::<Vec<u8>, _>(ordinal))),
+        _ => json!(row.get::<String, _>(ordinal)),
+    }
+}
+
+/// Converts a cell to JSON by the storage class of the stored value: NULL
+/// as null, INTEGER and REAL as numbers, and BLOBs as hex text.
+fn typed_value(row: &SqliteRow, ordinal: usize) -> Value {
+    let Ok(raw) = row.try_get_raw(ordinal) else {
+        return Value::Null;
+    };
+    if raw.is_null() {
+        return Value::Null;
+    }
+    let type_name = raw.type_info().name().to_string();
+    match type_name.as_str() {
+        "INTEGER" => json!(row.get::<i64, _>(ordinal)),
+        "REAL" => json!(row.get::<f64, _>(ordinal)),
+        "BLOB" => json!(hex::encode(row.get::<Vec<u8>, _>(ordinal))),
+        _ => json!(row.get::<String, _>(ordinal)),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::schema::SchemaChange;
 
     async fn test_db() -> SqliteDb {
         SqliteDb::memory().await.unwrap()
@@ -127,9 +1307,655 @@ mod tests {
         assert_eq!(db.tables().await.unwrap().len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_db_interrupt() {
+        let db = test_db().await;
+        let running = db.clone();
+        let query = tokio::spawn(async move {
+            sqlx::query(
+                r#"
+                WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 1e9)
+                SELECT count(*) FROM c
+                "#,
+            )
+            .fetch_one(&running.pool)
+            .await
+        });
+
+        while !query.is_finished() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            db.interrupt();
+        }
+        assert!(query.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_db_nasty_table_names() {
+        let db = test_db().await;
+        let names = [
+            "order",
+            "my table",
+            r#"quo"ted"#,
+            "tábla ünï",
+            "x; DROP TABLE y; --",
+            "[bracketed]",
+        ];
+        for name in names {
+            let query = format!(
+                "CREATE TABLE {} ({} INTEGER, {} TEXT)",
+                Ident::new(name),
+                Ident::new("select"),
+                Ident::new(r#"col "b""#)
+            );
+            sqlx::query(AssertSqlSafe(query.as_str()))
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            let query = format!("INSERT INTO {} VALUES (1, 'one')", Ident::new(name));
+            sqlx::query(AssertSqlSafe(query.as_str()))
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(db.tables().await.unwrap(), names);
+        for name in names {
+            assert_eq!(
+                db.columns(name).await.unwrap(),
+                vec!["select".to_string(), r#"col "b""#.to_string()]
+            );
+            assert_eq!(db.column_types(name).await.unwrap(), ["INTEGER", "TEXT"]);
+            assert_eq!(db.count(name).await.unwrap(), 1);
+            assert_eq!(
+                db.rows(name, None).await.unwrap().rows,
+                vec![vec![json!("1"), json!("one")]]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_db_query() {
+        let db = test_db().await;
+        db.query("CREATE TABLE t (a INTEGER, b REAL, c TEXT, d BLOB)")
+            .await
+            .unwrap();
+        db.query("INSERT INTO t VALUES (1, 1.5, 'x', x'ff'), (NULL, NULL, NULL, NULL)")
+            .await
+            .unwrap();
+
+        let result = db.query("SELECT * FROM t").await.unwrap();
+        assert_eq!(result.columns, vec!["a", "b", "c", "d"]);
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![json!("1"), json!("1.5"), json!("x"), json!("ff")],
+                vec![json!("null"), json!("null"), json!("null"), json!("null")],
+            ]
+        );
+
+        let result = db.query("SELECT a AS first FROM t WHERE 0").await.unwrap();
+        assert_eq!(result.columns, vec!["first"]);
+        assert!(result.rows.is_empty());
+
+        let head = db.head("t", 2).await.unwrap();
+        assert_eq!(
+            head.rows,
+            vec![
+                vec![json!(1), json!(1.5), json!("x"), json!("ff")],
+                vec![Value::Null, Value::Null, Value::Null, Value::Null],
+            ]
+        );
+        let result = db.query_values("SELECT '1', 2").await.unwrap();
+        assert_eq!(result.rows, vec![vec![json!("1"), json!(2)]]);
+        let all = "SELECT * FROM t;";
+        assert_eq!(db.position(all, &[], "a IS NULL").await.unwrap(), Some(1));
+        assert_eq!(db.position(all, &[], "c = 'y'").await.unwrap(), None);
+        let sorted = "SELECT c AS name FROM t ORDER BY a DESC";
+        assert_eq!(
+            db.position(sorted, &[], "name = 'x'").await.unwrap(),
+            Some(0)
+        );
+
+        let filter = WhereClause {
+            sql: "a IS NULL OR c IN (?, ?)".to_string(),
+            params: vec![json!("x"), json!(2)],
+        };
+        let rows = db.rows("t", Some(&filter)).await.unwrap().rows;
+        assert_eq!(rows.len(), 2);
+        let filter = WhereClause {
+            sql: "b < ?".to_string(),
+            params: vec![json!(2)],
+        };
+        let result = db.rows("t", Some(&filter)).await.unwrap();
+        assert_eq!(result.stats.unwrap().rows_returned, 1);
+        assert_eq!(
+            result.rows,
+            vec![vec![json!("1"), json!("1.5"), json!("x"), json!("ff")]]
+        );
+        assert_eq!(
+            db.position(
+                &WhereClause::select("t", Some(&filter)),
+                &filter.params,
+                "c = 'x'"
+            )
+            .await
+            .unwrap(),
+            Some(0)
+        );
+        assert!(db.query("SELECT * FROM missing").await.is_err());
+
+        assert!(db.reads_only("SELECT * FROM t -- all").await.unwrap());
+        assert!(db
+            .reads_only("WITH x AS (SELECT 1) SELECT * FROM x")
+            .await
+            .unwrap());
+        assert!(db.reads_only("PRAGMA table_info(t)").await.unwrap());
+        assert!(!db.reads_only("DELETE FROM t").await.unwrap());
+        assert!(!db.reads_only("SELECT 1; DROP TABLE t").await.unwrap());
+        assert!(!db.reads_only("CREATE TABLE u (a)").await.unwrap());
+        assert!(db.reads_only("SELECT * FROM missing").await.is_err());
+        assert_eq!(db.tables().await.unwrap(), ["t"]);
+    }
+
     #[tokio::test]
     async fn test_db_table_schema_unhappy() {
         let db = test_db().await;
         assert!(db.schema("users").await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_db_query_stats() {
+        let db = test_db().await;
+        db.query("CREATE TABLE t (a INTEGER)").await.unwrap();
+        db.query("INSERT INTO t VALUES (3), (1), (2)")
+            .await
+            .unwrap();
+        let result = db.query("SELECT a FROM t ORDER BY a").await.unwrap();
+
+        let log = db.stats();
+        assert_eq!(result.stats.as_ref(), log.last());
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[1].rows_changed, 3);
+        let select = &log[2];
+        assert_eq!(select.sql, "SELECT a FROM t ORDER BY a");
+        assert_eq!(select.rows_returned, 3);
+        assert_eq!(select.rows_changed, 0);
+        assert_eq!(select.sorts, 1);
+        assert!(select.fullscan_steps >= 2);
+        assert!(select.vm_steps > 0);
+
+        let table = QueryStats::to_result_set(&log);
+        assert_eq!(table.columns[1], "elapsed_ms");
+        assert_eq!(table.rows[2][2], json!("3"));
+
+        // Counted for the statement alone, not since it was first cached.
+        let again = db.query("SELECT a FROM t ORDER BY a").await.unwrap();
+        assert_eq!(again.stats.unwrap().sorts, 1);
+        assert_eq!(db.count("t").await.unwrap(), 3);
+        let count = db.stats().pop().unwrap();
+        assert_eq!(count.sql, r#"SELECT COUNT(*) FROM "t""#);
+        assert_eq!(count.rows_returned, 1);
+        db.execute(&["UPDATE t SET a = a + 1".to_string()])
+            .await
+            .unwrap();
+        let update = db.stats().pop().unwrap();
+        assert_eq!(update.sql, "UPDATE t SET a = a + 1");
+        assert_eq!(update.rows_changed, 3);
+        assert!(update.fullscan_steps >= 2);
+
+        for _ in 0..STATS_LOG_SIZE {
+            db.query("SELECT 1").await.unwrap();
+        }
+        let log = db.stats();
+        assert_eq!(log.len(), STATS_LOG_SIZE);
+        assert!(log.iter().all(|stats| stats.sql == "SELECT 1"));
+    }
+
+    #[tokio::test]
+    async fn test_db_info() {
+        let db = test_db().await;
+        db.query("PRAGMA user_version = 7").await.unwrap();
+        db.query("CREATE TABLE t (a)").await.unwrap();
+
+        let info = db.info().await.unwrap();
+        assert_eq!(info.user_version, 7);
+        assert_eq!(info.encoding, "UTF-8");
+        assert_eq!(info.journal_mode, "memory");
+        assert_eq!(info.wal_size, None);
+        assert!(info.page_count >= 2);
+        assert_eq!(info.file_size, info.page_size * info.page_count);
+        assert!(!info.sqlite_version.is_empty());
+        assert!(!info.compile_options.is_empty());
+
+        let table = info.to_result_set();
+        assert_eq!(table.columns, ["property", "value"]);
+        assert_eq!(table.rows[8], vec![json!("user_version"), json!("7")]);
+    }
+
+    #[tokio::test]
+    async fn test_db_json_tree() {
+        let db = test_db().await;
+        let nodes = db
+            .json_tree(r#"{"a": [1, "x"], "b c": null, "d": true}"#)
+            .await
+            .unwrap();
+        let summary: Vec<_> = nodes
+            .iter()
+            .map(|n| {
+                (
+                    n.key.as_deref(),
+                    n.kind.as_str(),
+                    n.value.as_deref(),
+                    n.path.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    None,
+                    "object",
+                    Some(r#"{"a":[1,"x"],"b c":null,"d":true}"#),
+                    "$"
+                ),
+                (Some("a"), "array", Some(r#"[1,"x"]"#), "$.a"),
+                (Some("0"), "integer", Some("1"), "$.a[0]"),
+                (Some("1"), "text", Some("x"), "$.a[1]"),
+                (Some("b c"), "null", None, r#"$."b c""#),
+                (Some("d"), "true", Some("1"), "$.d"),
+            ]
+        );
+        assert_eq!(nodes[0].parent, None);
+        assert_eq!(nodes[2].parent, Some(nodes[1].id));
+
+        assert!(db.json_tree("{oops").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_db_column_stats() {
+        let db = test_db().await;
+        db.query("CREATE TABLE t (n, s)").await.unwrap();
+        db.query(
+            "INSERT INTO t VALUES (1, 'a'), (2, 'bb'), (2, 'bb'), (17, 'dddd'), (NULL, NULL), \
+             ('x', 'a')",
+        )
+        .await
+        .unwrap();
+
+        let stats = db.column_stats("t", "n").await.unwrap();
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.distinct, 4);
+        assert_eq!(stats.nulls, 1);
+        assert_eq!(stats.min.as_deref(), Some("1"));
+        assert_eq!(stats.max.as_deref(), Some("x"));
+        assert_eq!(stats.average, Some(5.5));
+        let values = stats.values.unwrap();
+        assert_eq!((values.low, values.high), (1.0, 17.0));
+        assert_eq!(values.buckets.len(), ColumnStats::BUCKETS);
+        assert_eq!(values.buckets[0], 1);
+        assert_eq!(values.buckets[1], 2);
+        assert_eq!(values.buckets[15], 1);
+        let lengths = stats.lengths.unwrap();
+        assert_eq!(lengths.buckets, [1]);
+        assert_eq!(stats.top[0], ("2".to_string(), 2));
+        assert_eq!(stats.top.len(), 4);
+
+        let stats = db.column_stats("t", "s").await.unwrap();
+        assert_eq!(stats.average, None);
+        assert_eq!(stats.values, None);
+        let lengths = stats.lengths.unwrap();
+        assert_eq!((lengths.low, lengths.high), (1.0, 4.0));
+        assert_eq!(lengths.buckets, [2, 2, 0, 1]);
+        assert_eq!(
+            stats.top,
+            [
+                ("a".to_string(), 2),
+                ("bb".to_string(), 2),
+                ("dddd".to_string(), 1)
+            ]
+        );
+
+        assert!(db.column_stats("missing", "n").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_db_storage() {
+        let path = std::env::temp_dir().join(format!("sqliters-storage-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = SqliteDb::connect(path.to_str().unwrap(), true)
+            .await
+            .unwrap();
+        db.query("CREATE TABLE t (a INTEGER PRIMARY KEY, b TEXT)")
+            .await
+            .unwrap();
+        db.query("CREATE INDEX t_b ON t (b)").await.unwrap();
+        db.query(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500) \
+             INSERT INTO t SELECT i, printf('%.*c', i * 7 % 3000, 'x') FROM n",
+        )
+        .await
+        .unwrap();
+
+        let mut dbstat = db.storage().await.unwrap();
+        let mut walked = db.walk_pages().await.unwrap();
+        dbstat.sort_by(|a, b| a.name.cmp(&b.name));
+        walked.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(walked, dbstat);
+
+        let t = dbstat.iter().find(|s| s.name == "t").unwrap();
+        assert!(!t.index);
+        assert!(t.pages > 100);
+        assert!(t.payload > 500_000);
+        let index = dbstat.iter().find(|s| s.name == "t_b").unwrap();
+        assert!(index.index);
+        assert_eq!(index.table, "t");
+
+        // The page walk does not read changes in the WAL.
+        db.query("PRAGMA journal_mode = WAL").await.unwrap();
+        db.query("INSERT INTO t (b) VALUES ('y')").await.unwrap();
+        let err = db.walk_pages().await.unwrap_err().to_string();
+        assert!(err.contains("run a checkpoint first"), "{err}");
+        db.query("PRAGMA wal_checkpoint(TRUNCATE)").await.unwrap();
+        assert!(db.walk_pages().await.is_ok());
+
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(format!("{}-wal", path.display()));
+        let _ = std::fs::remove_file(format!("{}-shm", path.display()));
+    }
+
+    #[test]
+    fn btree_positions() {
+        let mut paths = ["/001+000001", "/", "/1000/", "/001/", "/00f/", "/001/000/"];
+        paths.sort_by_key(|path| btree_position(path));
+        assert_eq!(
+            paths,
+            ["/", "/001/", "/001/000/", "/00f/", "/1000/", "/001+000001"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_db_maintain() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("sqliters-maintain-{}.db", std::process::id()));
+        let copy = dir.join(format!("sqliters-maintain-{}-copy.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let copy = copy.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(&copy);
+
+        let db = SqliteDb::connect(path, true).await.unwrap();
+        db.query("CREATE TABLE t (a TEXT)").await.unwrap();
+        db.query(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 200) \
+             INSERT INTO t SELECT printf('%.*c', 2000, 'x') FROM n",
+        )
+        .await
+        .unwrap();
+        db.query("DELETE FROM t WHERE rowid > 10").await.unwrap();
+        let before = db.info().await.unwrap();
+        assert!(before.freelist_count > 0);
+
+        let copied = db
+            .maintain(&Maintenance::VacuumInto(copy.clone()))
+            .await
+            .unwrap();
+        assert_eq!(copied.path, copy);
+        assert_eq!(copied.freelist_count, 0);
+        assert!(copied.page_count < before.page_count);
+        assert_eq!(copied.file_size, copied.page_size * copied.page_count);
+
+        let after = db.maintain(&Maintenance::Vacuum).await.unwrap();
+        assert_eq!(after.freelist_count, 0);
+        assert_eq!(after.page_count, copied.page_count);
+        for action in [
+            Maintenance::Analyze,
+            Maintenance::Reindex,
+            Maintenance::Optimize,
+            Maintenance::Checkpoint,
+        ] {
+            db.maintain(&action).await.unwrap();
+        }
+
+        let read_only = SqliteDb::connect_read_only(&copy).await.unwrap();
+        assert!(read_only.is_read_only());
+        assert!(read_only.maintain(&Maintenance::Analyze).await.is_err());
+        assert!(read_only.query("DELETE FROM t").await.is_err());
+        let (lookup, params) = ("SELECT * FROM t WHERE rowid = ?", [1.into()]);
+        let plan = read_only.explain_with(&[], lookup, &params).await;
+        assert!(plan.is_ok());
+        let index = ["CREATE INDEX t_rowid ON t (rowid)".to_string()];
+        let plan = read_only.explain_with(&index, lookup, &params).await;
+        assert!(plan.is_err());
+
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(&copy);
+    }
+
+    #[tokio::test]
+    async fn test_db_version() {
+        let path = std::env::temp_dir().join(format!("sqliters-version-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let db = SqliteDb::connect(path, true).await.unwrap();
+        db.query("CREATE TABLE t (a)").await.unwrap();
+        let before = db.version().await.unwrap();
+        assert!(before.modified.is_some());
+        assert_eq!(db.version().await.unwrap(), before);
+
+        // Commits on other connections bump data_version, own ones do not.
+        let other = SqliteDb::connect(path, false).await.unwrap();
+        other.query("INSERT INTO t VALUES (1)").await.unwrap();
+        let after = db.version().await.unwrap();
+        assert_ne!(after.data_version, before.data_version);
+
+        assert_eq!(test_db().await.version().await.unwrap().modified, None);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_db_backup() {
+        let dir = std::env::temp_dir();
+        let main = dir.join(format!("sqliters-backup-{}-main.db", std::process::id()));
+        let aux = dir.join(format!("sqliters-backup-{}-aux.db", std::process::id()));
+        let (main, aux) = (main.to_str().unwrap(), aux.to_str().unwrap());
+        let _ = std::fs::remove_file(main);
+        let _ = std::fs::remove_file(aux);
+
+        let db = test_db().await;
+        db.query("CREATE TABLE t (a TEXT)").await.unwrap();
+        db.query(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 300) \
+             INSERT INTO t SELECT printf('%.*c', 3000, 'x') FROM n",
+        )
+        .await
+        .unwrap();
+        db.query("ATTACH ':memory:' AS other").await.unwrap();
+        db.query("CREATE TABLE other.u (b)").await.unwrap();
+
+        let mut steps = Vec::new();
+        let pages = db
+            .backup("main", main, |copied, total| steps.push((copied, total)))
+            .await
+            .unwrap();
+        assert!(steps.len() > 1);
+        assert_eq!(steps.last(), Some(&(pages, pages)));
+        assert!(db.backup("main", main, |_, _| {}).await.is_err());
+        let copy = SqliteDb::connect_read_only(main).await.unwrap();
+        assert_eq!(copy.count("t").await.unwrap(), 300);
+
+        db.backup("other", aux, |_, _| {}).await.unwrap();
+        let copy = SqliteDb::connect_read_only(aux).await.unwrap();
+        assert_eq!(copy.tables().await.unwrap(), ["u"]);
+
+        // A failed backup leaves no file behind.
+        let _ = std::fs::remove_file(main);
+        assert!(db.backup("missing", main, |_, _| {}).await.is_err());
+        assert!(!std::path::Path::new(main).exists());
+        let cancel = db.clone();
+        let err = db
+            .backup("main", main, |_, _| cancel.interrupt())
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "backup cancelled");
+        assert!(!std::path::Path::new(main).exists());
+
+        let _ = std::fs::remove_file(aux);
+    }
+
+    #[tokio::test]
+    async fn test_db_check() {
+        let db = test_db().await;
+        db.query("CREATE TABLE parent (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        db.query("CREATE TABLE child (id INTEGER PRIMARY KEY, parent REFERENCES parent (id))")
+            .await
+            .unwrap();
+        db.query("INSERT INTO parent VALUES (1)").await.unwrap();
+        db.query("PRAGMA foreign_keys = OFF").await.unwrap();
+        db.query("INSERT INTO child VALUES (1, 1), (2, 5), (3, 6)")
+            .await
+            .unwrap();
+
+        let mut problems = Vec::new();
+        let count = db
+            .check(Check::Integrity, None, |p| problems.push(p))
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+        assert!(problems.is_empty());
+
+        let count = db
+            .check(Check::ForeignKeys, Some("child"), |p| problems.push(p))
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            problems[0],
+            "child rowid 2: no matching row in parent (foreign key 0)"
+        );
+        assert_eq!(
+            db.check(Check::Quick, Some("parent"), |_| {})
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_db_import() {
+        let db = test_db().await;
+        let columns = [
+            ("id".to_string(), "INTEGER"),
+            ("full name".to_string(), "TEXT"),
+        ];
+        let rows = vec![
+            vec![Value::from(1), Value::from("ann")],
+            vec![Value::from(2), Value::Null],
+        ];
+        assert_eq!(
+            db.create_table("my people", &columns, &rows).await.unwrap(),
+            2
+        );
+        let result = db
+            .query("SELECT typeof(id), \"full name\" FROM \"my people\"")
+            .await
+            .unwrap();
+        assert_eq!(result.rows[0], [Value::from("integer"), Value::from("ann")]);
+
+        // A failing row rolls back the whole import.
+        db.query("CREATE TABLE strict (a INTEGER NOT NULL)")
+            .await
+            .unwrap();
+        let rows = vec![vec![Value::from(1)], vec![Value::Null]];
+        assert!(db.insert("strict", &["a".into()], &rows).await.is_err());
+        assert_eq!(db.count("strict").await.unwrap(), 0);
+        assert!(db.create_table("strict", &columns, &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_db_schema_change() {
+        let db = test_db().await;
+        db.query("PRAGMA foreign_keys = ON").await.unwrap();
+        db.query("CREATE TABLE teams (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        db.query(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+             email TEXT NOT NULL UNIQUE, team REFERENCES teams (id) ON DELETE CASCADE, age)",
+        )
+        .await
+        .unwrap();
+        db.query("CREATE INDEX users_age ON users (age)")
+            .await
+            .unwrap();
+        db.query("CREATE VIEW emails AS SELECT email FROM users")
+            .await
+            .unwrap();
+        db.query("INSERT INTO teams VALUES (1)").await.unwrap();
+        db.query("INSERT INTO users VALUES (1, 'a@b', 1, 30)")
+            .await
+            .unwrap();
+
+        let schema = db.table_schema("users").await.unwrap();
+        assert_eq!(schema.columns.len(), 4);
+        assert!(schema.columns[1].not_null);
+        assert_eq!(schema.columns[0].primary_key, 1);
+        assert_eq!(schema.indexes.len(), 2);
+        assert_eq!(schema.indexes[0].columns, ["email"]);
+        assert_eq!(schema.foreign_keys[0].on_delete, "CASCADE");
+        assert!(db.table_schema("missing").await.is_err());
+
+        // Dropping an indexed column takes a rebuild, which keeps the rows,
+        // the unique constraint and the foreign key.
+        let change = SchemaChange::DropColumn {
+            table: "users".into(),
+            column: "age".into(),
+        };
+        db.execute(&change.statements(Some(&schema)).unwrap())
+            .await
+            .unwrap();
+        let schema = db.table_schema("users").await.unwrap();
+        assert_eq!(schema.columns.len(), 3);
+        assert_eq!(schema.indexes.len(), 1);
+        assert_eq!(schema.foreign_keys.len(), 1);
+        assert!(schema.sql.contains("AUTOINCREMENT"));
+        assert_eq!(db.count("users").await.unwrap(), 1);
+        assert_eq!(
+            db.query("SELECT * FROM emails").await.unwrap().rows.len(),
+            1
+        );
+        assert!(db
+            .query("INSERT INTO users (email) VALUES ('a@b')")
+            .await
+            .is_err());
+        // Foreign keys are enforced again afterwards.
+        assert!(db
+            .query("INSERT INTO users (email, team) VALUES ('c@d', 9)")
+            .await
+            .is_err());
+
+        // A failing statement rolls back the earlier ones.
+        let statements = [
+            "ALTER TABLE users ADD COLUMN note".to_string(),
+            "DROP TABLE missing".to_string(),
+        ];
+        assert!(db.execute(&statements).await.is_err());
+        assert_eq!(db.columns("users").await.unwrap().len(), 3);
+
+        // The legacy rename behaviour is kept as the user set it.
+        let legacy = || async {
+            let result = db.query("PRAGMA legacy_alter_table").await.unwrap();
+            result.rows[0][0].clone()
+        };
+        db.query("PRAGMA legacy_alter_table = ON").await.unwrap();
+        db.execute(&["ALTER TABLE users ADD COLUMN note".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(legacy().await, json!("1"));
+        db.query("PRAGMA legacy_alter_table = OFF").await.unwrap();
+        assert!(db.execute(&statements).await.is_err());
+        assert_eq!(legacy().await, json!("0"));
+    }
 }
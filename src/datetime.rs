@@ -0,0 +1,420 @@
+//! Rendering of timestamps, which SQLite stores as ISO 8601 text, Julian
+//! day numbers or Unix epoch integers, as readable local or UTC times.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+use jiff::{civil, tz::TimeZone, Timestamp};
+use serde::{Deserialize, Serialize};
+
+/// Number of values a column's format is detected from.
+const SAMPLE: usize = 20;
+/// Julian day numbers of 0001-01-01 and 9999-12-31.
+const JULIAN_DAYS: std::ops::RangeInclusive<f64> = 1_721_425.5..=5_373_484.5;
+/// Julian day number of the Unix epoch.
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2_440_587.5;
+/// Seconds since the Unix epoch of 1973-03-03 and 2100-01-01, the times a
+/// numeric column is recognised as epoch times in.
+const PLAUSIBLE_SECONDS: std::ops::Range<f64> = 1e8..4_102_444_800.0;
+/// Seconds since the Unix epoch of 0001-01-01 and 9999-12-31 23:59:59.
+const EPOCH_SECONDS: std::ops::RangeInclusive<i64> = -62_135_596_800..=253_402_300_799;
+
+/// How the timestamps of a column are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeFormat {
+    /// Shown as stored, turning off the detection for the column.
+    Raw,
+    /// `YYYY-MM-DD HH:MM:SS` text in UTC, optionally with a fraction and an
+    /// offset.
+    Text,
+    JulianDay,
+    Seconds,
+    Millis,
+    Micros,
+}
+
+/// Time zone timestamps are shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    Local,
+    Utc,
+}
+
+impl TimeFormat {
+    /// Format of the timestamps of `column` with the declared type
+    /// `declared`, from a sample of its `values`. Columns declared as
+    /// `DATE`, `TIME`, `DATETIME` or `TIMESTAMP` are detected in any format.
+    /// Integer and real columns are only detected as epoch times when named
+    /// like one, e.g. `created_at`, and all values fall between 1973 and
+    /// 2100. Others are given a format by the user.
+    pub fn detect<'a>(
+        column: &str,
+        declared: &str,
+        values: impl IntoIterator<Item = &'a str>,
+    ) -> Option<TimeFormat> {
+        let declared = declared.to_ascii_uppercase();
+        let numeric = ["INT", "REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|name| declared.contains(name));
+        let classify = if declared.contains("DATE") || declared.contains("TIME") {
+            classify
+        } else if numeric && time_like(column) {
+            classify_epoch
+        } else {
+            return None;
+        };
+        let mut detected = None;
+        for value in values.into_iter().filter(|v| *v != "null").take(SAMPLE) {
+            let format = classify(value)?;
+            if detected.is_some_and(|detected| detected != format) {
+                return None;
+            }
+            detected = Some(format);
+        }
+        detected
+    }
+
+    /// `value` as a time in `zone`, `None` when it is not a timestamp in
+    /// this format or a date without a time.
+    pub fn render(self, value: &str, zone: Zone) -> Option<String> {
+        let instant = match self {
+            TimeFormat::Raw => None,
+            TimeFormat::Text => Instant::parse(value),
+            TimeFormat::JulianDay => {
+                let day: f64 = value.parse().ok()?;
+                Instant::from_seconds((day - UNIX_EPOCH_JULIAN_DAY) * 86_400.0)
+            }
+            TimeFormat::Seconds => match value.parse::<i64>() {
+                Ok(seconds) => Instant::new(seconds, 0, 0),
+                Err(_) => Instant::from_seconds(value.parse().ok()?),
+            },
+            TimeFormat::Millis => Instant::from_fraction(value.parse().ok()?, 1_000, 3),
+            TimeFormat::Micros => Instant::from_fraction(value.parse().ok()?, 1_000_000, 6),
+        }?;
+        Some(instant.format(zone))
+    }
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeFormat::Raw => "raw",
+            TimeFormat::Text => "text",
+            TimeFormat::JulianDay => "julian",
+            TimeFormat::Seconds => "s",
+            TimeFormat::Millis => "ms",
+            TimeFormat::Micros => "us",
+        })
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        Ok(match input {
+            "raw" => TimeFormat::Raw,
+            "text" => TimeFormat::Text,
+            "julian" => TimeFormat::JulianDay,
+            "s" => TimeFormat::Seconds,
+            "ms" => TimeFormat::Millis,
+            "us" | "µs" => TimeFormat::Micros,
+            _ => bail!("unknown time format: {input}, expected raw, text, julian, s, ms or us"),
+        })
+    }
+}
+
+/// Format of a single value. Integers are epoch seconds, milliseconds or
+/// microseconds by magnitude, or Julian days when smaller and in their
+/// range. Reals are Julian days when in their range, text is ISO 8601.
+fn classify(value: &str) -> Option<TimeFormat> {
+    if let Ok(n) = value.parse::<i64>() {
+        return Some(match n.unsigned_abs() {
+            100_000_000..=99_999_999_999 => TimeFormat::Seconds,
+            100_000_000_000..=99_999_999_999_999 => TimeFormat::Millis,
+            100_000_000_000_000..=99_999_999_999_999_999 => TimeFormat::Micros,
+            _ if JULIAN_DAYS.contains(&(n as f64)) => TimeFormat::JulianDay,
+            _ => TimeFormat::Seconds,
+        });
+    }
+    if let Ok(x) = value.parse::<f64>() {
+        return match x.abs() {
+            x if JULIAN_DAYS.contains(&x) => Some(TimeFormat::JulianDay),
+            x if (1e8..1e11).contains(&x) => Some(TimeFormat::Seconds),
+            _ => None,
+        };
+    }
+    Instant::parse(value).is_some().then_some(TimeFormat::Text)
+}
+
+/// Format of a value of a numeric column named like a time: epoch seconds,
+/// or for integers also milliseconds or microseconds, from 1973 to 2100.
+fn classify_epoch(value: &str) -> Option<TimeFormat> {
+    let units: &[(TimeFormat, f64)] = match value.parse::<i64>() {
+        Ok(_) => &[
+            (TimeFormat::Seconds, 1.0),
+            (TimeFormat::Millis, 1e3),
+            (TimeFormat::Micros, 1e6),
+        ],
+        Err(_) => &[(TimeFormat::Seconds, 1.0)],
+    };
+    let x: f64 = value.parse().ok()?;
+    units
+        .iter()
+        .find(|(_, per_second)| PLAUSIBLE_SECONDS.contains(&(x / per_second)))
+        .map(|(format, _)| *format)
+}
+
+/// Whether `column` is named like a time, by its last word: `created_at`,
+/// `updatedAt`, `event_ts`, `start_time` or `modified`.
+fn time_like(column: &str) -> bool {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut lower = false;
+    for c in column.chars() {
+        if !c.is_alphanumeric() || (lower && c.is_uppercase()) {
+            words.push(std::mem::take(&mut word));
+        }
+        if c.is_alphanumeric() {
+            word.push(c.to_ascii_lowercase());
+        }
+        lower = c.is_lowercase();
+    }
+    words.push(word);
+    words
+        .iter()
+        .rev()
+        .find(|word| !word.is_empty())
+        .is_some_and(|last| {
+            [
+                "at",
+                "ts",
+                "time",
+                "timestamp",
+                "epoch",
+                "created",
+                "updated",
+                "modified",
+            ]
+            .contains(&last.as_str())
+        })
+}
+
+/// Point in time with the digits of its fraction shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Instant {
+    timestamp: Timestamp,
+    /// Digits of the fraction shown.
+    digits: usize,
+}
+
+impl Instant {
+    fn new(seconds: i64, nanos: u32, digits: usize) -> Option<Self> {
+        if !EPOCH_SECONDS.contains(&seconds) {
+            return None;
+        }
+        let timestamp = Timestamp::new(seconds, nanos as i32).ok()?;
+        Some(Self { timestamp, digits })
+    }
+
+    /// `seconds` rounded to milliseconds, showing them when not whole.
+    fn from_seconds(seconds: f64) -> Option<Self> {
+        if !seconds.is_finite() || seconds.abs() > 1e15 {
+            return None;
+        }
+        let millis = (seconds * 1000.0).round() as i64;
+        let digits = if millis % 1000 == 0 { 0 } else { 3 };
+        Self::from_fraction(millis, 1000, digits)
+    }
+
+    /// `value` in units of 1/`per_second` seconds.
+    fn from_fraction(value: i64, per_second: i64, digits: usize) -> Option<Self> {
+        let nanos = value.rem_euclid(per_second) * (1_000_000_000 / per_second);
+        Self::new(value.div_euclid(per_second), nanos as u32, digits)
+    }
+
+    /// Parses `YYYY-MM-DD HH:MM[:SS[.fff]]`, with a `T` in place of the space
+    /// and an optional `Z` or `±HH:MM` offset, as UTC unless offset.
+    fn parse(text: &str) -> Option<Self> {
+        // A date alone is not a point in time.
+        let time = text.get(10..)?.strip_prefix([' ', 'T'])?;
+        let timestamp = match text.parse::<Timestamp>() {
+            Ok(timestamp) => timestamp,
+            Err(_) => text
+                .parse::<civil::DateTime>()
+                .ok()?
+                .to_zoned(TimeZone::UTC)
+                .ok()?
+                .timestamp(),
+        };
+        let digits = time.split_once('.').map_or(0, |(_, fraction)| {
+            fraction
+                .bytes()
+                .take_while(u8::is_ascii_digit)
+                .count()
+                .min(9)
+        });
+        Self::new(timestamp.as_second(), 0, digits).map(|instant| Self {
+            timestamp,
+            ..instant
+        })
+    }
+
+    /// Shown in local time, or in UTC labeled as such when the local time
+    /// zone is unknown.
+    fn format(&self, zone: Zone) -> String {
+        let (zone, utc) = match zone {
+            Zone::Local => local_time_zone().map_or((TimeZone::UTC, true), |zone| (zone, false)),
+            Zone::Utc => (TimeZone::UTC, true),
+        };
+        let time = self.timestamp.to_zoned(zone);
+        let mut text = time.strftime("%Y-%m-%d %H:%M:%S").to_string();
+        if self.digits > 0 {
+            text.push('.');
+            text.push_str(&format!("{:09}", time.subsec_nanosecond())[..self.digits]);
+        }
+        if utc {
+            text.push_str(" UTC");
+        }
+        text
+    }
+}
+
+/// Time zone of the system, following daylight saving time.
+pub fn local_time_zone() -> Result<TimeZone> {
+    Ok(TimeZone::try_system()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        let detect = |declared, values: &[&str]| {
+            TimeFormat::detect("value", declared, values.iter().copied())
+        };
+        assert_eq!(
+            detect("DATETIME", &["2024-03-01 12:00:00", "null"]),
+            Some(TimeFormat::Text)
+        );
+        assert_eq!(
+            detect("TIMESTAMP", &["2460371.0", "2460372"]),
+            Some(TimeFormat::JulianDay)
+        );
+        assert_eq!(detect("timestamp", &["0"]), Some(TimeFormat::Seconds));
+        assert_eq!(
+            detect("DATETIME", &["1709294400", "1709294401"]),
+            Some(TimeFormat::Seconds)
+        );
+        assert_eq!(detect("DATE", &["1709294400123"]), Some(TimeFormat::Millis));
+        assert_eq!(
+            detect("TIMESTAMP", &["1709294400123456"]),
+            Some(TimeFormat::Micros)
+        );
+        // Epoch numbers elsewhere need a column named like a time.
+        assert_eq!(detect("INTEGER", &["1709294400"]), None);
+        assert_eq!(detect("", &["1709294400123"]), None);
+        assert_eq!(detect("TEXT", &["2024-03-01"]), None);
+        assert_eq!(
+            detect("DATETIME", &["1709294400", "2024-03-01 12:00"]),
+            None
+        );
+        assert_eq!(detect("DATETIME", &["soon"]), None);
+        assert_eq!(detect("DATETIME", &["null"]), None);
+    }
+
+    #[test]
+    fn detect_epochs() {
+        let detect = |column, declared, values: &[&str]| {
+            TimeFormat::detect(column, declared, values.iter().copied())
+        };
+        assert_eq!(
+            detect("created_at", "INTEGER", &["1709294400", "null"]),
+            Some(TimeFormat::Seconds)
+        );
+        assert_eq!(
+            detect("updatedAt", "BIGINT", &["1709294400123"]),
+            Some(TimeFormat::Millis)
+        );
+        assert_eq!(
+            detect("event_ts", "INT", &["1709294400123456"]),
+            Some(TimeFormat::Micros)
+        );
+        assert_eq!(
+            detect("modified", "REAL", &["1709294400.25"]),
+            Some(TimeFormat::Seconds)
+        );
+        // Not named like a time, not numeric, or out of the plausible range.
+        assert_eq!(detect("amount", "INTEGER", &["1709294400"]), None);
+        assert_eq!(detect("timeout", "INTEGER", &["1709294400"]), None);
+        assert_eq!(detect("created_at", "TEXT", &["1709294400"]), None);
+        assert_eq!(detect("created_at", "", &["1709294400"]), None);
+        assert_eq!(detect("created_at", "INTEGER", &["0"]), None);
+        assert_eq!(detect("created_at", "INTEGER", &["2460371"]), None);
+        assert_eq!(detect("created_at", "INTEGER", &["-1709294400"]), None);
+        assert_eq!(detect("created_at", "INTEGER", &["9999999999"]), None);
+        assert_eq!(detect("created_at", "REAL", &["1709294400123.5"]), None);
+        assert_eq!(
+            detect("created_at", "INTEGER", &["1709294400", "1709294400123"]),
+            None
+        );
+        assert_eq!(detect("created_at", "INTEGER", &["null"]), None);
+    }
+
+    #[test]
+    fn render_utc() {
+        let render = |format: TimeFormat, value| format.render(value, Zone::Utc);
+        assert_eq!(
+            render(TimeFormat::Seconds, "1709294400").unwrap(),
+            "2024-03-01 12:00:00 UTC"
+        );
+        assert_eq!(
+            render(TimeFormat::Seconds, "-1.5").unwrap(),
+            "1969-12-31 23:59:58.500 UTC"
+        );
+        assert_eq!(
+            render(TimeFormat::Millis, "1709294400123").unwrap(),
+            "2024-03-01 12:00:00.123 UTC"
+        );
+        assert_eq!(
+            render(TimeFormat::Micros, "1709294400000042").unwrap(),
+            "2024-03-01 12:00:00.000042 UTC"
+        );
+        assert_eq!(
+            render(TimeFormat::JulianDay, "2460371.0").unwrap(),
+            "2024-03-01 12:00:00 UTC"
+        );
+        assert_eq!(
+            render(TimeFormat::Text, "2024-02-29T23:30:00.5-01:00").unwrap(),
+            "2024-03-01 00:30:00.5 UTC"
+        );
+        assert_eq!(
+            render(TimeFormat::Text, "2000-01-01 00:00").unwrap(),
+            "2000-01-01 00:00:00 UTC"
+        );
+        assert_eq!(render(TimeFormat::Text, "2024-03-01"), None);
+        assert_eq!(render(TimeFormat::Text, "2024-13-01 00:00"), None);
+        assert_eq!(render(TimeFormat::Seconds, "soon"), None);
+        assert_eq!(render(TimeFormat::Raw, "1709294400"), None);
+        assert_eq!(render(TimeFormat::Micros, "null"), None);
+    }
+
+    #[test]
+    fn render_local() {
+        let local = TimeFormat::Seconds
+            .render("1709294400", Zone::Local)
+            .unwrap();
+        assert_eq!(local.len(), "2024-03-01 12:00:00".len());
+        assert!(local.starts_with("2024-03-0"));
+    }
+
+    #[test]
+    fn parse_formats() {
+        for format in ["raw", "text", "julian", "s", "ms", "us"] {
+            assert_eq!(format.parse::<TimeFormat>().unwrap().to_string(), format);
+        }
+        assert!("days".parse::<TimeFormat>().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+/// Single line text input used for SQL statements.
+///
+/// The cursor is a character index into the text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Editor {
+    text: String,
+    cursor: usize,
+    /// Position in the query history while recalling with up/down.
+    history_index: Option<usize>,
+}
+
+impl Editor {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            cursor: text.chars().count(),
+            history_index: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Text left of the cursor.
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.byte_index(self.cursor)]
+    }
+
+    /// Replaces the `len` characters left of the cursor with `text`.
+    pub fn complete(&mut self, len: usize, text: &str) {
+        let start = self.byte_index(self.cursor.saturating_sub(len));
+        let end = self.byte_index(self.cursor);
+        self.text.replace_range(start..end, text);
+        self.cursor = self.cursor.saturating_sub(len) + text.chars().count();
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let i = self.byte_index(self.cursor);
+        self.text.insert(i, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let i = self.byte_index(self.cursor);
+            self.text.remove(i);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.chars().count() {
+            let i = self.byte_index(self.cursor);
+            self.text.remove(i);
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.chars().count());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.chars().count();
+    }
+
+    /// Replaces the text with the previous (older) entry of `history`, which
+    /// is ordered from oldest to newest.
+    pub fn recall_previous(&mut self, history: &[String]) {
+        let index = match self.history_index {
+            Some(0) => return,
+            Some(i) => i - 1,
+            None if history.is_empty() => return,
+            None => history.len() - 1,
+        };
+        self.recall(history, Some(index));
+    }
+
+    /// Replaces the text with the next (newer) entry of `history`, clearing
+    /// it when moving past the newest one.
+    pub fn recall_next(&mut self, history: &[String]) {
+        match self.history_index {
+            Some(i) if i + 1 < history.len() => self.recall(history, Some(i + 1)),
+            Some(_) => self.recall(history, None),
+            None => {}
+        }
+    }
+
+    fn recall(&mut self, history: &[String], index: Option<usize>) {
+        let text = index
+            .and_then(|i| history.get(i))
+            .map_or("", String::as_str);
+        *self = Self {
+            history_index: index,
+            ..Self::new(text)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_text() {
+        let mut editor = Editor::new("SELEC");
+        editor.insert('T');
+        assert_eq!(editor.text(), "SELECT");
+        editor.home();
+        editor.delete();
+        editor.insert('s');
+        assert_eq!(editor.text(), "sELECT");
+        editor.right();
+        editor.backspace();
+        assert_eq!(editor.text(), "sLECT");
+        assert_eq!(editor.cursor(), 1);
+        editor.end();
+        editor.left();
+        editor.insert('é');
+        editor.insert('ő');
+        assert_eq!(editor.text(), "sLECéőT");
+        editor.backspace();
+        assert_eq!(editor.text(), "sLECéT");
+    }
+
+    #[test]
+    fn complete_word() {
+        let mut editor = Editor::new("SELECT * FROM us WHERE");
+        for _ in 0..6 {
+            editor.left();
+        }
+        assert_eq!(editor.before_cursor(), "SELECT * FROM us");
+        editor.complete(2, "users");
+        assert_eq!(editor.text(), "SELECT * FROM users WHERE");
+        assert_eq!(editor.before_cursor(), "SELECT * FROM users");
+    }
+
+    #[test]
+    fn recall_history() {
+        let history = vec!["SELECT 1".to_string(), "SELECT 2".to_string()];
+        let mut editor = Editor::default();
+        editor.recall_next(&history);
+        assert_eq!(editor.text(), "");
+        editor.recall_previous(&history);
+        assert_eq!(editor.text(), "SELECT 2");
+        editor.recall_previous(&history);
+        editor.recall_previous(&history);
+        assert_eq!(editor.text(), "SELECT 1");
+        assert_eq!(editor.cursor(), 8);
+        editor.recall_next(&history);
+        assert_eq!(editor.text(), "SELECT 2");
+        editor.recall_next(&history);
+        assert_eq!(editor.text(), "");
+    }
+}
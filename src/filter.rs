@@ -0,0 +1,555 @@
+//! Conditions on the columns of a table composed in the filter panel and
+//! the parameterized `WHERE` clause they translate to.
+
+use std::fmt;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::database::{Ident, WhereClause};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Like,
+    In,
+    IsNull,
+    Between,
+}
+
+impl Operator {
+    pub const ALL: [Operator; 8] = [
+        Operator::Eq,
+        Operator::Ne,
+        Operator::Lt,
+        Operator::Gt,
+        Operator::Like,
+        Operator::In,
+        Operator::IsNull,
+        Operator::Between,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Eq => "=",
+            Operator::Ne => "!=",
+            Operator::Lt => "<",
+            Operator::Gt => ">",
+            Operator::Like => "LIKE",
+            Operator::In => "IN",
+            Operator::IsNull => "IS NULL",
+            Operator::Between => "BETWEEN",
+        }
+    }
+
+    /// Whether the operator compares with a value.
+    pub fn has_value(&self) -> bool {
+        *self != Operator::IsNull
+    }
+}
+
+/// How a condition combines with the ones before it. `AND` binds tighter
+/// than `OR`, as in SQL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Join {
+    #[default]
+    And,
+    Or,
+}
+
+impl Join {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Join::And => "AND",
+            Join::Or => "OR",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    /// Ignored for the first condition.
+    #[serde(default)]
+    pub join: Join,
+    pub column: String,
+    /// JSON path compared within the column's document, extracted with
+    /// `json_extract`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub operator: Operator,
+    /// Compared value: comma separated values for `IN`, the lower and
+    /// upper bound for `BETWEEN`. Numbers are compared as numbers unless
+    /// quoted.
+    #[serde(default)]
+    pub value: String,
+}
+
+impl Condition {
+    /// Condition testing `column` for equality, with no value yet.
+    pub fn new(column: &str) -> Self {
+        Self {
+            join: Join::And,
+            column: column.to_string(),
+            path: None,
+            operator: Operator::Eq,
+            value: String::new(),
+        }
+    }
+
+    /// Condition comparing the value at the JSON `path` of `column` with
+    /// `value`.
+    pub fn json(column: &str, path: &str, operator: Operator, value: &str) -> Self {
+        Self {
+            path: Some(path.to_string()),
+            operator,
+            value: value.to_string(),
+            ..Self::new(column)
+        }
+    }
+
+    /// Column, or the value at the path in it, as shown in the chip.
+    fn subject(&self) -> String {
+        match &self.path {
+            Some(path) => format!("{} ->> '{path}'", self.column),
+            None => self.column.clone(),
+        }
+    }
+
+    /// SQL of the condition with a placeholder per value.
+    fn to_sql(&self, params: &mut Vec<Value>) -> Result<String> {
+        let column = match &self.path {
+            Some(path) => {
+                params.push(Value::from(path.as_str()));
+                format!("json_extract({}, ?)", Ident::new(&self.column))
+            }
+            None => Ident::new(&self.column).to_string(),
+        };
+        let operator = self.operator.symbol();
+        let values = self.values();
+        match (self.operator, values.as_slice()) {
+            (Operator::IsNull, _) => Ok(format!("{column} IS NULL")),
+            (Operator::In, []) => bail!("IN on {} needs at least one value", self.column),
+            (Operator::In, values) => {
+                params.extend(values.iter().map(|v| parse(v)));
+                let placeholders = vec!["?"; values.len()].join(", ");
+                Ok(format!("{column} IN ({placeholders})"))
+            }
+            (Operator::Between, [low, high]) => {
+                params.extend([parse(low), parse(high)]);
+                Ok(format!("{column} BETWEEN ? AND ?"))
+            }
+            (Operator::Between, _) => bail!(
+                "BETWEEN on {} needs two values separated by a comma",
+                self.column
+            ),
+            (_, [value]) if !value.is_empty() => {
+                params.push(parse(value));
+                Ok(format!("{column} {operator} ?"))
+            }
+            _ => bail!("{} {operator} needs a value", self.column),
+        }
+    }
+
+    fn values(&self) -> Vec<&str> {
+        match self.operator {
+            Operator::In | Operator::Between => self
+                .value
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .collect(),
+            _ => vec![self.value.trim()],
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = self.operator.symbol();
+        let subject = self.subject();
+        match self.operator {
+            Operator::IsNull => write!(f, "{subject} {operator}"),
+            Operator::In => write!(f, "{subject} {operator} ({})", self.value.trim()),
+            Operator::Between => match self.values().as_slice() {
+                [low, high] => write!(f, "{subject} {operator} {low} AND {high}"),
+                _ => write!(f, "{subject} {operator} {}", self.value.trim()),
+            },
+            _ => write!(f, "{subject} {operator} {}", self.value.trim()),
+        }
+    }
+}
+
+/// Conditions on the rows of a table shown in table view.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Filter {
+    pub conditions: Vec<Condition>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// The `WHERE` clause matching the conditions, `None` without any.
+    pub fn to_where(&self) -> Result<Option<WhereClause>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let mut params = Vec::new();
+        let mut sql = String::new();
+        for (i, condition) in self.conditions.iter().enumerate() {
+            if i > 0 {
+                sql.push_str(&format!(" {} ", condition.join.keyword()));
+            }
+            sql.push_str(&condition.to_sql(&mut params)?);
+        }
+        Ok(Some(WhereClause { sql, params }))
+    }
+}
+
+/// Value bound for `text`: an integer or real number, or text. Quotes
+/// force text.
+fn parse(text: &str) -> Value {
+    for quote in ['\'', '"'] {
+        if let Some(inner) = text.strip_prefix(quote).and_then(|t| t.strip_suffix(quote)) {
+            return Value::from(inner);
+        }
+    }
+    if let Ok(i) = text.parse::<i64>() {
+        return Value::from(i);
+    }
+    match text.parse::<f64>() {
+        Ok(f) if f.is_finite() => Value::from(f),
+        _ => Value::from(text),
+    }
+}
+
+/// Part of a condition focused in the filter panel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Join,
+    Column,
+    Operator,
+    Value,
+}
+
+/// The filter panel of a table: the conditions being edited and the
+/// focused one, applied with [`FilterForm::filter`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterForm {
+    table: String,
+    columns: Vec<String>,
+    conditions: Vec<Condition>,
+    selected: usize,
+    field: Field,
+}
+
+impl FilterForm {
+    /// Edits `filter` of `table`, starting with one condition when empty.
+    pub fn new(table: &str, columns: &[String], filter: &Filter) -> Self {
+        let mut conditions = filter.conditions.clone();
+        if conditions.is_empty() {
+            conditions.extend(columns.first().map(|c| Condition::new(c)));
+        }
+        Self {
+            table: table.to_string(),
+            columns: columns.to_vec(),
+            conditions,
+            selected: 0,
+            field: Field::Column,
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    /// Focused condition and field.
+    pub fn focus(&self) -> (usize, Field) {
+        (self.selected, self.field)
+    }
+
+    pub fn filter(&self) -> Filter {
+        Filter {
+            conditions: self.conditions.clone(),
+        }
+    }
+
+    /// Fields of the condition at `index` that can be focused.
+    fn fields(&self, index: usize) -> Vec<Field> {
+        let mut fields = vec![Field::Join, Field::Column, Field::Operator, Field::Value];
+        if index == 0 {
+            fields.remove(0);
+        }
+        if self
+            .conditions
+            .get(index)
+            .is_some_and(|c| !c.operator.has_value())
+        {
+            fields.pop();
+        }
+        fields
+    }
+
+    /// Moves the focus to the next field, on to the next condition after
+    /// the last one.
+    pub fn next(&mut self) {
+        let fields = self.fields(self.selected);
+        match fields.iter().position(|f| *f == self.field) {
+            Some(i) if i + 1 < fields.len() => self.field = fields[i + 1],
+            _ => {
+                self.selected = (self.selected + 1) % self.conditions.len().max(1);
+                self.field = self.fields(self.selected)[0];
+            }
+        }
+    }
+
+    pub fn previous(&mut self) {
+        let fields = self.fields(self.selected);
+        match fields.iter().position(|f| *f == self.field) {
+            Some(i) if i > 0 => self.field = fields[i - 1],
+            _ => {
+                self.selected = self
+                    .selected
+                    .checked_sub(1)
+                    .unwrap_or(self.conditions.len().saturating_sub(1));
+                self.field = *self.fields(self.selected).last().unwrap_or(&Field::Column);
+            }
+        }
+    }
+
+    /// Moves to the condition `step` conditions away, keeping the field if
+    /// it has one.
+    pub fn move_condition(&mut self, step: isize) {
+        if self.conditions.is_empty() {
+            return;
+        }
+        let len = self.conditions.len() as isize;
+        self.selected = (self.selected as isize + step).rem_euclid(len) as usize;
+        self.focus_field();
+    }
+
+    /// Keeps the focus on a field the selected condition has.
+    fn focus_field(&mut self) {
+        let fields = self.fields(self.selected);
+        if !fields.contains(&self.field) {
+            self.field = Field::Column;
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        if self.field == Field::Value {
+            if let Some(condition) = self.conditions.get_mut(self.selected) {
+                condition.value.push(c);
+            }
+        }
+    }
+
+    pub fn pop(&mut self) {
+        if self.field == Field::Value {
+            if let Some(condition) = self.conditions.get_mut(self.selected) {
+                condition.value.pop();
+            }
+        }
+    }
+
+    /// Moves the focused join, column or operator by `step` options,
+    /// wrapping around.
+    pub fn cycle(&mut self, step: isize) {
+        let Some(condition) = self.conditions.get_mut(self.selected) else {
+            return;
+        };
+        let wrap = |i: usize, len: usize| (i as isize + step).rem_euclid(len as isize) as usize;
+        match self.field {
+            Field::Join => {
+                condition.join = match condition.join {
+                    Join::And => Join::Or,
+                    Join::Or => Join::And,
+                }
+            }
+            Field::Column if !self.columns.is_empty() => {
+                let i = self.columns.iter().position(|c| *c == condition.column);
+                let i = wrap(i.unwrap_or(0), self.columns.len());
+                condition.column = self.columns[i].clone();
+                condition.path = None;
+            }
+            Field::Operator => {
+                let i = Operator::ALL.iter().position(|o| *o == condition.operator);
+                condition.operator = Operator::ALL[wrap(i.unwrap_or(0), Operator::ALL.len())];
+            }
+            Field::Column | Field::Value => {}
+        }
+    }
+
+    /// Adds a condition on the same column after the selected one.
+    pub fn add_condition(&mut self) {
+        let column = self
+            .conditions
+            .get(self.selected)
+            .map(|c| c.column.clone())
+            .or_else(|| self.columns.first().cloned());
+        let Some(column) = column else {
+            return;
+        };
+        let index = (self.selected + 1).min(self.conditions.len());
+        self.conditions.insert(index, Condition::new(&column));
+        self.selected = index;
+        self.field = Field::Column;
+    }
+
+    /// Appends `condition` and focuses its value.
+    pub fn push_condition(&mut self, condition: Condition) {
+        self.conditions.push(condition);
+        self.selected = self.conditions.len() - 1;
+        self.field = Field::Value;
+        self.focus_field();
+    }
+
+    pub fn remove_condition(&mut self) {
+        if self.selected < self.conditions.len() {
+            self.conditions.remove(self.selected);
+        }
+        self.selected = self.selected.min(self.conditions.len().saturating_sub(1));
+        self.focus_field();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn condition(join: Join, column: &str, operator: Operator, value: &str) -> Condition {
+        Condition {
+            join,
+            column: column.to_string(),
+            path: None,
+            operator,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn where_clause() {
+        assert_eq!(Filter::default().to_where().unwrap(), None);
+
+        let filter = Filter {
+            conditions: vec![
+                condition(Join::And, "age", Operator::Between, "18, 30.5"),
+                condition(Join::And, "city", Operator::In, "Oslo, '42'"),
+                condition(Join::Or, "name", Operator::Like, "a%"),
+                condition(Join::Or, "email", Operator::IsNull, ""),
+                condition(Join::And, "id", Operator::Ne, "7"),
+            ],
+        };
+        let clause = filter.to_where().unwrap().unwrap();
+        assert_eq!(
+            clause.sql,
+            r#""age" BETWEEN ? AND ? AND "city" IN (?, ?) OR "name" LIKE ? OR "email" IS NULL AND "id" != ?"#
+        );
+        assert_eq!(
+            clause.params,
+            [
+                json!(18),
+                json!(30.5),
+                json!("Oslo"),
+                json!("42"),
+                json!("a%"),
+                json!(7)
+            ]
+        );
+
+        let chips: Vec<String> = filter.conditions.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            chips,
+            [
+                "age BETWEEN 18 AND 30.5",
+                "city IN (Oslo, '42')",
+                "name LIKE a%",
+                "email IS NULL",
+                "id != 7"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        let filter = |operator, value| Filter {
+            conditions: vec![condition(Join::And, "a", operator, value)],
+        };
+        assert!(filter(Operator::Eq, " ").to_where().is_err());
+        assert!(filter(Operator::In, ",").to_where().is_err());
+        assert!(filter(Operator::Between, "1").to_where().is_err());
+        assert!(filter(Operator::IsNull, "").to_where().is_ok());
+    }
+
+    #[test]
+    fn json_path() {
+        let filter = Filter {
+            conditions: vec![Condition::json("data", "$.city", Operator::Eq, "'Oslo'")],
+        };
+        let clause = filter.to_where().unwrap().unwrap();
+        assert_eq!(clause.sql, r#"json_extract("data", ?) = ?"#);
+        assert_eq!(clause.params, [json!("$.city"), json!("Oslo")]);
+        assert_eq!(
+            filter.conditions[0].to_string(),
+            "data ->> '$.city' = 'Oslo'"
+        );
+
+        let columns = ["id", "data"].map(String::from);
+        let mut form = FilterForm::new("t", &columns, &filter);
+        form.push_condition(Condition::json("data", "$.tags", Operator::IsNull, ""));
+        assert_eq!(form.focus(), (1, Field::Column));
+        form.cycle(1);
+        assert_eq!(form.conditions()[1].path, None);
+    }
+
+    #[test]
+    fn form() {
+        let columns = ["id", "name"].map(String::from);
+        let mut form = FilterForm::new("t", &columns, &Filter::default());
+        assert_eq!(form.focus(), (0, Field::Column));
+        form.cycle(1);
+        form.next();
+        form.cycle(-1);
+        assert_eq!(form.conditions()[0].operator, Operator::Between);
+        form.next();
+        "1,5".chars().for_each(|c| form.push(c));
+        form.pop();
+        form.push('9');
+
+        form.add_condition();
+        assert_eq!(form.focus(), (1, Field::Column));
+        form.previous();
+        assert_eq!(form.focus(), (1, Field::Join));
+        form.cycle(1);
+        form.next();
+        form.next();
+        form.cycle(-2);
+        assert_eq!(form.conditions()[1].operator, Operator::IsNull);
+        form.next();
+        assert_eq!(form.focus(), (0, Field::Column));
+
+        let clause = form.filter().to_where().unwrap().unwrap();
+        assert_eq!(clause.sql, r#""name" BETWEEN ? AND ? OR "name" IS NULL"#);
+        assert_eq!(clause.params, [json!(1), json!(9)]);
+
+        form.move_condition(1);
+        form.remove_condition();
+        form.remove_condition();
+        assert!(form.filter().is_empty());
+        form.push('x');
+        form.cycle(1);
+    }
+}
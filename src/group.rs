@@ -0,0 +1,282 @@
+//! `GROUP BY` summaries of the rows of a view and pivots of them on a
+//! second column.
+
+use serde_json::Value;
+
+use crate::database::Ident;
+
+/// Most frequent values of the pivot column shown as columns, the others
+/// are counted together.
+const PIVOT_COLUMNS: usize = 12;
+
+/// Rows of a table or statement grouped by one of its columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grouping {
+    /// Table name or parenthesized statement the rows are read from.
+    from: String,
+    column: String,
+    /// Columns of `from`, the candidates for a pivot.
+    columns: Vec<String>,
+    pivot: Option<Pivot>,
+    /// Value of each group as shown and as an SQL literal.
+    keys: Vec<(Value, String)>,
+}
+
+/// Second column whose most frequent values become columns of the summary.
+#[derive(Debug, Clone, PartialEq)]
+struct Pivot {
+    column: String,
+    /// Values as shown and as SQL literals, most frequent first.
+    values: Vec<(Value, String)>,
+    /// Whether there are more values than [`PIVOT_COLUMNS`].
+    other: bool,
+}
+
+impl Grouping {
+    /// Groups the rows of `table`, or of the statement `sql` when it is not
+    /// a table, by `column`.
+    pub fn new(table: Option<&str>, sql: &str, column: &str, columns: &[String]) -> Self {
+        let from = match table {
+            Some(table) => Ident::new(table).to_string(),
+            None => format!("({})", sql.trim().trim_end_matches(';')),
+        };
+        Self {
+            from,
+            column: column.to_string(),
+            columns: columns.to_vec(),
+            pivot: None,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn pivot(&self) -> Option<&str> {
+        self.pivot.as_ref().map(|pivot| pivot.column.as_str())
+    }
+
+    /// Number of rows and their share per value, most frequent first, or
+    /// the number of rows per value of the pivot column in a pivot.
+    pub fn sql(&self) -> String {
+        let column = Ident::new(&self.column);
+        let mut select = vec![column.to_string(), r#"count(*) AS "count""#.to_string()];
+        match &self.pivot {
+            None => select.push(
+                r#"round(100.0 * count(*) / sum(count(*)) OVER (), 2) AS "percent""#.to_string(),
+            ),
+            Some(pivot) => {
+                let pivot_column = Ident::new(&pivot.column);
+                for (value, literal) in &pivot.values {
+                    select.push(format!(
+                        "count(*) FILTER (WHERE {pivot_column} IS {literal}) AS {}",
+                        Ident::new(&label(value))
+                    ));
+                }
+                if pivot.other {
+                    select.push(format!(
+                        r#"count(*) FILTER (WHERE NOT ({})) AS "other""#,
+                        pivot.any()
+                    ));
+                }
+            }
+        }
+        format!(
+            "SELECT {} FROM {} GROUP BY {column} ORDER BY count(*) DESC, {column}",
+            select.join(", "),
+            self.from
+        )
+    }
+
+    /// Values of the grouped column and their SQL literals, read by
+    /// [`Grouping::set_keys`].
+    pub fn keys_sql(&self) -> String {
+        let column = Ident::new(&self.column);
+        format!(
+            "SELECT {column}, quote({column}) FROM {} GROUP BY {column}",
+            self.from
+        )
+    }
+
+    pub fn set_keys(&mut self, rows: Vec<Vec<Value>>) {
+        self.keys = pairs(rows);
+    }
+
+    /// Column to pivot on next: the one after the current pivot, skipping
+    /// the grouped column, or `None` after the last to show the plain
+    /// summary again.
+    pub fn next_pivot(&self) -> Option<&str> {
+        let start = match &self.pivot {
+            Some(pivot) => self.columns.iter().position(|c| *c == pivot.column)? + 1,
+            None => 0,
+        };
+        self.columns[start..]
+            .iter()
+            .find(|c| **c != self.column)
+            .map(String::as_str)
+    }
+
+    /// Most frequent values of `column` and their SQL literals, one more
+    /// than shown to tell whether there are others, read by
+    /// [`Grouping::set_pivot`].
+    pub fn pivot_sql(&self, column: &str) -> String {
+        let column = Ident::new(column);
+        format!(
+            "SELECT {column}, quote({column}) FROM {} GROUP BY {column} \
+             ORDER BY count(*) DESC, {column} LIMIT {}",
+            self.from,
+            PIVOT_COLUMNS + 1
+        )
+    }
+
+    /// Pivots on `column`, or shows the plain summary for `None`.
+    pub fn set_pivot(&mut self, column: Option<&str>, rows: Vec<Vec<Value>>) {
+        self.pivot = column.map(|column| {
+            let mut values = pairs(rows);
+            let other = values.len() > PIVOT_COLUMNS;
+            values.truncate(PIVOT_COLUMNS);
+            Pivot {
+                column: column.to_string(),
+                values,
+                other,
+            }
+        });
+    }
+
+    /// Rows of the group shown as `key`. In a pivot, a `column` of the
+    /// summary that counts a value of the pivot column narrows them to
+    /// that value.
+    pub fn drill_sql(&self, key: &Value, column: usize) -> Option<String> {
+        let (_, literal) = self.keys.iter().find(|(value, _)| value == key)?;
+        let mut condition = format!("{} IS {literal}", Ident::new(&self.column));
+        if let Some(pivot) = &self.pivot {
+            let pivot_column = Ident::new(&pivot.column);
+            match column.checked_sub(2) {
+                Some(i) if i < pivot.values.len() => {
+                    condition.push_str(&format!(" AND {pivot_column} IS {}", pivot.values[i].1));
+                }
+                Some(i) if i == pivot.values.len() && pivot.other => {
+                    condition.push_str(&format!(" AND NOT ({})", pivot.any()));
+                }
+                _ => {}
+            }
+        }
+        Some(format!("SELECT * FROM {} WHERE {condition}", self.from))
+    }
+}
+
+impl Pivot {
+    /// Condition matching any of the shown values.
+    fn any(&self) -> String {
+        let column = Ident::new(&self.column);
+        self.values
+            .iter()
+            .map(|(_, literal)| format!("{column} IS {literal}"))
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    }
+}
+
+/// Shown value and SQL literal from the first two cells of each row.
+fn pairs(rows: Vec<Vec<Value>>) -> Vec<(Value, String)> {
+    rows.into_iter()
+        .filter_map(|row| {
+            let mut cells = row.into_iter();
+            let value = cells.next()?;
+            let literal = label(&cells.next()?);
+            Some((value, literal))
+        })
+        .collect()
+}
+
+fn label(value: &Value) -> String {
+    value
+        .as_str()
+        .map_or_else(|| value.to_string(), String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn grouping() -> Grouping {
+        let columns = ["id", "city", "kind"].map(String::from);
+        let mut grouping = Grouping::new(Some("users"), "", "city", &columns);
+        grouping.set_keys(vec![
+            vec![json!("Oslo"), json!("'Oslo'")],
+            vec![json!("null"), json!("NULL")],
+        ]);
+        grouping
+    }
+
+    #[test]
+    fn group_sql() {
+        let grouping = grouping();
+        assert_eq!(
+            grouping.sql(),
+            r#"SELECT "city", count(*) AS "count", round(100.0 * count(*) / sum(count(*)) OVER (), 2) AS "percent" FROM "users" GROUP BY "city" ORDER BY count(*) DESC, "city""#
+        );
+        assert_eq!(
+            grouping.keys_sql(),
+            r#"SELECT "city", quote("city") FROM "users" GROUP BY "city""#
+        );
+
+        let query = Grouping::new(None, "SELECT * FROM t;\n", "a", &[]);
+        assert!(query.sql().contains("FROM (SELECT * FROM t) GROUP BY"));
+    }
+
+    #[test]
+    fn pivot() {
+        let mut grouping = grouping();
+        assert_eq!(grouping.next_pivot(), Some("id"));
+        grouping.set_pivot(Some("id"), Vec::new());
+        assert_eq!(grouping.next_pivot(), Some("kind"));
+
+        assert!(grouping.pivot_sql("kind").ends_with("LIMIT 13"));
+        let mut values: Vec<Vec<Value>> = (0..13)
+            .map(|i| vec![json!(i.to_string()), json!(i.to_string())])
+            .collect();
+        values[0] = vec![json!("a'b"), json!("'a''b'")];
+        grouping.set_pivot(Some("kind"), values);
+        assert_eq!(grouping.pivot(), Some("kind"));
+        assert_eq!(grouping.next_pivot(), None);
+
+        let sql = grouping.sql();
+        assert!(sql.starts_with(
+            r#"SELECT "city", count(*) AS "count", count(*) FILTER (WHERE "kind" IS 'a''b') AS "a'b", count(*) FILTER (WHERE "kind" IS 1) AS "1","#
+        ));
+        assert!(sql.contains(r#"count(*) FILTER (WHERE NOT ("kind" IS 'a''b' OR "kind" IS 1"#));
+        assert!(!sql.contains(r#"IS 12"#));
+
+        grouping.set_pivot(None, Vec::new());
+        assert_eq!(grouping.pivot(), None);
+    }
+
+    #[test]
+    fn drill_down() {
+        let mut grouping = grouping();
+        assert_eq!(
+            grouping.drill_sql(&json!("Oslo"), 0).unwrap(),
+            r#"SELECT * FROM "users" WHERE "city" IS 'Oslo'"#
+        );
+        assert_eq!(
+            grouping.drill_sql(&json!("null"), 1).unwrap(),
+            r#"SELECT * FROM "users" WHERE "city" IS NULL"#
+        );
+        assert!(grouping.drill_sql(&json!("Bergen"), 0).is_none());
+
+        grouping.set_pivot(
+            Some("kind"),
+            vec![
+                vec![json!("a"), json!("'a'")],
+                vec![json!("b"), json!("'b'")],
+            ],
+        );
+        assert_eq!(
+            grouping.drill_sql(&json!("Oslo"), 3).unwrap(),
+            r#"SELECT * FROM "users" WHERE "city" IS 'Oslo' AND "kind" IS 'b'"#
+        );
+        assert_eq!(
+            grouping.drill_sql(&json!("Oslo"), 4).unwrap(),
+            r#"SELECT * FROM "users" WHERE "city" IS 'Oslo'"#
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::store::Store;
+
+const MAX_ENTRIES: usize = 1000;
+const DIR_NAME: &str = "queries";
+
+/// A named query shown in the main list next to the tables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub name: String,
+    pub sql: String,
+}
+
+/// Executed statements and saved queries of one database file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    #[serde(default)]
+    history: Vec<String>,
+    #[serde(default)]
+    saved: Vec<SavedQuery>,
+}
+
+/// Query history and saved queries, persisted per database file in
+/// the `queries` directory of the data directory.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    store: Store,
+    entry: Entry,
+}
+
+impl History {
+    /// Loads the history of the database at `database` from the data
+    /// directory.
+    pub fn load(database: &str) -> Result<Self> {
+        Self::load_from(Store::open(DIR_NAME, database))
+    }
+
+    fn load_from(store: Store) -> Result<Self> {
+        let entry = store.load()?;
+        Ok(Self { store, entry })
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.entry)
+    }
+
+    /// Executed statements, oldest first.
+    pub fn entries(&self) -> &[String] {
+        &self.entry.history
+    }
+
+    pub fn push(&mut self, sql: &str) -> Result<()> {
+        let sql = sql.trim();
+        if sql.is_empty() || self.entry.history.last().is_some_and(|last| last == sql) {
+            return Ok(());
+        }
+        self.entry.history.push(sql.to_string());
+        let overflow = self.entry.history.len().saturating_sub(MAX_ENTRIES);
+        self.entry.history.drain(..overflow);
+        self.save()
+    }
+
+    pub fn saved_queries(&self) -> &[SavedQuery] {
+        &self.entry.saved
+    }
+
+    /// Saves `sql` under `name`, replacing a saved query of the same name.
+    pub fn save_query(&mut self, name: &str, sql: &str) -> Result<()> {
+        let query = SavedQuery {
+            name: name.to_string(),
+            sql: sql.to_string(),
+        };
+        match self.entry.saved.iter_mut().find(|q| q.name == name) {
+            Some(existing) => *existing = query,
+            None => self.entry.saved.push(query),
+        }
+        self.save()
+    }
+
+    /// Removes the saved query `name`, returning whether it existed.
+    pub fn remove_query(&mut self, name: &str) -> Result<bool> {
+        let len = self.entry.saved.len();
+        self.entry.saved.retain(|q| q.name != name);
+        if self.entry.saved.len() == len {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// History entries matching `pattern`, best match first and newest
+    /// first among equal matches.
+    pub fn search(&self, pattern: &str) -> Vec<&str> {
+        let mut matches: Vec<(usize, usize, &str)> = self
+            .entry
+            .history
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(i, sql)| {
+                // Hide older duplicates.
+                !self.entry.history[i + 1..].contains(sql)
+            })
+            .filter_map(|(i, sql)| fuzzy_score(pattern, sql).map(|score| (score, i, sql.as_str())))
+            .collect();
+        matches.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        matches.into_iter().map(|(_, _, sql)| sql).collect()
+    }
+}
+
+/// Scores `text` against `pattern` when all pattern characters appear in
+/// order, case insensitively. Lower is better: the sum of gaps between
+/// matched characters.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut chars = text.chars().flat_map(char::to_lowercase).enumerate();
+    let mut last: Option<usize> = None;
+    for p in pattern.chars().flat_map(char::to_lowercase) {
+        let (i, _) = chars.find(|(_, c)| *c == p)?;
+        score += last.map_or(0, |last| i - last - 1);
+        last = Some(i);
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::store;
+
+    fn load(dir: &std::path::Path, database: &str) -> Result<History> {
+        History::load_from(Store::at(dir, database))
+    }
+
+    #[test]
+    fn fuzzy_scores() {
+        assert_eq!(fuzzy_score("", "SELECT"), Some(0));
+        assert_eq!(fuzzy_score("sel", "SELECT"), Some(0));
+        assert_eq!(fuzzy_score("st", "SELECT"), Some(4));
+        assert_eq!(fuzzy_score("ts", "SELECT"), None);
+    }
+
+    #[test]
+    fn persist_history() {
+        let dir = store::temp_dir("history", DIR_NAME);
+        let mut history = load(&dir, "a.db").unwrap();
+        history.push("SELECT 1").unwrap();
+        history.push("SELECT 1").unwrap();
+        history.push(" ").unwrap();
+        history.push("SELECT * FROM users").unwrap();
+        history.save_query("users", "SELECT * FROM users").unwrap();
+
+        let mut other = load(&dir, "b.db").unwrap();
+        assert!(other.entries().is_empty());
+        other.push("SELECT 2").unwrap();
+
+        let history = load(&dir, "a.db").unwrap();
+        assert_eq!(history.entries(), ["SELECT 1", "SELECT * FROM users"]);
+        assert_eq!(
+            history.saved_queries(),
+            [SavedQuery {
+                name: "users".into(),
+                sql: "SELECT * FROM users".into()
+            }]
+        );
+        assert_eq!(history.search("sfu"), ["SELECT * FROM users"]);
+        assert_eq!(history.search(""), ["SELECT * FROM users", "SELECT 1"]);
+
+        let mut history = load(&dir, "a.db").unwrap();
+        assert!(history.remove_query("users").unwrap());
+        assert!(!history.remove_query("users").unwrap());
+        assert!(load(&dir, "a.db").unwrap().saved_queries().is_empty());
+        assert_eq!(load(&dir, "b.db").unwrap().entries(), ["SELECT 2"]);
+        let files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 2);
+        assert!(files
+            .iter()
+            .all(|file| file.extension() == Some("json".as_ref())));
+
+        for file in files {
+            fs::write(file, "{").unwrap();
+        }
+        assert!(load(&dir, "a.db").is_err());
+    }
+}
@@ -0,0 +1,477 @@
+//! Reading CSV, JSON and NDJSON files into typed rows for
+//! [`Database::create_table`] and [`Database::insert`].
+
+use std::{collections::HashSet, path::Path};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::database::{Database, ResultSet};
+
+/// Delimiters tried when sniffing a CSV file.
+const DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+/// Records looked at when sniffing the delimiter.
+const SNIFF_RECORDS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ImportFormat {
+    /// Delimited values, optionally with a header row
+    Csv,
+    /// An array of objects or arrays
+    Json,
+    /// One object or array per line
+    Ndjson,
+}
+
+impl ImportFormat {
+    /// Guesses the format from the extension of `path`, falling back to the
+    /// first character of `text`.
+    pub fn detect(path: &str, text: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => ImportFormat::Json,
+            Some("ndjson" | "jsonl") => ImportFormat::Ndjson,
+            Some("csv" | "tsv" | "txt") => ImportFormat::Csv,
+            _ => match text.trim_start().chars().next() {
+                Some('[') => ImportFormat::Json,
+                Some('{') => ImportFormat::Ndjson,
+                _ => ImportFormat::Csv,
+            },
+        }
+    }
+}
+
+/// Storage class inferred for an imported column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Text,
+}
+
+impl ColumnType {
+    pub fn declared(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+
+    /// Narrowest type holding every value of a column. Strings count as
+    /// numbers when `parse` is set and they parse as one.
+    fn infer<'a>(values: impl Iterator<Item = &'a Value>, parse: bool) -> Self {
+        let mut kind = ColumnType::Integer;
+        for value in values {
+            let this = match value {
+                Value::Null => continue,
+                Value::Bool(_) => ColumnType::Integer,
+                Value::Number(n) if n.is_i64() || n.is_u64() => ColumnType::Integer,
+                Value::Number(_) => ColumnType::Real,
+                Value::String(s) if parse && s.trim().parse::<i64>().is_ok() => ColumnType::Integer,
+                Value::String(s) if parse && s.trim().parse::<f64>().is_ok() => ColumnType::Real,
+                _ => return ColumnType::Text,
+            };
+            if this == ColumnType::Real {
+                kind = ColumnType::Real;
+            }
+        }
+        kind
+    }
+
+    /// Converts `value` to this type, e.g. a CSV field to a number.
+    fn convert(&self, value: Value) -> Value {
+        match (self, value) {
+            (ColumnType::Integer, Value::String(s)) => s
+                .trim()
+                .parse::<i64>()
+                .map_or(Value::String(s), Value::from),
+            (ColumnType::Real, Value::String(s)) => s
+                .trim()
+                .parse::<f64>()
+                .map_or(Value::String(s), Value::from),
+            (ColumnType::Text, Value::Number(n)) => Value::String(n.to_string()),
+            (_, value) => value,
+        }
+    }
+}
+
+/// Options of an import, `None` meaning detect.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub format: Option<ImportFormat>,
+    pub delimiter: Option<char>,
+    pub header: Option<bool>,
+}
+
+/// Rows read from a file, with the inferred type of every column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub columns: Vec<String>,
+    pub types: Vec<ColumnType>,
+    /// Typed values: null, numbers and strings.
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Import {
+    pub fn read(path: &str, options: &Options) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+        match options
+            .format
+            .unwrap_or_else(|| ImportFormat::detect(path, text))
+        {
+            ImportFormat::Csv => Self::csv(text, options.delimiter, options.header),
+            ImportFormat::Json => match serde_json::from_str(text)? {
+                Value::Array(records) => Self::json(records),
+                _ => bail!("expected an array of objects or arrays"),
+            },
+            ImportFormat::Ndjson => {
+                let records = text
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(serde_json::from_str)
+                    .collect::<serde_json::Result<_>>()?;
+                Self::json(records)
+            }
+        }
+    }
+
+    pub fn csv(text: &str, delimiter: Option<char>, header: Option<bool>) -> Result<Self> {
+        let delimiter = delimiter.unwrap_or_else(|| sniff_delimiter(text));
+        let mut records = parse_csv(text, delimiter);
+        let header = header.unwrap_or_else(|| has_header(&records));
+        let columns = match header {
+            true if !records.is_empty() => records.remove(0),
+            _ => Vec::new(),
+        };
+        let width = records
+            .iter()
+            .map(Vec::len)
+            .chain([columns.len()])
+            .max()
+            .unwrap_or(0);
+        let rows = records
+            .into_iter()
+            .map(|record| {
+                let mut row: Vec<Value> = record
+                    .into_iter()
+                    .map(|field| match field.is_empty() {
+                        true => Value::Null,
+                        false => Value::String(field),
+                    })
+                    .collect();
+                row.resize(width, Value::Null);
+                row
+            })
+            .collect();
+        Ok(Self::typed(column_names(columns, width), rows, true))
+    }
+
+    /// Rows from JSON objects, keyed by name, or arrays, by position.
+    pub fn json(records: Vec<Value>) -> Result<Self> {
+        let mut columns: Vec<String> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut width = 0;
+        for record in &records {
+            match record {
+                Value::Object(object) => {
+                    for key in object.keys() {
+                        if seen.insert(key.clone()) {
+                            columns.push(key.clone());
+                        }
+                    }
+                }
+                Value::Array(values) => width = width.max(values.len()),
+                _ => bail!("expected objects or arrays, found {record}"),
+            }
+        }
+        if !columns.is_empty() && width > 0 {
+            bail!("cannot mix objects and arrays");
+        }
+        let width = width.max(columns.len());
+        let rows = records
+            .into_iter()
+            .map(|record| {
+                let mut row = match record {
+                    Value::Object(mut object) => columns
+                        .iter()
+                        .map(|column| json_value(object.remove(column)))
+                        .collect(),
+                    Value::Array(values) => values.into_iter().map(Some).map(json_value).collect(),
+                    _ => Vec::new(),
+                };
+                row.resize(width, Value::Null);
+                row
+            })
+            .collect();
+        Ok(Self::typed(column_names(columns, width), rows, false))
+    }
+
+    fn typed(columns: Vec<String>, mut rows: Vec<Vec<Value>>, parse: bool) -> Self {
+        let types: Vec<ColumnType> = (0..columns.len())
+            .map(|i| ColumnType::infer(rows.iter().map(|row| &row[i]), parse))
+            .collect();
+        for row in &mut rows {
+            for (value, kind) in row.iter_mut().zip(&types) {
+                *value = kind.convert(value.take());
+            }
+        }
+        Self {
+            columns,
+            types,
+            rows,
+        }
+    }
+
+    /// The first `limit` rows as cells of the table grid, the header
+    /// showing the inferred types.
+    pub fn preview(&self, limit: usize) -> ResultSet {
+        ResultSet {
+            columns: self
+                .columns
+                .iter()
+                .zip(&self.types)
+                .map(|(column, kind)| format!("{column} {}", kind.declared()))
+                .collect(),
+            rows: self
+                .rows
+                .iter()
+                .take(limit)
+                .map(|row| {
+                    row.iter()
+                        .map(|value| match value {
+                            Value::String(s) => Value::String(s.clone()),
+                            value => Value::String(value.to_string()),
+                        })
+                        .collect()
+                })
+                .collect(),
+            stats: None,
+        }
+    }
+
+    /// Writes the rows to `table`, creating it when it does not exist and
+    /// appending otherwise. Returns the number of rows written and whether
+    /// the table was created.
+    pub async fn write(&self, db: &impl Database, table: &str) -> Result<(u64, bool)> {
+        if !db.tables().await?.iter().any(|name| name == table) {
+            let columns: Vec<(String, &str)> = self
+                .columns
+                .iter()
+                .zip(&self.types)
+                .map(|(column, kind)| (column.clone(), kind.declared()))
+                .collect();
+            let rows = db.create_table(table, &columns, &self.rows).await?;
+            return Ok((rows, true));
+        }
+
+        let existing = db.columns(table).await?;
+        // Generated names of headerless files map to columns by position.
+        let positional = self
+            .columns
+            .iter()
+            .enumerate()
+            .all(|(i, column)| *column == format!("column{}", i + 1));
+        let columns = if positional {
+            if self.columns.len() > existing.len() {
+                bail!(
+                    "{table} has {} columns, the file {}",
+                    existing.len(),
+                    self.columns.len()
+                );
+            }
+            existing[..self.columns.len()].to_vec()
+        } else {
+            if let Some(missing) = self.columns.iter().find(|c| !existing.contains(c)) {
+                bail!("{table} has no column {missing}");
+            }
+            self.columns.clone()
+        };
+        Ok((db.insert(table, &columns, &self.rows).await?, false))
+    }
+}
+
+/// Table name for an import of `path`: the file name without extension.
+pub fn table_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("import")
+        .to_string()
+}
+
+fn json_value(value: Option<Value>) -> Value {
+    match value {
+        None | Some(Value::Null) => Value::Null,
+        Some(Value::Bool(b)) => Value::from(i64::from(b)),
+        Some(value @ (Value::Number(_) | Value::String(_))) => value,
+        // Nested values are stored as JSON text, ready for json_extract.
+        Some(value) => Value::String(value.to_string()),
+    }
+}
+
+/// Header names made unique, with `columnN` for missing ones.
+fn column_names(header: Vec<String>, width: usize) -> Vec<String> {
+    let mut seen = HashSet::new();
+    (0..width)
+        .map(|i| {
+            let name = header
+                .get(i)
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("column{}", i + 1));
+            let mut unique = name.clone();
+            let mut n = 1;
+            while !seen.insert(unique.clone()) {
+                n += 1;
+                unique = format!("{name}_{n}");
+            }
+            unique
+        })
+        .collect()
+}
+
+/// The delimiter splitting the first records into the most fields, the same
+/// number on every record.
+fn sniff_delimiter(text: &str) -> char {
+    let sample = &text[..text.floor_char_boundary(64 * 1024)];
+    let score = |delimiter: char| {
+        let records = parse_csv(sample, delimiter);
+        let mut counts = records.iter().take(SNIFF_RECORDS).map(Vec::len);
+        let first = counts.next().unwrap_or(0);
+        // The sample may end inside the last record.
+        let consistent = counts
+            .take(records.len().saturating_sub(2))
+            .all(|n| n == first);
+        if consistent {
+            first
+        } else {
+            0
+        }
+    };
+    // Ties go to the delimiter listed first.
+    DELIMITERS
+        .into_iter()
+        .rev()
+        .map(|delimiter| (score(delimiter), delimiter))
+        .filter(|(score, _)| *score > 1)
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, delimiter)| delimiter)
+        .unwrap_or(',')
+}
+
+/// Whether the first record looks like column names: distinct non-empty
+/// fields, none of them a number.
+fn has_header(records: &[Vec<String>]) -> bool {
+    let Some(first) = records.first() else {
+        return false;
+    };
+    let mut seen = HashSet::new();
+    first.iter().all(|field| {
+        let field = field.trim();
+        !field.is_empty() && field.parse::<f64>().is_err() && seen.insert(field)
+    })
+}
+
+/// Splits CSV text into records of fields, with RFC 4180 quoting. Blank
+/// lines are skipped.
+fn parse_csv(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            c if quoted => field.push(c),
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                if record != [""] {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_quoted_csv() {
+        let records = parse_csv("a,b\r\n\"x, \"\"y\"\"\",\"multi\nline\"\n\n1,\n", ',');
+        assert_eq!(
+            records,
+            [
+                vec!["a", "b"],
+                vec!["x, \"y\"", "multi\nline"],
+                vec!["1", ""]
+            ]
+        );
+        assert_eq!(parse_csv("a;b", ';'), [vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn sniff_csv() {
+        assert_eq!(sniff_delimiter("a;b;c\n1;2,5;3\n"), ';');
+        assert_eq!(sniff_delimiter("a\tb\n1\t2\n"), '\t');
+        assert_eq!(sniff_delimiter("a,b\n1,2\n"), ',');
+        assert_eq!(sniff_delimiter("single\n"), ',');
+
+        let import = Import::csv("id;name;score\n1;ann;2.5\n2;bob;\n", None, None).unwrap();
+        assert_eq!(import.columns, ["id", "name", "score"]);
+        assert_eq!(
+            import.types,
+            [ColumnType::Integer, ColumnType::Text, ColumnType::Real]
+        );
+        assert_eq!(
+            import.rows[1],
+            [Value::from(2), Value::from("bob"), Value::Null]
+        );
+
+        let import = Import::csv("1,ann\n2,bob\n", None, None).unwrap();
+        assert_eq!(import.columns, ["column1", "column2"]);
+        assert_eq!(import.rows.len(), 2);
+    }
+
+    #[test]
+    fn read_json() {
+        let records = serde_json::from_str(
+            r#"[{"id": 1, "tags": ["a"]}, {"id": 2.5, "ok": true, "id2": null}]"#,
+        )
+        .unwrap();
+        let import = Import::json(records).unwrap();
+        assert_eq!(import.columns, ["id", "tags", "ok", "id2"]);
+        assert_eq!(import.types[0], ColumnType::Real);
+        assert_eq!(import.rows[0][1], Value::from(r#"["a"]"#));
+        assert_eq!(import.rows[1][2], Value::from(1));
+        assert_eq!(import.preview(1).columns[0], "id REAL");
+
+        let records = vec![serde_json::json!([1, "x"]), serde_json::json!({"a": 1})];
+        assert!(Import::json(records).is_err());
+    }
+}
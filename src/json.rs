@@ -0,0 +1,265 @@
+//! The JSON viewer: a collapsible tree of the document in a cell, listed by
+//! SQLite's `json_tree`, and the SQL reading the selected element.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    database::{Ident, JsonNode},
+    filter::{Condition, Operator},
+};
+
+/// JSON document of a cell browsed as a tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonView {
+    /// Table the cell was read from, `None` for the rows of a statement.
+    table: Option<String>,
+    column: String,
+    nodes: Vec<JsonNode>,
+    /// Objects and arrays whose elements are hidden.
+    collapsed: HashSet<i64>,
+    /// Index of the selected node among the visible ones.
+    selected: usize,
+}
+
+impl JsonView {
+    pub fn new(table: Option<&str>, column: &str, nodes: Vec<JsonNode>) -> Self {
+        Self {
+            table: table.map(String::from),
+            column: column.to_string(),
+            nodes,
+            collapsed: HashSet::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn table(&self) -> Option<&str> {
+        self.table.as_deref()
+    }
+
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn is_collapsed(&self, node: &JsonNode) -> bool {
+        self.collapsed.contains(&node.id)
+    }
+
+    /// Nodes outside collapsed containers with their depth, in document
+    /// order.
+    pub fn visible(&self) -> Vec<(usize, &JsonNode)> {
+        let mut depths: HashMap<i64, usize> = HashMap::new();
+        let mut hidden: HashSet<i64> = HashSet::new();
+        let mut visible = Vec::new();
+        for node in &self.nodes {
+            let depth = node
+                .parent
+                .and_then(|parent| depths.get(&parent))
+                .map_or(0, |depth| depth + 1);
+            depths.insert(node.id, depth);
+            let parent_hidden = node
+                .parent
+                .is_some_and(|parent| hidden.contains(&parent) || self.collapsed.contains(&parent));
+            if parent_hidden {
+                hidden.insert(node.id);
+            } else {
+                visible.push((depth, node));
+            }
+        }
+        visible
+    }
+
+    pub fn selected_node(&self) -> Option<&JsonNode> {
+        self.visible().get(self.selected).map(|(_, node)| *node)
+    }
+
+    pub fn next(&mut self) {
+        let len = self.visible().len();
+        self.selected = (self.selected + 1).min(len.saturating_sub(1));
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Collapses or expands the selected object or array.
+    pub fn toggle(&mut self) {
+        if let Some(id) = self.selected_container() {
+            if !self.collapsed.remove(&id) {
+                self.collapsed.insert(id);
+            }
+        }
+    }
+
+    pub fn expand(&mut self) {
+        if let Some(id) = self.selected_container() {
+            self.collapsed.remove(&id);
+        }
+    }
+
+    /// Collapses the selected container, or selects the parent of an
+    /// element that is not an expanded container.
+    pub fn collapse(&mut self) {
+        match self.selected_container() {
+            Some(id) if !self.collapsed.contains(&id) => {
+                self.collapsed.insert(id);
+            }
+            _ => {
+                let Some(parent) = self.selected_node().and_then(|node| node.parent) else {
+                    return;
+                };
+                if let Some(i) = self.visible().iter().position(|(_, n)| n.id == parent) {
+                    self.selected = i;
+                }
+            }
+        }
+    }
+
+    fn selected_container(&self) -> Option<i64> {
+        self.selected_node()
+            .filter(|node| node.is_container())
+            .map(|node| node.id)
+    }
+
+    /// `json_extract` of the selected element from the column.
+    pub fn expression(&self) -> Option<String> {
+        let node = self.selected_node()?;
+        Some(format!(
+            "json_extract({}, '{}')",
+            Ident::new(&self.column),
+            node.path.replace('\'', "''")
+        ))
+    }
+
+    /// Filter condition matching the selected element's value. Booleans
+    /// compare as the 1 and 0 `json_extract` returns, text is quoted so
+    /// that it is never compared as a number.
+    pub fn condition(&self) -> Option<Condition> {
+        let node = self.selected_node()?;
+        let value = node.value.as_deref().unwrap_or_default();
+        let (operator, value) = match node.kind.as_str() {
+            "null" => (Operator::IsNull, String::new()),
+            "true" => (Operator::Eq, "1".to_string()),
+            "false" => (Operator::Eq, "0".to_string()),
+            "integer" | "real" => (Operator::Eq, value.to_string()),
+            _ => (Operator::Eq, format!("'{value}'")),
+        };
+        Some(Condition::json(&self.column, &node.path, operator, &value))
+    }
+
+    /// Name proposed for a virtual column of the selected element, e.g.
+    /// `data_tags_0` for `$.tags[0]` of `data`.
+    pub fn column_name(&self) -> Option<String> {
+        let node = self.selected_node()?;
+        let mut name = self.column.clone();
+        for part in node.path.split(|c: char| !c.is_alphanumeric() && c != '_') {
+            if !part.is_empty() {
+                name.push('_');
+                name.push_str(part);
+            }
+        }
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(
+        id: i64,
+        parent: Option<i64>,
+        key: &str,
+        kind: &str,
+        value: &str,
+        path: &str,
+    ) -> JsonNode {
+        JsonNode {
+            id,
+            parent,
+            key: (!key.is_empty()).then(|| key.to_string()),
+            kind: kind.to_string(),
+            value: (kind != "null").then(|| value.to_string()),
+            path: path.to_string(),
+        }
+    }
+
+    /// `{"a": {"b": "x'y", "c": null}, "tags": [true, 2]}`
+    fn view() -> JsonView {
+        let nodes = vec![
+            node(0, None, "", "object", "{...}", "$"),
+            node(1, Some(0), "a", "object", "{...}", "$.a"),
+            node(2, Some(1), "b", "text", "x'y", "$.a.b"),
+            node(3, Some(1), "c", "null", "", "$.a.c"),
+            node(4, Some(0), "tags", "array", "[true,2]", "$.tags"),
+            node(5, Some(4), "0", "true", "1", "$.tags[0]"),
+            node(6, Some(4), "1", "integer", "2", "$.tags[1]"),
+        ];
+        JsonView::new(Some("events"), "data", nodes)
+    }
+
+    fn paths(view: &JsonView) -> Vec<(usize, &str)> {
+        view.visible()
+            .into_iter()
+            .map(|(depth, node)| (depth, node.path.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn collapse_and_expand() {
+        let mut view = view();
+        assert_eq!(paths(&view).len(), 7);
+        assert_eq!(paths(&view)[2], (2, "$.a.b"));
+
+        view.next();
+        view.toggle();
+        assert_eq!(
+            paths(&view),
+            [
+                (0, "$"),
+                (1, "$.a"),
+                (1, "$.tags"),
+                (2, "$.tags[0]"),
+                (2, "$.tags[1]")
+            ]
+        );
+        view.expand();
+        view.next();
+        view.next();
+        assert_eq!(view.selected_node().unwrap().path, "$.a.c");
+        view.collapse();
+        assert_eq!(view.selected_node().unwrap().path, "$.a");
+        view.collapse();
+        assert_eq!(paths(&view).len(), 5);
+
+        view.previous();
+        view.collapse();
+        assert_eq!(paths(&view), [(0, "$")]);
+        view.next();
+        assert_eq!(view.selected(), 0);
+    }
+
+    #[test]
+    fn selected_sql() {
+        let mut view = view();
+        view.next();
+        view.next();
+        assert_eq!(
+            view.expression().unwrap(),
+            r#"json_extract("data", '$.a.b')"#
+        );
+        let condition = view.condition().unwrap();
+        assert_eq!(condition.to_string(), "data ->> '$.a.b' = 'x'y'");
+        assert_eq!(view.column_name().unwrap(), "data_a_b");
+
+        view.next();
+        assert_eq!(view.condition().unwrap().operator, Operator::IsNull);
+        view.next();
+        view.next();
+        assert_eq!(view.condition().unwrap().value, "1");
+        assert_eq!(view.column_name().unwrap(), "data_tags_0");
+    }
+}
@@ -1,21 +1,62 @@
 mod app;
 mod cli;
+mod command;
 mod database;
+mod datetime;
+mod editor;
+mod filter;
+mod group;
+mod history;
+mod import;
+mod json;
 mod model;
+mod output;
+mod plan;
 mod popup;
+mod schema;
+mod settings;
+mod sql;
+mod store;
 mod ui;
 
+use std::process::ExitCode;
+
 use anyhow::Result;
 
 use crate::database::SqliteDb;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let args = cli::Args::from();
-    let db = SqliteDb::connect(&args.input, false).await?;
+    let db = if args.read_only {
+        SqliteDb::connect_read_only(&args.input).await?
+    } else {
+        SqliteDb::connect(&args.input, false).await?
+    };
+
+    if let Some(command) = &args.command {
+        return command.run(&db, args.format, &mut std::io::stdout()).await;
+    }
 
-    let mut app = app::App::new(db).await?;
+    // Unreadable files are replaced by an empty history or settings kept
+    // in memory only, leaving the files as they are.
+    let mut problems = Vec::new();
+    let history = history::History::load(&args.input).unwrap_or_else(|err| {
+        problems.push(format!("query history not loaded: {err:#}"));
+        history::History::default()
+    });
+    let settings = settings::Settings::load(&args.input).unwrap_or_else(|err| {
+        problems.push(format!("settings not loaded: {err:#}"));
+        settings::Settings::default()
+    });
+    let mut app = app::App::new(db, &args.startup())
+        .await?
+        .with_wrap(!args.no_wrap)
+        .with_watch(args.watch)
+        .with_history(history)
+        .with_settings(settings)
+        .with_message((!problems.is_empty()).then(|| problems.join("; ")));
     app.run().await?;
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
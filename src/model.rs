@@ -1,4 +1,12 @@
-use anyhow::Result;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use futures::{future::BoxFuture, FutureExt};
 use ratatui::{
     prelude::*,
     widgets::{ScrollbarState, TableState},
@@ -6,10 +14,31 @@ use ratatui::{
 use serde_json::Value;
 use style::{palette::tailwind, Color};
 
-use crate::database::Database;
+use crate::{
+    database::{
+        Check, ColumnStats, Database, DatabaseInfo, Ident, Maintenance, QueryStats, ResultSet,
+        Storage, TableSchema, Version, WhereClause,
+    },
+    datetime::{self, TimeFormat, Zone},
+    editor::Editor,
+    filter::{Condition, Filter, FilterForm, Operator},
+    group::Grouping,
+    history::{History, SavedQuery},
+    import::{self, Import},
+    json::JsonView,
+    output::{self, Format},
+    plan::{Plan, PlanChange},
+    schema::{self, SchemaChange, SchemaForm},
+    settings::Settings,
+    sql,
+};
 
 pub const ITEM_HEIGHT: u16 = 4;
 pub const MAX_TABLE_ITEMS: usize = 100;
+/// Rows of a file shown before importing it.
+const IMPORT_PREVIEW_ROWS: usize = 100;
+const READ_ONLY: &str = "maintenance is disabled, the database is opened read-only";
+const READ_ONLY_SCHEMA: &str = "schema changes are disabled, the database is opened read-only";
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct TableColors {
@@ -42,21 +71,84 @@ impl TableColors {
     }
 }
 
+/// Columns and rows of a single table as fetched from the database.
+#[derive(Debug, Clone)]
+pub struct Contents {
+    columns: Vec<String>,
+    /// Time format of each column detected from its declared type and
+    /// first rows when they were loaded, empty for a statement.
+    times: Vec<Option<TimeFormat>>,
+    rows: Vec<Vec<Value>>,
+    /// Statistics of the statement the rows were read with.
+    stats: Option<QueryStats>,
+}
+
+impl Contents {
+    pub fn new(columns: Vec<String>, rows: Vec<Vec<Value>>) -> Self {
+        Self {
+            columns,
+            times: Vec::new(),
+            rows,
+            stats: None,
+        }
+    }
+
+    /// Sets the declared `types` of the columns of a table, detecting the
+    /// time formats of those declared as times or holding epoch times.
+    pub fn with_types(self, types: Vec<String>) -> Self {
+        let times = types
+            .iter()
+            .enumerate()
+            .map(|(i, declared)| {
+                let values = self.rows.iter().filter_map(|row| row.get(i)?.as_str());
+                TimeFormat::detect(&self.columns[i], declared, values)
+            })
+            .collect();
+        Self { times, ..self }
+    }
+
+    pub fn with_stats(self, stats: Option<QueryStats>) -> Self {
+        Self { stats, ..self }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Table {
     name: String,
+    /// Column names, used for completion in the query editor.
     columns: Vec<String>,
-    rows: Vec<Vec<Value>>,
+    overview: Vec<Value>,
+    contents: Option<Contents>,
     schema: String,
+    /// SQL of a saved query listed among the tables.
+    saved_query: Option<String>,
 }
 
 impl Table {
-    pub fn new(name: String, columns: Vec<String>, rows: Vec<Vec<Value>>, schema: String) -> Self {
+    pub fn new(name: String, columns: Vec<String>, overview: Vec<Value>, schema: String) -> Self {
         Self {
             name,
             columns,
-            rows,
+            overview,
+            contents: None,
             schema,
+            saved_query: None,
+        }
+    }
+
+    fn saved(id: usize, query: &SavedQuery) -> Self {
+        Self {
+            name: query.name.clone(),
+            columns: Vec::new(),
+            overview: vec![
+                Value::from(id.to_string()),
+                Value::from(query.name.clone()),
+                Value::from(""),
+                Value::from("saved query"),
+            ],
+            contents: None,
+            schema: query.sql.clone(),
+            saved_query: Some(query.sql.clone()),
         }
     }
 
@@ -64,40 +156,351 @@ impl Table {
         &self.name
     }
 
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Row describing the table in the main list.
+    pub fn overview(&self) -> &[Value] {
+        &self.overview
+    }
+
     pub fn rows(&self) -> &[Vec<Value>] {
-        &self.rows
+        self.contents
+            .as_ref()
+            .map_or(&[], |contents| contents.rows.as_slice())
     }
 
     pub fn schema(&self) -> &str {
         &self.schema
     }
 
-    pub fn set_rows(&mut self, rows: Vec<Vec<Value>>) {
-        self.rows = rows;
+    /// Whether the table's rows are cached and can be shown without a load.
+    pub fn is_loaded(&self) -> bool {
+        self.contents.is_some()
     }
 
-    pub fn set_columns(&mut self, columns: Vec<String>) {
-        self.columns = columns;
+    pub fn set_contents(&mut self, contents: Contents) {
+        self.contents = Some(contents);
     }
+
+    /// Drops the cached rows so they are fetched again on the next visit.
+    pub fn invalidate(&mut self) {
+        self.contents = None;
+    }
+}
+
+/// Data loaded in the background, applied with [`Model::apply`].
+#[derive(Debug)]
+pub enum Update {
+    Overview(Vec<Table>),
+    TableView {
+        table: String,
+        contents: Contents,
+    },
+    Query {
+        sql: String,
+        contents: Contents,
+        /// Whether the statement leaves the database unchanged.
+        read_only: bool,
+    },
+    /// Rows of the view grouped by a column, shown in query view.
+    Grouped {
+        grouping: Box<Grouping>,
+        contents: Contents,
+    },
+    Plan(Plan),
+    /// Profile of the active column.
+    ColumnStats(Box<ColumnStats>),
+    /// JSON document of the selected cell.
+    Json(Box<JsonView>),
+    Report(Report),
+    /// Problem found by a running check, appended to its report.
+    ReportLine(String),
+    /// A check finished after finding `problems` problems.
+    Checked {
+        check: Check,
+        problems: usize,
+    },
+    /// A file was read for import and is previewed.
+    ImportPreview(Box<PendingImport>),
+    /// Rows of an import were written, with the reloaded main list.
+    Imported {
+        table: String,
+        rows: u64,
+        created: bool,
+        tables: Vec<Table>,
+    },
+    /// Progress of the running load, shown in place of the loading text.
+    Status(String),
+    Maintained(Box<MaintenanceResult>),
+    /// Schema of the selected table, if any, for the schema editor.
+    SchemaEditor(Option<Box<TableSchema>>),
+    /// Schema of the open table for the create index form on `columns`.
+    IndexForm {
+        schema: Box<TableSchema>,
+        columns: Vec<String>,
+    },
+    /// Schema of the open table for a virtual column computed by
+    /// `expression`.
+    GeneratedColumnForm {
+        schema: Box<TableSchema>,
+        name: String,
+        expression: String,
+    },
+    /// Plans of the view's queries before and after the reviewed index.
+    IndexPlans(Vec<PlanChange>),
+    /// A schema change ran, with the reloaded main list.
+    SchemaChanged {
+        action: schema::Action,
+        statements: usize,
+        tables: Vec<Table>,
+    },
+    /// A backup of `schema` into `path` finished.
+    BackedUp {
+        schema: String,
+        path: String,
+        pages: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ViewState {
     Main,
     Table,
+    Query,
+    /// Read-only listing such as the info dashboard or a check report.
+    Report,
+}
+
+/// What a report view lists, used to reload it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportKind {
+    /// Properties of the database file.
+    Info,
+    Check {
+        check: Check,
+        table: Option<String>,
+    },
+    /// Preview of a file to import into `table`.
+    Import {
+        path: String,
+        table: String,
+    },
+}
+
+impl ReportKind {
+    pub fn title(&self) -> String {
+        match self {
+            ReportKind::Info => "database info".to_string(),
+            ReportKind::Check {
+                check,
+                table: Some(table),
+            } => format!("{} of {table}", check.pragma()),
+            ReportKind::Check { check, table: None } => check.pragma().to_string(),
+            ReportKind::Import { path, table } => format!("import of {path} into {table}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    kind: ReportKind,
+    contents: Contents,
+}
+
+/// A file read for import, waiting to be written with
+/// [`Model::load_import_write`].
+#[derive(Debug, Clone)]
+pub struct PendingImport {
+    path: String,
+    table: String,
+    /// Whether `table` exists and the rows are appended to it.
+    append: bool,
+    import: Import,
+}
+
+/// Fuzzy search over the query history.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryPicker {
+    filter: String,
+    selected: usize,
+}
+
+impl HistoryPicker {
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+}
+
+/// Query plan popup, showing either the plan tree or the bytecode.
+#[derive(Debug, Clone)]
+pub struct PlanView {
+    plan: Plan,
+    bytecode: bool,
+    scroll: usize,
+}
+
+impl PlanView {
+    pub fn plan(&self) -> &Plan {
+        &self.plan
+    }
+
+    pub fn is_bytecode(&self) -> bool {
+        self.bytecode
+    }
+
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+}
+
+/// Maintenance popup: the menu, the confirmation of an action or its
+/// result.
+#[derive(Debug, Clone)]
+pub enum MaintenanceView {
+    Menu { selected: usize },
+    Confirm(Maintenance),
+    Done(Box<MaintenanceResult>),
+}
+
+/// A finished maintenance action with the file properties before and after
+/// it.
+#[derive(Debug, Clone)]
+pub struct MaintenanceResult {
+    pub action: Maintenance,
+    pub before: DatabaseInfo,
+    pub after: DatabaseInfo,
+}
+
+/// Schema editor popup: the menu of actions, the form of one or the
+/// statements generated from it for review.
+#[derive(Debug, Clone)]
+pub enum SchemaView {
+    Menu {
+        table: Option<Box<TableSchema>>,
+        selected: usize,
+    },
+    Form(Box<SchemaForm>),
+    Review {
+        form: Box<SchemaForm>,
+        statements: Vec<String>,
+        /// Whether the statements rebuild the table.
+        rebuild: bool,
+        /// Plans a new index changes, loaded after the review opens.
+        plans: Vec<PlanChange>,
+    },
+}
+
+/// Polling of the database for changes made by other processes.
+#[derive(Debug, Clone)]
+struct Watch {
+    interval: Duration,
+    version: Option<Version>,
+    /// The main list missed a change while another view was shown.
+    stale: bool,
+    /// When the last change was picked up.
+    updated: Option<Instant>,
+}
+
+/// Completion popup of the query editor.
+#[derive(Debug, Clone, Default)]
+pub struct Completion {
+    items: Vec<String>,
+    selected: usize,
+    /// Length in characters of the word being completed.
+    prefix: usize,
+}
+
+impl Completion {
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+}
+
+/// Popup or panel over the current view, which takes the keys while open.
+/// Only one is open at a time.
+#[derive(Debug, Clone)]
+pub enum Overlay {
+    /// Query editor, with the completion popup of the word being typed.
+    Editor {
+        editor: Editor,
+        completion: Option<Completion>,
+    },
+    /// History search over the editor, which is shown again when closed.
+    Picker {
+        picker: HistoryPicker,
+        editor: Editor,
+    },
+    Plan(PlanView),
+    ColumnStats(Box<ColumnStats>),
+    Json(Box<JsonView>),
+    Filter(Box<FilterForm>),
+    Maintenance(MaintenanceView),
+    SchemaEditor(SchemaView),
+}
+
+/// Result of an SQL statement entered by the user.
+#[derive(Debug, Clone)]
+struct QueryResult {
+    sql: String,
+    contents: Contents,
+    /// Whether the statement leaves the database unchanged, only such are
+    /// run again by watch mode.
+    read_only: bool,
+    /// Summary the statement was built for by [`Model::load_group`].
+    group: Option<Grouping>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Model<D: Database> {
     tables: Vec<Table>,
+    columns: Vec<String>,
     selected_table_id: usize,
     state: TableState,
     scroll_state: ScrollbarState,
     active_column: usize,
+    marked_columns: Vec<usize>,
     colors: TableColors,
     view_state: ViewState,
     schema: bool,
     column: bool,
+    record: bool,
+    sort: Option<(usize, SortOrder)>,
+    wrap: bool,
+    /// Zone timestamps are shown in, `None` shows them as stored.
+    times: Option<Zone>,
+    page_size: usize,
+    prompt: Option<String>,
+    message: Option<String>,
+    loading: Option<Instant>,
+    /// Progress of the running load.
+    status: Option<String>,
+    query: Option<QueryResult>,
+    report: Option<Report>,
+    /// Whether the main list shows disk usage columns.
+    storage: bool,
+    overlay: Option<Overlay>,
+    /// Filters applied to tables in this session, overriding saved ones.
+    filters: HashMap<String, Filter>,
+    import: Option<PendingImport>,
+    watch: Option<Watch>,
+    history: History,
+    settings: Settings,
     db: D,
 }
 
@@ -105,315 +508,2352 @@ impl<D: Database> Model<D> {
     pub fn new(db: D) -> Self {
         Model {
             tables: Vec::new(),
+            columns: Self::columns(false),
             selected_table_id: 0,
             state: TableState::default().with_selected(0),
             scroll_state: ScrollbarState::default(),
             active_column: 0,
+            marked_columns: Vec::new(),
             colors: TableColors::new(&tailwind::TEAL),
             view_state: ViewState::Main,
             schema: false,
             column: false,
+            record: false,
+            sort: None,
+            wrap: true,
+            times: Some(Zone::Local),
+            page_size: 1,
+            prompt: None,
+            message: None,
+            loading: None,
+            status: None,
+            query: None,
+            report: None,
+            storage: false,
+            overlay: None,
+            filters: HashMap::new(),
+            import: None,
+            watch: None,
+            history: History::default(),
+            settings: Settings::default(),
             db,
         }
     }
     pub async fn initialize(&mut self) -> Result<()> {
-        let tables = self.db.tables().await?;
-        let items_future: Vec<_> = tables
-            .into_iter()
-            .enumerate()
-            .map(|(id, table)| {
-                let db = self.db.clone();
-                async move {
-                    let columns = Self::columns(None, &db, &ViewState::Main).await?;
-                    let rows = Self::rows(id + 1, &table, &db, &ViewState::Main).await?;
-                    let schema = db.schema(table.as_str()).await?;
-                    Ok::<Table, anyhow::Error>(Table::new(table, columns, rows, schema))
-                }
-            })
-            .collect();
-        let items: Vec<Result<Table, _>> = futures::future::join_all(items_future).await;
-        self.tables = items.into_iter().collect::<Result<Vec<Table>>>()?;
+        let update = self.load_overview().await?;
+        self.apply(update);
         self.scroll_state = ScrollbarState::new(self.tables.len().saturating_sub(1));
 
         Ok(())
     }
 
+    /// Fetches the main list: one overview row and the schema per table.
+    pub fn load_overview(&self) -> impl Future<Output = Result<Update>> + Send + 'static {
+        let db = self.db.clone();
+        let with_storage = self.storage;
+
+        async move {
+            let tables = db.tables().await?;
+            let storage = match with_storage {
+                true => Some(db.storage().await?),
+                false => None,
+            };
+            let storage = storage.as_deref();
+            let items_future: Vec<_> = tables
+                .into_iter()
+                .enumerate()
+                .map(|(id, table)| {
+                    let db = db.clone();
+                    async move {
+                        let columns = db.columns(&table).await?;
+                        let rows = Self::rows(id + 1, &table, &columns, storage, &db).await?;
+                        let schema = db.schema(table.as_str()).await?;
+                        Ok::<Table, anyhow::Error>(Table::new(table, columns, rows, schema))
+                    }
+                })
+                .collect();
+            let items: Vec<Result<Table, _>> = futures::future::join_all(items_future).await;
+            Ok(Update::Overview(
+                items.into_iter().collect::<Result<Vec<Table>>>()?,
+            ))
+        }
+    }
+
     pub fn next(&mut self) {
+        let last = self.item_count().saturating_sub(1);
         let i = match self.state.selected() {
-            Some(i) => match self.view_state {
-                ViewState::Main => {
-                    if i >= self.tables.len().saturating_sub(1) {
-                        0
-                    } else {
-                        i + 1
-                    }
-                }
-                ViewState::Table => match self.tables.get(self.selected_table_id) {
-                    Some(table) => {
-                        if i >= table.rows().len().saturating_sub(1) {
-                            0
-                        } else {
-                            i + 1
-                        }
-                    }
-                    None => 0,
-                },
-            },
+            Some(i) if i >= last && self.wrap => 0,
+            Some(i) => (i + 1).min(last),
             None => 0,
         };
-        self.state.select(Some(i));
-        self.scroll_state = self
-            .scroll_state
-            .position(Self::calculate_scroll_position(i, ITEM_HEIGHT));
+        self.select(i);
     }
 
     pub fn previous(&mut self) {
+        let last = self.item_count().saturating_sub(1);
         let i = match self.state.selected() {
-            Some(i) => match self.view_state {
-                ViewState::Main => {
-                    if i == 0 {
-                        self.tables.len().saturating_sub(1)
-                    } else {
-                        i - 1
-                    }
-                }
-                ViewState::Table => match self.tables.get(self.selected_table_id) {
-                    Some(table) => {
-                        if i == 0 {
-                            table.rows().len().saturating_sub(1)
-                        } else {
-                            i - 1
-                        }
-                    }
-                    None => 0,
-                },
-            },
+            Some(0) if self.wrap => last,
+            Some(i) => i.saturating_sub(1),
             None => 0,
         };
+        self.select(i);
+    }
+
+    pub fn next_page(&mut self) {
+        let i = self.state.selected().unwrap_or(0);
+        self.select(i.saturating_add(self.page_size));
+    }
+
+    pub fn previous_page(&mut self) {
+        let i = self.state.selected().unwrap_or(0);
+        self.select(i.saturating_sub(self.page_size));
+    }
+
+    pub fn first(&mut self) {
+        self.select(0);
+    }
+
+    pub fn last(&mut self) {
+        self.select(self.item_count().saturating_sub(1));
+    }
+
+    /// Selects the 1-based `row`, as entered in the goto prompt.
+    pub fn goto(&mut self, row: usize) {
+        self.select(row.saturating_sub(1));
+    }
+
+    pub fn select(&mut self, index: usize) {
+        let i = index.min(self.item_count().saturating_sub(1));
         self.state.select(Some(i));
         self.scroll_state = self
             .scroll_state
             .position(Self::calculate_scroll_position(i, ITEM_HEIGHT));
     }
 
-    pub async fn switch_to_table_view(&mut self) -> Result<()> {
-        if self.view_state == ViewState::Main {
-            self.schema = false;
-            self.column = false;
-            self.active_column = 0;
-            self.selected_table_id = self.state.selected().unwrap_or(0);
-            self.state = TableState::default().with_selected(0);
-            self.view_state = ViewState::Table;
-
-            for i in 0..self.tables.len() {
-                if let Some(table) = self.tables.get_mut(i) {
-                    let rows = Self::rows(i + 1, table.name(), &self.db, &ViewState::Table).await?;
-                    let columns =
-                        Self::columns(Some(table.name()), &self.db, &ViewState::Table).await?;
-                    table.set_rows(rows);
-                    table.set_columns(columns);
-                }
-            }
+    /// Selects the item at `position / length` of the current list, used when
+    /// jumping via the scrollbar.
+    pub fn select_fraction(&mut self, position: usize, length: usize) {
+        let last = self.item_count().saturating_sub(1);
+        let index = if length <= 1 {
+            0
+        } else {
+            (last * position.min(length - 1)) / (length - 1)
+        };
+        self.select(index);
+    }
 
-            if let Some(selected_table) = self.tables.get(self.selected_table_id) {
-                let max_items = selected_table.rows().len().saturating_sub(1);
-                self.scroll_state =
-                    ScrollbarState::new(Self::calculate_scroll_position(max_items, ITEM_HEIGHT));
+    pub fn item_count(&self) -> usize {
+        match self.view_state {
+            ViewState::Main => self.tables.len(),
+            ViewState::Table | ViewState::Query | ViewState::Report => {
+                self.contents().map_or(0, |contents| contents.rows.len())
             }
         }
-        Ok(())
     }
 
-    pub async fn switch_to_main_view(&mut self) -> Result<()> {
-        if self.view_state == ViewState::Table {
-            self.column = false;
-            self.active_column = 0;
-            self.selected_table_id = self
-                .state
-                .selected()
-                .unwrap_or(0)
-                .min(self.tables.len().saturating_sub(1));
-            self.state = TableState::default().with_selected(0);
-            self.view_state = ViewState::Main;
-            for i in 0..self.tables.len() {
-                self.tables[i].rows =
-                    Self::rows(i + 1, &self.tables[i].name, &self.db, &ViewState::Main).await?;
-                self.tables[i].columns = Self::columns(None, &self.db, &ViewState::Main).await?;
-            }
-            let max_items = self.tables.len().saturating_sub(1);
-            self.scroll_state =
-                ScrollbarState::new(Self::calculate_scroll_position(max_items, ITEM_HEIGHT));
+    /// Rows of the current view: one overview row per table in main view,
+    /// the shown rows otherwise.
+    fn current_rows(&self) -> Vec<&[Value]> {
+        match self.view_state {
+            ViewState::Main => self.tables.iter().map(Table::overview).collect(),
+            ViewState::Table | ViewState::Query | ViewState::Report => self.get_table_rows(),
         }
-        Ok(())
     }
 
-    pub fn tables(&self) -> &[Table] {
-        &self.tables
+    /// Rows shown in table or query view.
+    fn contents(&self) -> Option<&Contents> {
+        match self.view_state {
+            ViewState::Main => None,
+            ViewState::Table => self
+                .tables
+                .get(self.selected_table_id)
+                .and_then(|table| table.contents.as_ref()),
+            ViewState::Query => self.query.as_ref().map(|query| &query.contents),
+            ViewState::Report => self.report.as_ref().map(|report| &report.contents),
+        }
     }
 
-    pub fn table_schema(&self) -> Option<&str> {
-        self.tables
-            .get(self.state.selected().unwrap_or(0))
-            .map(Table::schema)
+    /// Statistics of the statement behind the shown rows.
+    pub fn stats(&self) -> Option<&QueryStats> {
+        self.contents()?.stats.as_ref()
     }
 
-    pub fn get_table_columns(&self) -> &[String] {
-        self.tables
-            .get(self.selected_table_id)
-            .map_or(&[], |table| table.columns.as_slice())
+    /// Writes the statistics of the last statements run this session to
+    /// `path`, as JSON when it ends in `.json` and as CSV otherwise.
+    pub fn export_stats(&mut self, path: &str) -> Result<()> {
+        let format = if path.ends_with(".json") {
+            Format::Json
+        } else {
+            Format::Csv
+        };
+        let log = self.db.stats();
+        let mut file = std::fs::File::create(path)?;
+        output::write(&mut file, &QueryStats::to_result_set(&log), format)?;
+        self.set_message(format!("wrote {} statements to {path}", log.len()));
+        Ok(())
     }
 
-    pub fn get_table_rows(&self) -> Vec<&[Value]> {
-        self.tables.get(self.selected_table_id).map_or_else(
-            || vec![&[] as &[Value]],
-            |table| table.rows().iter().map(Vec::as_slice).collect(),
-        )
+    fn contents_mut(&mut self) -> Option<&mut Contents> {
+        match self.view_state {
+            ViewState::Main => None,
+            ViewState::Table => self
+                .tables
+                .get_mut(self.selected_table_id)
+                .and_then(|table| table.contents.as_mut()),
+            ViewState::Query => self.query.as_mut().map(|query| &mut query.contents),
+            ViewState::Report => self.report.as_mut().map(|report| &mut report.contents),
+        }
     }
 
-    pub fn longest_in_column(&self) -> u16 {
-        let selected = self.state().selected().unwrap_or(0);
-        if let Some(table) = self.tables.get(self.selected_table_id) {
-            if let Some(column_name) = table.columns.get(self.active_column) {
-                let header_len = column_name.as_str().len();
-
-                let cell_len = table
-                    .rows()
-                    .get(selected)
-                    .and_then(|row| row.get(self.active_column))
-                    .and_then(|value| value.as_str())
-                    .map_or(0, str::len);
+    pub async fn switch_to_table_view(&mut self) -> Result<()> {
+        if let Some(load) = self.enter_table_view() {
+            self.apply(load.await?);
+        }
+        Ok(())
+    }
 
-                return u16::try_from(header_len.max(cell_len)).unwrap_or(0);
+    /// Enters table view for the selected table. Cached tables are shown
+    /// right away; otherwise the returned fetch has to be run and its result
+    /// handed to [`Model::apply`].
+    pub fn enter_table_view(&mut self) -> Option<BoxFuture<'static, Result<Update>>> {
+        if self.view_state != ViewState::Main {
+            return None;
+        }
+        let table_id = self.state.selected().unwrap_or(0);
+        match self.tables.get(table_id) {
+            Some(Table {
+                saved_query: Some(sql),
+                ..
+            }) => Some(self.load_query(sql).boxed()),
+            Some(table) if table.is_loaded() => {
+                self.show_table(table_id);
+                None
+            }
+            Some(table) => Some(self.load_table(table.name()).boxed()),
+            None => {
+                self.show_table(table_id);
+                None
             }
         }
-        0
     }
 
-    pub fn view_state(&self) -> ViewState {
-        self.view_state.clone()
+    /// Fetches the rows of `table`, e.g. after it was invalidated.
+    pub fn load_table(&self, table: &str) -> impl Future<Output = Result<Update>> + Send + 'static {
+        let table = table.to_string();
+        let filter = self.filter(&table).map_or(Ok(None), Filter::to_where);
+        let db = self.db.clone();
+
+        async move {
+            let filter = filter?;
+            let columns = db.columns(&table).await?;
+            let types = db.column_types(&table).await?;
+            let result = db.rows(&table, filter.as_ref()).await?;
+            Ok(Update::TableView {
+                table,
+                contents: Contents::new(columns, result.rows)
+                    .with_types(types)
+                    .with_stats(result.stats),
+            })
+        }
     }
 
-    pub fn colors(&self) -> &TableColors {
-        &self.colors
+    /// Opens `table` in table view, loading its rows if needed.
+    pub async fn open_table(&mut self, table: &str) -> Result<()> {
+        let Some(table_id) = self.table_id(table) else {
+            bail!("no such table: {table}");
+        };
+        self.switch_to_main_view();
+        self.select(table_id);
+        self.switch_to_table_view().await
     }
 
-    pub fn state(&self) -> &TableState {
-        &self.state
+    pub async fn run_query(&mut self, sql: &str) -> Result<()> {
+        let update = self.load_query(sql).await?;
+        self.apply(update);
+        Ok(())
     }
 
-    pub fn scroll_state(&self) -> &ScrollbarState {
-        &self.scroll_state
+    /// Runs `sql`, the result is shown in query view by [`Model::apply`].
+    pub fn load_query(&self, sql: &str) -> impl Future<Output = Result<Update>> + Send + 'static {
+        let sql = sql.to_string();
+        let db = self.db.clone();
+
+        async move {
+            // Checked first, as a statement that changes the schema may not
+            // compile anymore once it ran.
+            let read_only = db.reads_only(&sql).await?;
+            let result = db.query(&sql).await?;
+            Ok(Update::Query {
+                sql,
+                contents: Contents::new(result.columns, result.rows).with_stats(result.stats),
+                read_only,
+            })
+        }
     }
 
-    pub fn is_schema_enabled(&self) -> bool {
-        self.schema
+    /// Groups the rows of the open table or query by the active column,
+    /// counting the rows of each value.
+    pub fn load_group(&self) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        if !self.column {
+            return None;
+        }
+        let table = match self.view_state {
+            ViewState::Table => {
+                let table = self.tables.get(self.selected_table_id)?;
+                table
+                    .saved_query
+                    .is_none()
+                    .then(|| table.name().to_string())
+            }
+            ViewState::Query => None,
+            ViewState::Main | ViewState::Report => return None,
+        };
+        // Grouping a table reads it by name, only a query needs its SQL.
+        let (sql, _) = self.statement().ok()??;
+        let columns = self.get_table_columns();
+        let column = columns.get(self.active_column)?;
+        let mut grouping = Grouping::new(table.as_deref(), &sql, column, columns);
+        let db = self.db.clone();
+
+        Some(async move {
+            let result = db.query(&grouping.sql()).await?;
+            let contents = Contents::new(result.columns, result.rows).with_stats(result.stats);
+            grouping.set_keys(db.query(&grouping.keys_sql()).await?.rows);
+            Ok(Update::Grouped {
+                grouping: Box::new(grouping),
+                contents,
+            })
+        })
     }
 
-    pub fn toggle_schema(&mut self) {
-        if self.view_state == ViewState::Main {
-            self.schema = !self.schema;
+    /// Pivots the shown summary on the next column, see
+    /// [`Grouping::next_pivot`].
+    pub fn load_pivot(&self) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        let mut grouping = self.grouping()?.clone();
+        let column = grouping.next_pivot().map(str::to_string);
+        let db = self.db.clone();
+
+        Some(async move {
+            let values = match &column {
+                Some(column) => db.query(&grouping.pivot_sql(column)).await?.rows,
+                None => Vec::new(),
+            };
+            grouping.set_pivot(column.as_deref(), values);
+            let result = db.query(&grouping.sql()).await?;
+            let contents = Contents::new(result.columns, result.rows).with_stats(result.stats);
+            Ok(Update::Grouped {
+                grouping: Box::new(grouping),
+                contents,
+            })
+        })
+    }
+
+    /// Reads the rows of the selected group, narrowed to the value of the
+    /// active column in a pivot.
+    pub fn load_drill_down(
+        &mut self,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        let grouping = self.grouping()?;
+        let key = self.get_table_rows().get(self.state.selected()?)?.first()?;
+        let column = if self.column { self.active_column } else { 0 };
+        match grouping.drill_sql(key, column) {
+            Some(sql) => Some(self.load_query(&sql)),
+            None => {
+                self.set_message("the group changed, group the rows again".to_string());
+                None
+            }
         }
     }
 
-    pub fn is_column_enabled(&self) -> bool {
-        self.column
+    /// Summary shown in query view, if the rows are grouped.
+    pub fn grouping(&self) -> Option<&Grouping> {
+        match self.view_state {
+            ViewState::Query => self.query.as_ref()?.group.as_ref(),
+            _ => None,
+        }
     }
 
-    pub fn toggle_column(&mut self) {
-        self.column = !self.column;
+    /// Selects the first row of the open table or query result matching the
+    /// SQL `condition`.
+    pub async fn select_where(&mut self, condition: &str) -> Result<()> {
+        let statement = match self.view_state {
+            ViewState::Table | ViewState::Query => self.statement()?,
+            ViewState::Main | ViewState::Report => None,
+        };
+        let Some((sql, params)) = statement else {
+            bail!("no table or query is open");
+        };
+        match self.db.position(&sql, &params, condition).await? {
+            Some(position) => self.select(position),
+            None => bail!("no row matches: {condition}"),
+        }
+        Ok(())
     }
 
-    pub fn active_column(&self) -> usize {
-        self.active_column
+    /// Filter of `table`: the one applied in this session, or else the
+    /// saved one.
+    pub fn filter(&self, table: &str) -> Option<&Filter> {
+        self.filters
+            .get(table)
+            .or_else(|| self.settings.filter(table))
+            .filter(|filter| !filter.is_empty())
     }
 
-    pub fn next_column(&mut self) {
-        if self.is_column_enabled() {
-            self.active_column = (self.active_column + 1)
-                % self
-                    .tables
-                    .get(self.selected_table_id)
-                    .map_or(0, |table| table.columns.len());
+    /// Filter of the table open in table view.
+    pub fn active_filter(&self) -> Option<&Filter> {
+        self.filter(self.selected_table()?)
+    }
+
+    pub fn filter_form(&self) -> Option<&FilterForm> {
+        match &self.overlay {
+            Some(Overlay::Filter(form)) => Some(form),
+            _ => None,
         }
     }
 
-    pub fn previous_column(&mut self) {
-        if self.is_column_enabled() {
-            self.active_column = if self.active_column == 0 {
-                self.tables
-                    .get(self.selected_table_id)
-                    .map_or(0, |table| table.columns.len())
-                    - 1
-            } else {
-                self.active_column - 1
-            };
+    pub fn filter_form_mut(&mut self) -> Option<&mut FilterForm> {
+        match &mut self.overlay {
+            Some(Overlay::Filter(form)) => Some(form),
+            _ => None,
         }
     }
 
-    pub fn get_info_text(&self) -> String {
-        let mut result =
-            String::from("(Esc) quit | (↑) move up | (↓) move down | (⇧ S) toggle column select");
-        match self.view_state {
-            ViewState::Main => {
-                result.push_str(" | (Space) toggle schema (→) table view");
-            }
-            ViewState::Table => {
-                result.push_str(" | (←) main view");
-            }
+    /// Opens the filter panel of the table open in table view.
+    pub fn open_filter(&mut self) {
+        if self.view_state != ViewState::Table {
+            return;
         }
+        let Some(table) = self
+            .tables
+            .get(self.selected_table_id)
+            .filter(|table| table.saved_query.is_none())
+        else {
+            return;
+        };
+        let filter = self.filter(table.name()).cloned().unwrap_or_default();
+        let form = FilterForm::new(table.name(), table.columns(), &filter);
+        self.overlay = Some(Overlay::Filter(Box::new(form)));
+    }
 
-        if self.is_column_enabled() {
-            result.push_str(" | (⇧ ←) previous column | (⇧ →) next column");
+    pub fn close_filter(&mut self) {
+        self.overlay = None;
+    }
+
+    /// Applies the conditions of the filter panel to its table, saving them
+    /// for later sessions when `save` is set. The returned fetch reloads the
+    /// table with the filter.
+    pub fn apply_filter(
+        &mut self,
+        save: bool,
+    ) -> Result<impl Future<Output = Result<Update>> + Send + 'static> {
+        let Some(form) = self.filter_form() else {
+            bail!("no filter is open");
+        };
+        let filter = form.filter();
+        filter.to_where()?;
+        let table = form.table().to_string();
+        if save {
+            self.settings.save_filter(&table, &filter)?;
         }
+        self.filters.insert(table.clone(), filter);
+        self.overlay = None;
+        Ok(self.load_table(&table))
+    }
 
-        result
+    /// SQL of the statement shown in query view.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_ref().map(|query| query.sql.as_str())
     }
 
-    async fn columns(name: Option<&str>, db: &D, view: &ViewState) -> Result<Vec<String>> {
-        match view {
-            ViewState::Main => Ok(vec!["#", "Table", "Columns", "Rows"]
-                .into_iter()
-                .map(String::from)
-                .collect()),
-            ViewState::Table => Ok(db.columns(name.unwrap()).await?),
+    /// Statement behind the current view and the values bound to its
+    /// placeholders: the query in query view, and the `SELECT` reading the
+    /// open or selected table otherwise.
+    pub fn statement(&self) -> Result<Option<(String, Vec<Value>)>> {
+        let table = match self.view_state {
+            ViewState::Query => return Ok(self.query().map(|sql| (sql.to_string(), Vec::new()))),
+            ViewState::Report => return Ok(None),
+            ViewState::Table => self.tables.get(self.selected_table_id),
+            ViewState::Main => self.state.selected().and_then(|i| self.tables.get(i)),
+        };
+        let Some(table) = table else {
+            return Ok(None);
+        };
+        match &table.saved_query {
+            Some(sql) => Ok(Some((sql.clone(), Vec::new()))),
+            None => self.table_query(table.name()).map(Some),
         }
     }
 
-    async fn rows(id: usize, table: &str, db: &D, view: &ViewState) -> Result<Vec<Vec<Value>>> {
-        match view {
-            ViewState::Main => {
-                let columns = db.columns(table).await?;
-                let rows = db.rows("*", table).await?;
-                let len = rows.len();
-
-                Ok(vec![vec![
-                    Value::from(id.to_string()),
-                    Value::from(table.to_string()),
-                    Value::from(columns.len().to_string()),
-                    Value::from(len.to_string()),
-                ]]
-                .into_iter()
-                .collect())
+    /// Query of the rows of `table` as [`Model::load_table`] reads them,
+    /// with its filter, ordered like the table view when it is open, and
+    /// the values bound to the filter's placeholders.
+    fn table_query(&self, table: &str) -> Result<(String, Vec<Value>)> {
+        let filter = self
+            .filter(table)
+            .map(Filter::to_where)
+            .transpose()?
+            .flatten();
+        let mut sql = WhereClause::select(table, filter.as_ref());
+        if let Some((column, order)) = self.sort.filter(|_| self.selected_table() == Some(table)) {
+            if let Some(name) = self.get_table_columns().get(column) {
+                sql.push_str(&format!(" ORDER BY {}", Ident::new(name)));
+                if order == SortOrder::Descending {
+                    sql.push_str(" DESC");
+                }
             }
-            ViewState::Table => db.rows("*", table).await,
         }
+        Ok((sql, filter.map(|filter| filter.params).unwrap_or_default()))
     }
 
-    fn calculate_scroll_position(index: usize, item_height: u16) -> usize {
+    /// Runs `EXPLAIN QUERY PLAN` and `EXPLAIN` for the current statement.
+    pub fn load_plan(&self) -> Result<impl Future<Output = Result<Update>> + Send + 'static> {
+        let Some((sql, params)) = self.statement()? else {
+            bail!("no statement to explain");
+        };
+        let db = self.db.clone();
+
+        Ok(async move {
+            let plan = db.explain_with(&[], &sql, &params).await?;
+            // The bytecode does not depend on the bound values.
+            let bytecode = db.query(&format!("EXPLAIN {sql}")).await?;
+            Ok(Update::Plan(Plan::new(sql, &plan, bytecode)))
+        })
+    }
+
+    pub fn plan(&self) -> Option<&PlanView> {
+        match &self.overlay {
+            Some(Overlay::Plan(plan)) => Some(plan),
+            _ => None,
+        }
+    }
+
+    fn plan_mut(&mut self) -> Option<&mut PlanView> {
+        match &mut self.overlay {
+            Some(Overlay::Plan(plan)) => Some(plan),
+            _ => None,
+        }
+    }
+
+    pub fn close_plan(&mut self) {
+        self.overlay = None;
+    }
+
+    /// Switches the plan popup between the plan tree and the bytecode.
+    pub fn toggle_bytecode(&mut self) {
+        if let Some(plan) = self.plan_mut() {
+            plan.bytecode = !plan.bytecode;
+            plan.scroll = 0;
+        }
+    }
+
+    pub fn scroll_plan(&mut self, lines: isize) {
+        if let Some(plan) = self.plan_mut() {
+            let len = if plan.bytecode {
+                plan.plan.bytecode.rows.len() + 2
+            } else {
+                plan.plan.steps.len()
+            };
+            plan.scroll = plan
+                .scroll
+                .saturating_add_signed(lines)
+                .min(len.saturating_sub(1));
+        }
+    }
+
+    /// Profiles the active column of the open table.
+    pub fn load_column_stats(
+        &self,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        let table = self.browsed_table()?.to_string();
+        let column = self.get_table_columns().get(self.active_column)?.clone();
+        let db = self.db.clone();
+
+        Some(async move {
+            let stats = db.column_stats(&table, &column).await?;
+            Ok(Update::ColumnStats(Box::new(stats)))
+        })
+    }
+
+    pub fn column_stats(&self) -> Option<&ColumnStats> {
+        match &self.overlay {
+            Some(Overlay::ColumnStats(stats)) => Some(stats),
+            _ => None,
+        }
+    }
+
+    pub fn close_column_stats(&mut self) {
+        self.overlay = None;
+    }
+
+    /// Parses the selected cell of the active column as JSON for the JSON
+    /// viewer.
+    pub fn load_json(&self) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        if self.view_state == ViewState::Main {
+            return None;
+        }
+        let cell = self
+            .get_table_rows()
+            .get(self.state.selected()?)?
+            .get(self.active_column)?;
+        let json = cell.as_str().map_or_else(|| cell.to_string(), String::from);
+        let column = self.get_table_columns().get(self.active_column)?.clone();
+        let table = self.browsed_table().map(String::from);
+        let db = self.db.clone();
+
+        Some(async move {
+            let nodes = db.json_tree(&json).await?;
+            let view = JsonView::new(table.as_deref(), &column, nodes);
+            Ok(Update::Json(Box::new(view)))
+        })
+    }
+
+    pub fn json(&self) -> Option<&JsonView> {
+        match &self.overlay {
+            Some(Overlay::Json(view)) => Some(view),
+            _ => None,
+        }
+    }
+
+    pub fn json_mut(&mut self) -> Option<&mut JsonView> {
+        match &mut self.overlay {
+            Some(Overlay::Json(view)) => Some(view),
+            _ => None,
+        }
+    }
+
+    pub fn close_json(&mut self) {
+        self.overlay = None;
+    }
+
+    /// Table open in table view, other than a saved query.
+    fn browsed_table(&self) -> Option<&str> {
+        if self.view_state != ViewState::Table {
+            return None;
+        }
+        self.tables
+            .get(self.selected_table_id)
+            .filter(|table| table.saved_query.is_none())
+            .map(Table::name)
+    }
+
+    /// Closes the JSON viewer and opens the filter panel of its table with a
+    /// condition on the selected element's value.
+    pub fn filter_json(&mut self) {
+        let Some(view) = self.json() else {
+            return;
+        };
+        let Some(condition) = view.condition() else {
+            return;
+        };
+        if view.table().is_none() || view.table() != self.browsed_table() {
+            self.set_message("only the cells of a table can be filtered".to_string());
+            return;
+        }
+        self.open_filter();
+        if let Some(form) = self.filter_form_mut() {
+            form.push_condition(condition);
+        }
+    }
+
+    /// Loads the schema of the JSON viewer's table for the form adding a
+    /// virtual column of the selected element.
+    pub fn json_column_form(
+        &mut self,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        let view = self.json()?;
+        let (Some(table), Some(name), Some(expression)) =
+            (view.table(), view.column_name(), view.expression())
+        else {
+            self.set_message("only the cells of a table can be extracted".to_string());
+            return None;
+        };
+        if self.db.is_read_only() {
+            self.set_message(READ_ONLY_SCHEMA.to_string());
+            return None;
+        }
+        let table = table.to_string();
+        self.overlay = None;
+        let db = self.db.clone();
+
+        Some(async move {
+            let schema = Box::new(db.table_schema(&table).await?);
+            Ok(Update::GeneratedColumnForm {
+                schema,
+                name,
+                expression,
+            })
+        })
+    }
+
+    pub fn maintenance(&self) -> Option<&MaintenanceView> {
+        match &self.overlay {
+            Some(Overlay::Maintenance(view)) => Some(view),
+            _ => None,
+        }
+    }
+
+    /// Selected action of the maintenance menu.
+    fn maintenance_selected(&mut self) -> Option<&mut usize> {
+        match &mut self.overlay {
+            Some(Overlay::Maintenance(MaintenanceView::Menu { selected })) => Some(selected),
+            _ => None,
+        }
+    }
+
+    /// Opens the maintenance menu, unless the database is read-only.
+    pub fn open_maintenance(&mut self) {
+        if self.db.is_read_only() {
+            self.set_message(READ_ONLY.to_string());
+        } else {
+            self.overlay = Some(Overlay::Maintenance(MaintenanceView::Menu { selected: 0 }));
+        }
+    }
+
+    pub fn close_maintenance(&mut self) {
+        self.overlay = None;
+    }
+
+    pub fn maintenance_next(&mut self) {
+        if let Some(selected) = self.maintenance_selected() {
+            *selected = (*selected + 1) % Maintenance::all().len();
+        }
+    }
+
+    pub fn maintenance_previous(&mut self) {
+        if let Some(selected) = self.maintenance_selected() {
+            *selected = selected
+                .checked_sub(1)
+                .unwrap_or(Maintenance::all().len() - 1);
+        }
+    }
+
+    /// Asks to confirm the action selected in the menu. `VACUUM INTO` asks
+    /// for the target file in the prompt first.
+    pub fn choose_maintenance(&mut self) {
+        let Some(&mut selected) = self.maintenance_selected() else {
+            return;
+        };
+        match Maintenance::all().into_iter().nth(selected) {
+            Some(Maintenance::VacuumInto(_)) => {
+                self.overlay = None;
+                self.prompt = Some("vacuum into ".to_string());
+            }
+            Some(action) => self.confirm_maintenance(action),
+            None => {}
+        }
+    }
+
+    /// Shows the confirmation of `action`.
+    pub fn confirm_maintenance(&mut self, action: Maintenance) {
+        if self.db.is_read_only() {
+            self.set_message(READ_ONLY.to_string());
+        } else {
+            self.overlay = Some(Overlay::Maintenance(MaintenanceView::Confirm(action)));
+        }
+    }
+
+    /// Runs the confirmed action, measuring the file before and after it.
+    /// `VACUUM INTO` reports the bytes written so far to `progress`.
+    pub fn load_maintenance(
+        &mut self,
+        progress: impl Fn(Update) + Send + Sync + 'static,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        let Some(Overlay::Maintenance(MaintenanceView::Confirm(action))) = self.overlay.take()
+        else {
+            return None;
+        };
+        let db = self.db.clone();
+
+        Some(async move {
+            let before = db.info().await?;
+            let sql = action.sql();
+            progress(Update::Status(format!("{sql}…")));
+            let run = db.maintain(&action);
+            let after = match &action {
+                Maintenance::VacuumInto(path) => {
+                    // The copy holds the live pages, the free ones are dropped.
+                    let total =
+                        before.page_size * before.page_count.saturating_sub(before.freelist_count);
+                    let mut ticks = tokio::time::interval(Duration::from_millis(250));
+                    tokio::pin!(run);
+                    loop {
+                        tokio::select! {
+                            after = &mut run => break after?,
+                            _ = ticks.tick() => {
+                                let written = std::fs::metadata(path).map_or(0, |m| m.len());
+                                progress(Update::Status(format!(
+                                    "{sql}: {written} of ~{total} bytes written"
+                                )));
+                            }
+                        }
+                    }
+                }
+                _ => run.await?,
+            };
+            Ok(Update::Maintained(Box::new(MaintenanceResult {
+                action,
+                before,
+                after,
+            })))
+        })
+    }
+
+    pub fn schema_editor(&self) -> Option<&SchemaView> {
+        match &self.overlay {
+            Some(Overlay::SchemaEditor(view)) => Some(view),
+            _ => None,
+        }
+    }
+
+    fn schema_editor_mut(&mut self) -> Option<&mut SchemaView> {
+        match &mut self.overlay {
+            Some(Overlay::SchemaEditor(view)) => Some(view),
+            _ => None,
+        }
+    }
+
+    /// Takes the schema editor out of the overlay to change its step.
+    fn take_schema_editor(&mut self) -> Option<SchemaView> {
+        match self.overlay.take() {
+            Some(Overlay::SchemaEditor(view)) => Some(view),
+            overlay => {
+                self.overlay = overlay;
+                None
+            }
+        }
+    }
+
+    fn set_schema_editor(&mut self, view: SchemaView) {
+        self.overlay = Some(Overlay::SchemaEditor(view));
+    }
+
+    /// Loads the schema of the table selected in the main list or open in
+    /// table view for the schema editor, unless the database is read-only.
+    pub fn open_schema_editor(
+        &mut self,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        if self.db.is_read_only() {
+            self.set_message(READ_ONLY_SCHEMA.to_string());
+            return None;
+        }
+        let table = match self.view_state {
+            ViewState::Main => self
+                .tables
+                .get(self.state.selected().unwrap_or(0))
+                .filter(|table| table.saved_query.is_none())
+                .map(|table| table.name().to_string()),
+            _ => self.selected_table().map(str::to_string),
+        };
+        let db = self.db.clone();
+
+        Some(async move {
+            let schema = match table {
+                Some(table) => Some(Box::new(db.table_schema(&table).await?)),
+                None => None,
+            };
+            Ok(Update::SchemaEditor(schema))
+        })
+    }
+
+    pub fn close_schema_editor(&mut self) {
+        self.overlay = None;
+    }
+
+    pub fn schema_editor_next(&mut self) {
+        if let Some(SchemaView::Menu { table, selected }) = self.schema_editor_mut() {
+            let len = schema::Action::available(table.as_deref()).len();
+            *selected = (*selected + 1) % len;
+        }
+    }
+
+    pub fn schema_editor_previous(&mut self) {
+        if let Some(SchemaView::Menu { table, selected }) = self.schema_editor_mut() {
+            let len = schema::Action::available(table.as_deref()).len();
+            *selected = selected.checked_sub(1).unwrap_or(len - 1);
+        }
+    }
+
+    /// Opens the form of the action selected in the menu. Dropping the
+    /// table has nothing to fill in and goes straight to the review.
+    pub fn choose_schema_action(&mut self) {
+        let Some(SchemaView::Menu { table, selected }) = self.take_schema_editor() else {
+            return;
+        };
+        let Some(&action) = schema::Action::available(table.as_deref()).get(selected) else {
+            return;
+        };
+        let table = match action {
+            schema::Action::CreateTable => None,
+            _ => table.map(|table| *table),
+        };
+        self.set_schema_editor(SchemaView::Form(Box::new(SchemaForm::new(action, table))));
+        if action == schema::Action::DropTable {
+            self.review_schema_change();
+        }
+    }
+
+    pub fn schema_form_mut(&mut self) -> Option<&mut SchemaForm> {
+        match self.schema_editor_mut() {
+            Some(SchemaView::Form(form)) => Some(form),
+            _ => None,
+        }
+    }
+
+    /// Generates the statements of the form for review, or shows why it
+    /// cannot be submitted.
+    pub fn review_schema_change(&mut self) {
+        let Some(SchemaView::Form(form)) = self.take_schema_editor() else {
+            return;
+        };
+        let reviewed = form.change().and_then(|change| {
+            let statements = change.statements(form.table())?;
+            let rebuild = form.table().is_some_and(|table| change.rebuilds(table));
+            Ok((statements, rebuild))
+        });
+        let view = match reviewed {
+            Ok((statements, rebuild)) => SchemaView::Review {
+                form,
+                statements,
+                rebuild,
+                plans: Vec::new(),
+            },
+            Err(err) => {
+                self.set_message(err.to_string());
+                SchemaView::Form(form)
+            }
+        };
+        self.set_schema_editor(view);
+    }
+
+    /// Loads the schema of the open table for the create index form on the
+    /// marked columns, or the active one when none are marked.
+    pub fn open_index_form(
+        &mut self,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        if self.view_state != ViewState::Table || !self.column {
+            return None;
+        }
+        if self.db.is_read_only() {
+            self.set_message(READ_ONLY_SCHEMA.to_string());
+            return None;
+        }
+        let table = self.selected_table()?.to_string();
+        let names = self.get_table_columns();
+        let columns: Vec<String> = match self.marked_columns.as_slice() {
+            [] => names.get(self.active_column).into_iter().cloned().collect(),
+            marked => marked
+                .iter()
+                .filter_map(|&c| names.get(c).cloned())
+                .collect(),
+        };
+        let db = self.db.clone();
+
+        Some(async move {
+            let schema = Box::new(db.table_schema(&table).await?);
+            Ok(Update::IndexForm { schema, columns })
+        })
+    }
+
+    /// Compares the plans of the open table's view and of a lookup of the
+    /// selected row by the index columns, without and with the reviewed
+    /// index. The index is built in a transaction that is rolled back.
+    pub fn load_index_plans(
+        &self,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        let Some(SchemaView::Review {
+            form, statements, ..
+        }) = self.schema_editor()
+        else {
+            return None;
+        };
+        let Ok(SchemaChange::CreateIndex {
+            table,
+            columns,
+            condition,
+            ..
+        }) = form.change()
+        else {
+            return None;
+        };
+        let queries = self.index_queries(&table, &columns, condition.as_deref());
+        let statements = statements.clone();
+        let db = self.db.clone();
+
+        Some(async move {
+            let mut plans = Vec::new();
+            for (sql, params) in queries? {
+                let before = db.explain_with(&[], &sql, &params).await?;
+                let after = db.explain_with(&statements, &sql, &params).await?;
+                plans.push(PlanChange {
+                    before: Plan::new(sql.clone(), &before, ResultSet::default()),
+                    after: Plan::new(sql, &after, ResultSet::default()),
+                });
+            }
+            Ok(Update::IndexPlans(plans))
+        })
+    }
+
+    /// The query of the table view, filtered and ordered like the view, and
+    /// a lookup of the selected row's values of `columns`, each with the
+    /// values bound to its placeholders.
+    fn index_queries(
+        &self,
+        table: &str,
+        columns: &[String],
+        condition: Option<&str>,
+    ) -> Result<Vec<(String, Vec<Value>)>> {
+        let view = self.table_query(table)?;
+        let names = self.get_table_columns();
+
+        // Matched like conditions of the filter panel: numbers as numbers,
+        // anything else quoted as text.
+        let rows = self.get_table_rows();
+        let row = self.state.selected().and_then(|i| rows.get(i));
+        let conditions = columns
+            .iter()
+            .map(|column| {
+                let value = names
+                    .iter()
+                    .position(|name| name == column)
+                    .and_then(|i| row.and_then(|row| row.get(i)))
+                    .map(|value| {
+                        value
+                            .as_str()
+                            .map_or_else(|| value.to_string(), str::to_string)
+                    });
+                let (operator, value) = match value.as_deref() {
+                    None | Some("null") => (Operator::IsNull, String::new()),
+                    Some(value) if value.parse::<f64>().is_ok() => {
+                        (Operator::Eq, value.to_string())
+                    }
+                    Some(value) => (Operator::Eq, format!("'{value}'")),
+                };
+                Condition {
+                    operator,
+                    value,
+                    ..Condition::new(column)
+                }
+            })
+            .collect();
+        let Some(mut clause) = (Filter { conditions }).to_where()? else {
+            bail!("an index needs a column");
+        };
+        if let Some(condition) = condition {
+            clause.sql.push_str(&format!(" AND ({condition})"));
+        }
+        let lookup = format!("SELECT * FROM {} WHERE {}", Ident::new(table), clause.sql);
+        Ok(vec![view, (lookup, clause.params)])
+    }
+
+    /// Returns from the review to the form, or closes the editor when the
+    /// form has nothing to edit.
+    pub fn back_schema_editor(&mut self) {
+        self.overlay = match self.take_schema_editor() {
+            Some(SchemaView::Review { form, .. }) if !form.fields().is_empty() => {
+                Some(Overlay::SchemaEditor(SchemaView::Form(form)))
+            }
+            _ => None,
+        };
+    }
+
+    /// Runs the reviewed statements in one transaction and reloads the main
+    /// list.
+    pub fn load_schema_change(
+        &mut self,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        let Some(SchemaView::Review {
+            form, statements, ..
+        }) = self.take_schema_editor()
+        else {
+            return None;
+        };
+        let action = form.action();
+        let db = self.db.clone();
+        let overview = self.load_overview();
+
+        Some(async move {
+            db.execute(&statements).await?;
+            let Update::Overview(tables) = overview.await? else {
+                bail!("unexpected update");
+            };
+            Ok(Update::SchemaChanged {
+                action,
+                statements: statements.len(),
+                tables,
+            })
+        })
+    }
+
+    /// Copies `schema` to a new file at `path` with the online backup API,
+    /// reporting the pages copied so far to `progress`.
+    pub fn load_backup(
+        &self,
+        schema: String,
+        path: String,
+        progress: impl Fn(Update) + Send + Sync + 'static,
+    ) -> impl Future<Output = Result<Update>> + Send + 'static {
+        let db = self.db.clone();
+
+        async move {
+            let pages = db
+                .backup(&schema, &path, |copied, total| {
+                    progress(Update::Status(format!(
+                        "backup of {schema}: {copied} of {total} pages"
+                    )))
+                })
+                .await?;
+            Ok(Update::BackedUp {
+                schema,
+                path,
+                pages,
+            })
+        }
+    }
+
+    /// Polls the database for changes every `interval`, or stops watching.
+    pub fn set_watch(&mut self, interval: Option<Duration>) {
+        self.watch = interval.map(|interval| Watch {
+            interval,
+            version: None,
+            stale: false,
+            updated: None,
+        });
+    }
+
+    pub fn watch_interval(&self) -> Option<Duration> {
+        self.watch.as_ref().map(|watch| watch.interval)
+    }
+
+    /// Time since watch mode last picked up a change.
+    pub fn updated_ago(&self) -> Option<Duration> {
+        Some(self.watch.as_ref()?.updated?.elapsed())
+    }
+
+    pub fn load_version(&self) -> impl Future<Output = Result<Version>> + Send + 'static {
+        let db = self.db.clone();
+
+        async move { db.version().await }
+    }
+
+    /// Records the polled `version` and reloads the current view when the
+    /// database changed since the last poll. The main list, cached while
+    /// another view is shown, is reloaded once it is shown again.
+    pub fn watch_version(
+        &mut self,
+        version: Version,
+    ) -> Option<BoxFuture<'static, Result<Update>>> {
+        let watch = self.watch.as_mut()?;
+        let changed = watch
+            .version
+            .replace(version.clone())
+            .is_some_and(|v| v != version);
+        let main = self.view_state == ViewState::Main;
+        if !(changed || main && watch.stale) {
+            return None;
+        }
+        watch.stale = !main;
+        watch.updated = Some(Instant::now());
+
+        match self.view_state {
+            ViewState::Main => {
+                self.invalidate(None);
+                Some(self.load_overview().boxed())
+            }
+            ViewState::Table => {
+                let table = self.tables.get(self.selected_table_id)?.name().to_string();
+                // Other tables are fetched again when opened, the open one
+                // keeps its rows until the new ones arrive.
+                self.tables
+                    .iter_mut()
+                    .filter(|t| t.name() != table)
+                    .for_each(Table::invalidate);
+                Some(self.load_table(&table).boxed())
+            }
+            // Statements that write are not run again, that would change
+            // the database on every poll.
+            ViewState::Query => {
+                let query = self.query.as_ref().filter(|query| query.read_only)?;
+                Some(self.load_query(&query.sql).boxed())
+            }
+            ViewState::Report => match self.report()? {
+                ReportKind::Info => Some(self.load_report(ReportKind::Info, |_| {}).boxed()),
+                // Checks can take long, they are only rerun on request.
+                ReportKind::Check { .. } | ReportKind::Import { .. } => None,
+            },
+        }
+    }
+
+    /// Drops the version recorded by watch mode after the app wrote to the
+    /// database itself, the next poll records the new one without counting
+    /// it as a change.
+    fn forget_version(&mut self) {
+        if let Some(watch) = self.watch.as_mut() {
+            watch.version = None;
+        }
+    }
+
+    /// Returns to the main list, which is kept cached while browsing tables.
+    pub fn switch_to_main_view(&mut self) {
+        if self.view_state != ViewState::Main {
+            self.column = false;
+            self.marked_columns.clear();
+            self.record = false;
+            self.sort = None;
+            self.active_column = 0;
+            self.view_state = ViewState::Main;
+            let max_items = self.tables.len().saturating_sub(1);
+            self.scroll_state =
+                ScrollbarState::new(Self::calculate_scroll_position(max_items, ITEM_HEIGHT));
+            self.select(self.selected_table_id);
+        }
+    }
+
+    /// Applies data loaded in the background.
+    pub fn apply(&mut self, update: Update) {
+        match update {
+            Update::Overview(mut tables) => {
+                // Cached rows survive an overview refresh unless invalidated.
+                for table in &mut tables {
+                    if let Some(cached) = self.tables.iter_mut().find(|t| t.name() == table.name())
+                    {
+                        table.contents = cached.contents.take();
+                    }
+                }
+                let selected = self.tables.get(self.selected_table_id).map(Table::name);
+                self.selected_table_id = selected
+                    .and_then(|name| tables.iter().position(|table| table.name() == name))
+                    .unwrap_or(0);
+                self.tables = tables;
+                self.sync_saved_queries();
+                self.select(self.state.selected().unwrap_or(0));
+            }
+            Update::TableView { table, contents } => {
+                let Some(table_id) = self.table_id(&table) else {
+                    return;
+                };
+                self.tables[table_id].set_contents(contents);
+
+                if self.view_state == ViewState::Table && self.selected_table_id == table_id {
+                    // Refreshing the open table keeps the position.
+                    self.sort_rows();
+                    self.set_active_column(self.active_column);
+                    self.select(self.state.selected().unwrap_or(0));
+                } else if self.view_state == ViewState::Main {
+                    self.show_table(table_id);
+                }
+            }
+            Update::Query {
+                sql,
+                contents,
+                read_only,
+            } => {
+                if !read_only {
+                    self.forget_version();
+                }
+                // Running the shown statement again keeps the position.
+                let rerun = self.view_state == ViewState::Query && self.query() == Some(&sql);
+                let group = match self.query.take() {
+                    Some(query) if rerun => query.group,
+                    _ => None,
+                };
+                self.query = Some(QueryResult {
+                    sql,
+                    contents,
+                    read_only,
+                    group,
+                });
+                if rerun {
+                    self.sort_rows();
+                    self.set_active_column(self.active_column);
+                    self.select(self.state.selected().unwrap_or(0));
+                } else {
+                    self.show_contents(ViewState::Query);
+                }
+            }
+            Update::Grouped { grouping, contents } => {
+                self.query = Some(QueryResult {
+                    sql: grouping.sql(),
+                    contents,
+                    read_only: true,
+                    group: Some(*grouping),
+                });
+                self.show_contents(ViewState::Query);
+            }
+            Update::Report(report) => {
+                self.report = Some(report);
+                self.show_contents(ViewState::Report);
+            }
+            Update::ReportLine(problem) => {
+                let Some(report) = self.report.as_mut() else {
+                    return;
+                };
+                let rows = &mut report.contents.rows;
+                rows.push(vec![(rows.len() + 1).to_string().into(), problem.into()]);
+                if self.view_state == ViewState::Report {
+                    let max_items = self.item_count().saturating_sub(1);
+                    self.scroll_state = self
+                        .scroll_state
+                        .content_length(Self::calculate_scroll_position(max_items, ITEM_HEIGHT));
+                }
+            }
+            Update::Checked { check, problems } => {
+                if problems == 0 {
+                    self.apply(Update::ReportLine("ok".to_string()));
+                    self.set_message(format!("{}: ok", check.pragma()));
+                } else {
+                    self.set_message(format!("{}: {problems} problems", check.pragma()));
+                }
+            }
+            Update::ImportPreview(pending) => {
+                let preview = pending.import.preview(IMPORT_PREVIEW_ROWS);
+                self.report = Some(Report {
+                    kind: ReportKind::Import {
+                        path: pending.path.clone(),
+                        table: pending.table.clone(),
+                    },
+                    contents: Contents::new(preview.columns, preview.rows),
+                });
+                self.import = Some(*pending);
+                self.show_contents(ViewState::Report);
+            }
+            Update::Imported {
+                table,
+                rows,
+                created,
+                tables,
+            } => {
+                self.forget_version();
+                self.invalidate(Some(&table));
+                self.switch_to_main_view();
+                self.apply(Update::Overview(tables));
+                if let Some(table_id) = self.table_id(&table) {
+                    self.select(table_id);
+                }
+                let action = if created { "created" } else { "appended to" };
+                self.set_message(format!("imported {rows} rows, {action} {table}"));
+            }
+            Update::SchemaEditor(table) => {
+                self.set_schema_editor(SchemaView::Menu { table, selected: 0 })
+            }
+            Update::IndexForm { schema, columns } => {
+                let form = SchemaForm::create_index(*schema, &columns);
+                self.set_schema_editor(SchemaView::Form(Box::new(form)));
+            }
+            Update::GeneratedColumnForm {
+                schema,
+                name,
+                expression,
+            } => {
+                let form = SchemaForm::generated_column(*schema, &name, &expression);
+                self.set_schema_editor(SchemaView::Form(Box::new(form)));
+            }
+            Update::IndexPlans(changes) => {
+                if let Some(SchemaView::Review { plans, .. }) = self.schema_editor_mut() {
+                    *plans = changes;
+                }
+            }
+            Update::SchemaChanged {
+                action,
+                statements,
+                tables,
+            } => {
+                self.forget_version();
+                self.invalidate(None);
+                self.switch_to_main_view();
+                self.apply(Update::Overview(tables));
+                self.set_message(format!("{}: ran {statements} statements", action.name()));
+            }
+            Update::Status(status) => self.status = Some(status),
+            Update::BackedUp {
+                schema,
+                path,
+                pages,
+            } => self.set_message(format!("backed up {pages} pages of {schema} to {path}")),
+            Update::Maintained(result) => {
+                // VACUUM may renumber rows without an INTEGER PRIMARY KEY.
+                self.forget_version();
+                self.invalidate(None);
+                self.overlay = Some(Overlay::Maintenance(MaintenanceView::Done(result)));
+            }
+            Update::ColumnStats(stats) => self.overlay = Some(Overlay::ColumnStats(stats)),
+            Update::Json(view) => self.overlay = Some(Overlay::Json(view)),
+            Update::Plan(plan) => {
+                self.overlay = Some(Overlay::Plan(PlanView {
+                    plan,
+                    bytecode: false,
+                    scroll: 0,
+                }));
+            }
+        }
+    }
+
+    /// Index of the database table `name`, skipping saved queries.
+    fn table_id(&self, name: &str) -> Option<usize> {
+        self.tables
+            .iter()
+            .position(|t| t.saved_query.is_none() && t.name() == name)
+    }
+
+    pub fn set_history(&mut self, history: History) {
+        self.history = history;
+        self.sync_saved_queries();
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+    }
+
+    /// Lists the saved queries after the tables in the main list.
+    fn sync_saved_queries(&mut self) {
+        self.tables.retain(|table| table.saved_query.is_none());
+        let first_id = self.tables.len() + 1;
+        let saved = self.history.saved_queries().iter().enumerate();
+        self.tables
+            .extend(saved.map(|(i, query)| Table::saved(first_id + i, query)));
+    }
+
+    /// Saves the statement shown in query view as `name`.
+    pub fn save_query(&mut self, name: &str) -> Result<()> {
+        let Some(sql) = self.query().map(str::to_string) else {
+            bail!("no query to save, run one first");
+        };
+        self.history.save_query(name, &sql)?;
+        self.sync_saved_queries();
+        Ok(())
+    }
+
+    pub fn remove_query(&mut self, name: &str) -> Result<()> {
+        if !self.history.remove_query(name)? {
+            bail!("no saved query: {name}");
+        }
+        self.sync_saved_queries();
+        self.select(self.state.selected().unwrap_or(0));
+        Ok(())
+    }
+
+    pub fn overlay(&self) -> Option<&Overlay> {
+        self.overlay.as_ref()
+    }
+
+    /// Query editor, also while the history picker is shown over it.
+    pub fn editor(&self) -> Option<&Editor> {
+        match &self.overlay {
+            Some(Overlay::Editor { editor, .. } | Overlay::Picker { editor, .. }) => Some(editor),
+            _ => None,
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> Option<&mut Editor> {
+        match &mut self.overlay {
+            Some(Overlay::Editor { editor, .. }) => Some(editor),
+            _ => None,
+        }
+    }
+
+    /// Opens the query editor, prefilled with the statement shown in query
+    /// view.
+    pub fn open_editor(&mut self) {
+        self.overlay = Some(Overlay::Editor {
+            editor: Editor::new(self.query().unwrap_or_default()),
+            completion: None,
+        });
+    }
+
+    pub fn close_editor(&mut self) {
+        self.overlay = None;
+    }
+
+    /// Closes the editor and returns its statement, recording it in the
+    /// history.
+    pub fn submit_editor(&mut self) -> Option<String> {
+        let Some(Overlay::Editor { editor, .. }) = self.overlay.take() else {
+            return None;
+        };
+        let sql = editor.text().trim().to_string();
+        if sql.is_empty() {
+            return None;
+        }
+        if let Err(err) = self.history.push(&sql) {
+            self.set_message(format!("failed to save history: {err}"));
+        }
+        Some(sql)
+    }
+
+    pub fn recall_previous(&mut self) {
+        if let Some(Overlay::Editor { editor, .. }) = &mut self.overlay {
+            editor.recall_previous(self.history.entries());
+        }
+    }
+
+    pub fn recall_next(&mut self) {
+        if let Some(Overlay::Editor { editor, .. }) = &mut self.overlay {
+            editor.recall_next(self.history.entries());
+        }
+    }
+
+    pub fn completion(&self) -> Option<&Completion> {
+        match &self.overlay {
+            Some(Overlay::Editor { completion, .. }) => completion.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Completion popup of the open editor, to open or close it.
+    fn completion_mut(&mut self) -> Option<&mut Option<Completion>> {
+        match &mut self.overlay {
+            Some(Overlay::Editor { completion, .. }) => Some(completion),
+            _ => None,
+        }
+    }
+
+    /// Completes the word under the editor cursor, right away when there is
+    /// a single candidate and through the completion popup otherwise.
+    pub fn open_completion(&mut self) {
+        let completion = self.completions();
+        let single = completion.items.len() == 1;
+        if let Some(slot) = self.completion_mut() {
+            *slot = (!completion.items.is_empty()).then_some(completion);
+        }
+        if single {
+            self.accept_completion();
+        }
+    }
+
+    /// Refreshes the candidates of an open popup after the text changed.
+    pub fn update_completion(&mut self) {
+        if self.completion().is_some() {
+            let completion = self.completions();
+            if let Some(slot) = self.completion_mut() {
+                *slot = (!completion.items.is_empty()).then_some(completion);
+            }
+        }
+    }
+
+    pub fn close_completion(&mut self) {
+        if let Some(slot) = self.completion_mut() {
+            *slot = None;
+        }
+    }
+
+    pub fn completion_next(&mut self) {
+        if let Some(Some(completion)) = self.completion_mut() {
+            completion.selected = (completion.selected + 1) % completion.items.len();
+        }
+    }
+
+    pub fn completion_previous(&mut self) {
+        if let Some(Some(completion)) = self.completion_mut() {
+            let len = completion.items.len();
+            completion.selected = (completion.selected + len - 1) % len;
+        }
+    }
+
+    /// Replaces the word under the cursor with the selected candidate.
+    pub fn accept_completion(&mut self) {
+        let Some(Overlay::Editor { editor, completion }) = &mut self.overlay else {
+            return;
+        };
+        let Some(completion) = completion.take() else {
+            return;
+        };
+        if let Some(item) = completion.items.get(completion.selected) {
+            editor.complete(completion.prefix, item);
+        }
+    }
+
+    /// Candidates for the word left of the editor cursor: tables after
+    /// `FROM` and the like, otherwise columns of the tables the statement
+    /// refers to, followed by table names and keywords.
+    fn completions(&self) -> Completion {
+        let Some(editor) = self.editor() else {
+            return Completion::default();
+        };
+        let (context, prefix) = sql::context(editor.before_cursor());
+        let tables = || self.tables.iter().filter(|t| t.saved_query.is_none());
+        let table = |name: &str| tables().find(|t| t.name().eq_ignore_ascii_case(name));
+
+        let mut names: Vec<&str> = Vec::new();
+        let mut keywords = Vec::new();
+        match &context {
+            sql::Context::Table => names.extend(tables().map(Table::name)),
+            sql::Context::Column(qualifier) => {
+                let referenced = sql::referenced_tables(editor.text());
+                let mut sources: Vec<&str> = referenced
+                    .iter()
+                    .filter(|(name, alias)| {
+                        qualifier
+                            .as_ref()
+                            .is_none_or(|q| alias.as_ref().unwrap_or(name).eq_ignore_ascii_case(q))
+                    })
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                if let (Some(qualifier), true) = (qualifier, sources.is_empty()) {
+                    sources.push(qualifier);
+                }
+                names.extend(
+                    sources
+                        .into_iter()
+                        .filter_map(table)
+                        .flat_map(|t| t.columns().iter().map(String::as_str)),
+                );
+                if qualifier.is_none() {
+                    names.extend(tables().map(Table::name));
+                    keywords.extend(sql::keywords(prefix));
+                }
+            }
+        }
+
+        let mut items: Vec<String> = Vec::new();
+        let candidates = names
+            .into_iter()
+            .filter(|name| sql::starts_with_ignore_case(name, prefix))
+            .map(sql::quote_identifier)
+            .chain(keywords.into_iter().map(str::to_string));
+        for item in candidates {
+            if !items.contains(&item) {
+                items.push(item);
+            }
+        }
+        Completion {
+            items,
+            selected: 0,
+            prefix: prefix.chars().count(),
+        }
+    }
+
+    pub fn picker(&self) -> Option<&HistoryPicker> {
+        match &self.overlay {
+            Some(Overlay::Picker { picker, .. }) => Some(picker),
+            _ => None,
+        }
+    }
+
+    fn picker_mut(&mut self) -> Option<&mut HistoryPicker> {
+        match &mut self.overlay {
+            Some(Overlay::Picker { picker, .. }) => Some(picker),
+            _ => None,
+        }
+    }
+
+    /// Opens the history picker over the editor.
+    pub fn open_picker(&mut self) {
+        self.overlay = match self.overlay.take() {
+            Some(Overlay::Editor { editor, .. }) => Some(Overlay::Picker {
+                picker: HistoryPicker::default(),
+                editor,
+            }),
+            overlay => overlay,
+        };
+    }
+
+    /// Closes the picker, showing the editor again.
+    pub fn close_picker(&mut self) {
+        self.close_picker_with(None);
+    }
+
+    /// Closes the picker, replacing the editor's statement with `sql`.
+    fn close_picker_with(&mut self, sql: Option<String>) {
+        self.overlay = match self.overlay.take() {
+            Some(Overlay::Picker { editor, .. }) => Some(Overlay::Editor {
+                editor: sql.map_or(editor, |sql| Editor::new(&sql)),
+                completion: None,
+            }),
+            overlay => overlay,
+        };
+    }
+
+    /// History entries matching the picker filter.
+    pub fn picker_matches(&self) -> Vec<&str> {
+        self.picker()
+            .map_or_else(Vec::new, |picker| self.history.search(&picker.filter))
+    }
+
+    pub fn push_picker(&mut self, c: char) {
+        if let Some(picker) = self.picker_mut() {
+            picker.filter.push(c);
+            picker.selected = 0;
+        }
+    }
+
+    pub fn pop_picker(&mut self) {
+        if let Some(picker) = self.picker_mut() {
+            picker.filter.pop();
+            picker.selected = 0;
+        }
+    }
+
+    pub fn picker_next(&mut self) {
+        let len = self.picker_matches().len();
+        if let Some(picker) = self.picker_mut() {
+            picker.selected = (picker.selected + 1).min(len.saturating_sub(1));
+        }
+    }
+
+    pub fn picker_previous(&mut self) {
+        if let Some(picker) = self.picker_mut() {
+            picker.selected = picker.selected.saturating_sub(1);
+        }
+    }
+
+    /// Closes the picker and opens the editor with the chosen statement.
+    pub fn choose_picker(&mut self) {
+        let selected = self.picker().map_or(0, HistoryPicker::selected);
+        let sql = self
+            .picker_matches()
+            .get(selected)
+            .map(|sql| sql.to_string());
+        self.close_picker_with(sql);
+    }
+
+    /// Switches to `view_state`, whose rows were just loaded, starting at the
+    /// first row.
+    fn show_contents(&mut self, view_state: ViewState) {
+        self.schema = false;
+        self.column = false;
+        self.marked_columns.clear();
+        self.record = false;
+        self.sort = None;
+        self.active_column = 0;
+        self.state = TableState::default().with_selected(0);
+        self.view_state = view_state;
+        let max_items = self.item_count().saturating_sub(1);
+        self.scroll_state =
+            ScrollbarState::new(Self::calculate_scroll_position(max_items, ITEM_HEIGHT));
+    }
+
+    /// Switches the main list between the plain and the disk usage column
+    /// sets. The overview has to be reloaded with [`Model::load_overview`]
+    /// to fill the new columns.
+    pub fn toggle_storage(&mut self) {
+        if self.view_state == ViewState::Main {
+            self.storage = !self.storage;
+            self.columns = Self::columns(self.storage);
+            self.active_column = 0;
+            self.sort = None;
+        }
+    }
+
+    /// Loads the report `kind`. Checks first show an empty report and then
+    /// hand each problem to `progress` as it is found.
+    pub fn load_report(
+        &self,
+        kind: ReportKind,
+        progress: impl Fn(Update) + Send + Sync + 'static,
+    ) -> impl Future<Output = Result<Update>> + Send + 'static {
+        let db = self.db.clone();
+
+        async move {
+            match kind {
+                ReportKind::Info => {
+                    let info = db.info().await?.to_result_set();
+                    let contents = Contents::new(info.columns, info.rows);
+                    Ok(Update::Report(Report { kind, contents }))
+                }
+                ReportKind::Check { check, ref table } => {
+                    let columns = vec!["#".to_string(), "problem".to_string()];
+                    progress(Update::Report(Report {
+                        kind: kind.clone(),
+                        contents: Contents::new(columns, Vec::new()),
+                    }));
+                    let problems = db
+                        .check(check, table.as_deref(), |problem| {
+                            progress(Update::ReportLine(problem))
+                        })
+                        .await?;
+                    Ok(Update::Checked { check, problems })
+                }
+                ReportKind::Import { path, table } => {
+                    let file = path.clone();
+                    let options = import::Options::default();
+                    let import = tokio::task::spawn_blocking(move || Import::read(&file, &options))
+                        .await??;
+                    let append = db.tables().await?.contains(&table);
+                    Ok(Update::ImportPreview(Box::new(PendingImport {
+                        path,
+                        table,
+                        append,
+                        import,
+                    })))
+                }
+            }
+        }
+    }
+
+    /// Writes the previewed import, creating or appending to its table,
+    /// and reloads the main list.
+    pub fn load_import_write(
+        &mut self,
+    ) -> Option<impl Future<Output = Result<Update>> + Send + 'static> {
+        if !matches!(self.report(), Some(ReportKind::Import { .. })) {
+            return None;
+        }
+        let pending = self.import.take()?;
+        let db = self.db.clone();
+        let overview = self.load_overview();
+
+        Some(async move {
+            let (rows, created) = pending.import.write(&db, &pending.table).await?;
+            let Update::Overview(tables) = overview.await? else {
+                bail!("unexpected update");
+            };
+            Ok(Update::Imported {
+                table: pending.table,
+                rows,
+                created,
+                tables,
+            })
+        })
+    }
+
+    /// Kind of the report shown in report view.
+    pub fn report(&self) -> Option<&ReportKind> {
+        match self.view_state {
+            ViewState::Report => self.report.as_ref().map(|report| &report.kind),
+            _ => None,
+        }
+    }
+
+    /// Drops cached rows of `table`, or of every table when `None`.
+    pub fn invalidate(&mut self, table: Option<&str>) {
+        self.tables
+            .iter_mut()
+            .filter(|t| table.is_none_or(|name| t.name() == name))
+            .for_each(Table::invalidate);
+    }
+
+    /// Name of the table open in table view.
+    pub fn selected_table(&self) -> Option<&str> {
+        match self.view_state {
+            ViewState::Main | ViewState::Query | ViewState::Report => None,
+            ViewState::Table => self.tables.get(self.selected_table_id).map(Table::name),
+        }
+    }
+
+    fn show_table(&mut self, table_id: usize) {
+        self.schema = false;
+        self.column = false;
+        self.marked_columns.clear();
+        self.record = false;
+        self.sort = None;
+        self.active_column = 0;
+        self.selected_table_id = table_id;
+        self.state = TableState::default().with_selected(0);
+        self.view_state = ViewState::Table;
+
+        if let Some(selected_table) = self.tables.get(self.selected_table_id) {
+            let max_items = selected_table.rows().len().saturating_sub(1);
+            self.scroll_state =
+                ScrollbarState::new(Self::calculate_scroll_position(max_items, ITEM_HEIGHT));
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
+    /// Time since the running background load was started.
+    pub fn loading_elapsed(&self) -> Option<Duration> {
+        self.loading.map(|since| since.elapsed())
+    }
+
+    pub fn set_loading(&mut self, loading: bool) {
+        self.loading = loading.then(Instant::now);
+        self.status = None;
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    pub fn table_schema(&self) -> Option<&str> {
+        self.tables
+            .get(self.state.selected().unwrap_or(0))
+            .map(Table::schema)
+    }
+
+    pub fn get_table_columns(&self) -> &[String] {
+        match self.view_state {
+            ViewState::Main => &self.columns,
+            ViewState::Table | ViewState::Query | ViewState::Report => self
+                .contents()
+                .map_or(&[], |contents| contents.columns.as_slice()),
+        }
+    }
+
+    pub fn get_table_rows(&self) -> Vec<&[Value]> {
+        self.contents().map_or_else(
+            || vec![&[] as &[Value]],
+            |contents| contents.rows.iter().map(Vec::as_slice).collect(),
+        )
+    }
+
+    pub fn longest_in_column(&self) -> u16 {
+        let selected = self.state().selected().unwrap_or(0);
+        if let Some(column_name) = self.get_table_columns().get(self.active_column) {
+            let header_len = column_name.as_str().len();
+
+            let formats = self.time_formats();
+            let cell_len = self
+                .current_rows()
+                .get(selected)
+                .and_then(|row| row.get(self.active_column))
+                .and_then(|value| value.as_str())
+                .map_or(0, |text| {
+                    self.cell_text(&formats, self.active_column, text).len()
+                });
+
+            return u16::try_from(header_len.max(cell_len)).unwrap_or(0);
+        }
+        0
+    }
+
+    pub fn view_state(&self) -> ViewState {
+        self.view_state.clone()
+    }
+
+    pub fn colors(&self) -> &TableColors {
+        &self.colors
+    }
+
+    pub fn state(&self) -> &TableState {
+        &self.state
+    }
+
+    pub fn scroll_state(&self) -> &ScrollbarState {
+        &self.scroll_state
+    }
+
+    pub fn is_schema_enabled(&self) -> bool {
+        self.schema
+    }
+
+    pub fn toggle_schema(&mut self) {
+        if self.view_state == ViewState::Main {
+            self.schema = !self.schema;
+        }
+    }
+
+    pub fn is_column_enabled(&self) -> bool {
+        self.column
+    }
+
+    pub fn toggle_column(&mut self) {
+        self.column = !self.column;
+        self.marked_columns.clear();
+    }
+
+    /// Marks or unmarks the active column for a multi-column index.
+    pub fn toggle_mark_column(&mut self) {
+        if !self.column || self.view_state != ViewState::Table {
+            return;
+        }
+        match self
+            .marked_columns
+            .iter()
+            .position(|&c| c == self.active_column)
+        {
+            Some(i) => {
+                self.marked_columns.remove(i);
+            }
+            None => self.marked_columns.push(self.active_column),
+        }
+    }
+
+    /// Columns marked for an index, in the order they were marked.
+    pub fn marked_columns(&self) -> &[usize] {
+        &self.marked_columns
+    }
+
+    pub fn active_column(&self) -> usize {
+        self.active_column
+    }
+
+    pub fn set_active_column(&mut self, column: usize) {
+        if column < self.get_table_columns().len() {
+            self.active_column = column;
+        }
+    }
+
+    pub fn is_record_enabled(&self) -> bool {
+        self.record
+    }
+
+    pub fn toggle_record(&mut self) {
+        if self.view_state != ViewState::Main {
+            self.record = !self.record;
+        }
+    }
+
+    /// Column name and value pairs of the selected row in table or query
+    /// view.
+    pub fn record(&self) -> Vec<(&str, &str)> {
+        let selected = self.state.selected().unwrap_or(0);
+        let Some(contents) = self.contents() else {
+            return Vec::new();
+        };
+        let Some(row) = contents.rows.get(selected) else {
+            return Vec::new();
+        };
+        contents
+            .columns
+            .iter()
+            .zip(row)
+            .map(|(column, value)| (column.as_str(), value.as_str().unwrap_or("NULL")))
+            .collect()
+    }
+
+    /// Interrupts the statement running on the database.
+    pub fn interrupt(&self) {
+        self.db.interrupt();
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    pub fn set_time_zone(&mut self, zone: Option<Zone>) {
+        if zone == Some(Zone::Local) {
+            if let Err(err) = datetime::local_time_zone() {
+                self.times = Some(Zone::Utc);
+                self.set_message(format!(
+                    "unknown local time zone, timestamps shown in UTC: {err}"
+                ));
+                return;
+            }
+        }
+        self.times = zone;
+        let shown = match zone {
+            Some(Zone::Local) => "in local time",
+            Some(Zone::Utc) => "in UTC",
+            None => "as stored",
+        };
+        self.set_message(format!("timestamps shown {shown}"));
+    }
+
+    /// Shows timestamps in local time, then UTC, then as stored.
+    pub fn cycle_time_zone(&mut self) {
+        self.set_time_zone(match self.times {
+            Some(Zone::Local) => Some(Zone::Utc),
+            Some(Zone::Utc) => None,
+            None => Some(Zone::Local),
+        });
+    }
+
+    /// Time format of each shown column: the one set for the column of the
+    /// open table, or else the one detected when it was loaded. `None` for
+    /// columns without timestamps.
+    pub fn time_formats(&self) -> Vec<Option<TimeFormat>> {
+        if !matches!(self.view_state, ViewState::Table | ViewState::Query) {
+            return Vec::new();
+        }
+        let Some(contents) = self.contents() else {
+            return Vec::new();
+        };
+        let table = self.browsed_table();
+        contents
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let set = table.and_then(|table| self.settings.time_format(table, column));
+                let format = set.or_else(|| contents.times.get(i).copied().flatten())?;
+                (format != TimeFormat::Raw).then_some(format)
+            })
+            .collect()
+    }
+
+    /// Text of the cell `text` in `column` as shown, a timestamp rendered in
+    /// the chosen zone when `formats` has a format for the column.
+    pub fn cell_text<'a>(
+        &self,
+        formats: &[Option<TimeFormat>],
+        column: usize,
+        text: &'a str,
+    ) -> Cow<'a, str> {
+        let rendered = self.times.and_then(|zone| {
+            let format = formats.get(column).copied().flatten()?;
+            format.render(text, zone)
+        });
+        rendered.map_or(Cow::Borrowed(text), Cow::Owned)
+    }
+
+    /// Sets the time format of `column` of the open table for this and later
+    /// sessions, detecting it again for `None`.
+    pub fn set_time_format(&mut self, column: &str, format: Option<TimeFormat>) -> Result<()> {
+        let Some(table) = self.browsed_table().map(String::from) else {
+            bail!("time formats are set for the columns of an open table");
+        };
+        if !self.get_table_columns().iter().any(|c| c == column) {
+            bail!("{table} has no column {column}");
+        }
+        self.settings.save_time_format(&table, column, format)?;
+        let format = format.map_or("detected".to_string(), |format| format.to_string());
+        self.set_message(format!("time format of {table}.{column}: {format}"));
+        Ok(())
+    }
+
+    /// Number of rows moved by page up/down, derived from the rendered area.
+    pub fn set_page_size(&mut self, page_size: usize) {
+        self.page_size = page_size.max(1);
+    }
+
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    pub fn open_prompt(&mut self) {
+        self.prompt = Some(String::new());
+    }
+
+    pub fn close_prompt(&mut self) {
+        self.prompt = None;
+    }
+
+    /// Closes the prompt and returns what was typed into it.
+    pub fn take_prompt(&mut self) -> Option<String> {
+        self.prompt.take()
+    }
+
+    pub fn push_prompt(&mut self, c: char) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            prompt.push(c);
+        }
+    }
+
+    pub fn pop_prompt(&mut self) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            prompt.pop();
+        }
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message = None;
+    }
+
+    pub fn sort(&self) -> Option<(usize, SortOrder)> {
+        self.sort
+    }
+
+    /// Sorts the current view by `column`, toggling between ascending and
+    /// descending order when the same column is sorted again.
+    pub fn sort_by_column(&mut self, column: usize) {
+        if column >= self.get_table_columns().len() {
+            return;
+        }
+        let order = match self.sort {
+            Some((current, SortOrder::Ascending)) if current == column => SortOrder::Descending,
+            _ => SortOrder::Ascending,
+        };
+        self.sort = Some((column, order));
+        self.sort_rows();
+        self.select(0);
+    }
+
+    fn sort_rows(&mut self) {
+        let Some((column, order)) = self.sort else {
+            return;
+        };
+        let compare = |a: &[Value], b: &[Value]| {
+            let ordering = compare_values(a.get(column), b.get(column));
+            match order {
+                SortOrder::Ascending => ordering,
+                SortOrder::Descending => ordering.reverse(),
+            }
+        };
+        match self.view_state {
+            ViewState::Main => self
+                .tables
+                .sort_by(|a, b| compare(a.overview(), b.overview())),
+            ViewState::Table | ViewState::Query | ViewState::Report => {
+                if let Some(contents) = self.contents_mut() {
+                    contents.rows.sort_by(|a, b| compare(a, b));
+                }
+            }
+        }
+    }
+
+    pub fn next_column(&mut self) {
+        if self.is_column_enabled() {
+            self.active_column = (self.active_column + 1) % self.get_table_columns().len().max(1);
+        }
+    }
+
+    pub fn previous_column(&mut self) {
+        if self.is_column_enabled() {
+            self.active_column = if self.active_column == 0 {
+                self.get_table_columns().len().saturating_sub(1)
+            } else {
+                self.active_column - 1
+            };
+        }
+    }
+
+    pub fn get_info_text(&self) -> String {
+        let mut result =
+            String::from("(Esc) quit | (↑) move up | (↓) move down | (PgUp/PgDn) page | (:) go to row | (e) edit query | (⇧ S) toggle column select");
+        match self.view_state {
+            ViewState::Main => {
+                result.push_str(
+                    " | (Space) toggle schema (→) table view | (i) database info | (d) disk usage",
+                );
+            }
+            ViewState::Table | ViewState::Query | ViewState::Report => {
+                result.push_str(" | (←) main view");
+                if self.view_state == ViewState::Table {
+                    result.push_str(" | (f) filter");
+                }
+                if self.time_formats().iter().any(Option::is_some) {
+                    let zone = match self.times {
+                        Some(Zone::Local) => "local",
+                        Some(Zone::Utc) => "UTC",
+                        None => "stored",
+                    };
+                    result.push_str(&format!(" | (⇧ T) times: {zone}"));
+                }
+                match self.grouping() {
+                    Some(grouping) => {
+                        result.push_str(" | (Enter) rows of group");
+                        match (grouping.next_pivot(), grouping.pivot()) {
+                            (Some(column), _) => {
+                                result.push_str(&format!(" | (v) pivot on {column}"))
+                            }
+                            (None, Some(_)) => result.push_str(" | (v) remove pivot"),
+                            (None, None) => {}
+                        }
+                    }
+                    None => result.push_str(" | (Enter) toggle record"),
+                }
+            }
+        }
+        if let (Some(ReportKind::Import { .. }), Some(pending)) = (self.report(), &self.import) {
+            let target = if pending.append {
+                "append to"
+            } else {
+                "create"
+            };
+            result.push_str(&format!(
+                " | (w) {target} {} with {} rows",
+                pending.table,
+                pending.import.rows.len()
+            ));
+        }
+
+        if self.is_column_enabled() {
+            result.push_str(" | (⇧ ←) previous column | (⇧ →) next column");
+            if self.view_state != ViewState::Main {
+                result.push_str(" | (o) view JSON");
+            }
+            if matches!(self.view_state, ViewState::Table | ViewState::Query) {
+                result.push_str(" | (a) group by column");
+            }
+            if self.view_state == ViewState::Table {
+                result.push_str(" | (p) column stats | (x) mark column | (⇧ I) create index");
+            }
+        }
+
+        result
+    }
+
+    /// Columns of the main list, with the disk usage columns when `storage`
+    /// is set.
+    fn columns(storage: bool) -> Vec<String> {
+        let mut columns = vec!["#", "Table", "Columns", "Rows"];
+        if storage {
+            columns.extend([
+                "Pages",
+                "Bytes",
+                "Payload",
+                "Unused",
+                "Frag %",
+                "Index bytes",
+            ]);
+        }
+        columns.into_iter().map(String::from).collect()
+    }
+
+    async fn rows(
+        id: usize,
+        table: &str,
+        columns: &[String],
+        storage: Option<&[Storage]>,
+        db: &D,
+    ) -> Result<Vec<Value>> {
+        let len = db.count(table).await?;
+
+        let mut row = vec![
+            Value::from(id.to_string()),
+            Value::from(table.to_string()),
+            Value::from(columns.len().to_string()),
+            Value::from(len.to_string()),
+        ];
+        if let Some(storage) = storage {
+            let own = storage
+                .iter()
+                .find(|s| !s.index && s.name == table)
+                .cloned()
+                .unwrap_or_default();
+            let index_bytes: u64 = storage
+                .iter()
+                .filter(|s| s.index && s.table == table)
+                .map(|s| s.bytes)
+                .sum();
+            row.extend(
+                [
+                    own.pages.to_string(),
+                    own.bytes.to_string(),
+                    own.payload.to_string(),
+                    own.unused.to_string(),
+                    format!("{:.1}", own.fragmentation()),
+                    index_bytes.to_string(),
+                ]
+                .map(Value::from),
+            );
+        }
+        Ok(row)
+    }
+
+    fn calculate_scroll_position(index: usize, item_height: u16) -> usize {
         index.saturating_mul(item_height as usize)
     }
 }
 
+/// Compares two cells numerically when both parse as numbers, and as text
+/// otherwise.
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> std::cmp::Ordering {
+    let a = a.and_then(Value::as_str).unwrap_or_default();
+    let b = b.and_then(Value::as_str).unwrap_or_default();
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::{ColumnInfo, DatabaseInfo, JsonNode, SqliteDb, Version};
+    use std::sync::{Arc, Mutex};
 
     #[derive(Clone)]
-    struct MockDb;
+    struct MockDb {
+        read_only: bool,
+    }
 
     impl MockDb {
         fn new() -> Self {
-            MockDb
+            MockDb { read_only: false }
         }
     }
     impl Database for MockDb {
@@ -421,16 +2861,227 @@ mod tests {
             Ok(vec!["test".into(), "test2".into()])
         }
 
-        async fn schema(&self, _table: &str) -> Result<String> {
-            Ok("".into())
+        async fn schema(&self, _table: &str) -> Result<String> {
+            Ok("".into())
+        }
+
+        async fn columns(&self, _table: &str) -> Result<Vec<String>> {
+            Ok(vec!["id".into()])
+        }
+
+        async fn column_types(&self, _table: &str) -> Result<Vec<String>> {
+            Ok(vec!["INTEGER".into()])
+        }
+
+        async fn rows(&self, _: &str, filter: Option<&WhereClause>) -> Result<ResultSet> {
+            let rows = match filter {
+                Some(_) => vec![vec![2.into()]],
+                None => vec![vec![1.into()], vec![2.into()], vec![3.into()]],
+            };
+            Ok(ResultSet {
+                rows,
+                ..ResultSet::default()
+            })
+        }
+
+        async fn table_schema(&self, table: &str) -> Result<TableSchema> {
+            Ok(TableSchema {
+                name: table.into(),
+                sql: format!("CREATE TABLE {table} (id INTEGER)"),
+                columns: vec![ColumnInfo {
+                    name: "id".into(),
+                    kind: "INTEGER".into(),
+                    ..ColumnInfo::default()
+                }],
+                ..TableSchema::default()
+            })
+        }
+
+        async fn count(&self, table: &str) -> Result<usize> {
+            Ok(self.rows(table, None).await?.rows.len())
+        }
+
+        async fn position(&self, _: &str, _: &[Value], _: &str) -> Result<Option<usize>> {
+            Ok(Some(2))
+        }
+
+        async fn head(&self, _: &str, _: usize) -> Result<ResultSet> {
+            Ok(ResultSet::default())
+        }
+
+        async fn query_values(&self, _: &str) -> Result<ResultSet> {
+            Ok(ResultSet::default())
+        }
+
+        async fn query(&self, sql: &str) -> Result<ResultSet> {
+            let rows = |rows: [[&str; 2]; 2]| -> Vec<Vec<Value>> {
+                rows.iter()
+                    .map(|row| row.map(Value::from).to_vec())
+                    .collect()
+            };
+            if sql.contains("quote(") {
+                return Ok(ResultSet {
+                    columns: vec!["id".into(), "quote".into()],
+                    rows: rows([["1", "1"], ["2", "2"]]),
+                    stats: None,
+                });
+            }
+            if sql.contains("GROUP BY") {
+                return Ok(ResultSet {
+                    columns: vec!["id".into(), "count".into()],
+                    rows: rows([["2", "3"], ["1", "1"]]),
+                    stats: None,
+                });
+            }
+            Ok(ResultSet::default())
+        }
+
+        async fn reads_only(&self, sql: &str) -> Result<bool> {
+            Ok(sql.starts_with("SELECT"))
+        }
+
+        fn interrupt(&self) {}
+
+        fn stats(&self) -> Vec<QueryStats> {
+            Vec::new()
+        }
+
+        async fn info(&self) -> Result<DatabaseInfo> {
+            Ok(DatabaseInfo {
+                path: "/".into(),
+                journal_mode: "wal".into(),
+                ..DatabaseInfo::default()
+            })
+        }
+
+        async fn column_stats(&self, table: &str, column: &str) -> Result<ColumnStats> {
+            Ok(ColumnStats {
+                table: table.to_string(),
+                column: column.to_string(),
+                count: 2,
+                distinct: 2,
+                ..ColumnStats::default()
+            })
+        }
+
+        async fn json_tree(&self, json: &str) -> Result<Vec<JsonNode>> {
+            let value: Value = serde_json::from_str(json)?;
+            Ok(vec![JsonNode {
+                id: 0,
+                parent: None,
+                key: None,
+                kind: "integer".into(),
+                value: Some(value.to_string()),
+                path: "$".into(),
+            }])
+        }
+
+        async fn check(
+            &self,
+            check: Check,
+            _table: Option<&str>,
+            mut report: impl FnMut(String) + Send,
+        ) -> Result<usize> {
+            if check == Check::ForeignKeys {
+                return Ok(0);
+            }
+            report("row 1 missing from index test_id".into());
+            report("row 2 missing from index test_id".into());
+            Ok(2)
+        }
+
+        async fn backup(
+            &self,
+            schema: &str,
+            _path: &str,
+            mut progress: impl FnMut(u64, u64) + Send,
+        ) -> Result<u64> {
+            if schema != "main" {
+                bail!("unknown database {schema}");
+            }
+            progress(2, 4);
+            progress(4, 4);
+            Ok(4)
+        }
+
+        async fn version(&self) -> Result<Version> {
+            Ok(Version::default())
+        }
+
+        async fn create_table(
+            &self,
+            _table: &str,
+            _columns: &[(String, &str)],
+            rows: &[Vec<serde_json::Value>],
+        ) -> Result<u64> {
+            if self.read_only {
+                bail!("the database is opened read-only");
+            }
+            Ok(rows.len() as u64)
+        }
+
+        async fn insert(
+            &self,
+            _table: &str,
+            _columns: &[String],
+            rows: &[Vec<serde_json::Value>],
+        ) -> Result<u64> {
+            if self.read_only {
+                bail!("the database is opened read-only");
+            }
+            Ok(rows.len() as u64)
+        }
+
+        async fn execute(&self, _statements: &[String]) -> Result<()> {
+            if self.read_only {
+                bail!("the database is opened read-only");
+            }
+            Ok(())
+        }
+
+        async fn explain_with(
+            &self,
+            statements: &[String],
+            _sql: &str,
+            _params: &[Value],
+        ) -> Result<ResultSet> {
+            let detail = match statements.is_empty() {
+                true => "SCAN test",
+                false => "SEARCH test USING INDEX test_id (id=?)",
+            };
+            Ok(ResultSet {
+                columns: Vec::new(),
+                rows: vec![vec!["2".into(), "0".into(), "0".into(), detail.into()]],
+                stats: None,
+            })
+        }
+
+        fn is_read_only(&self) -> bool {
+            self.read_only
         }
 
-        async fn columns(&self, _table: &str) -> Result<Vec<String>> {
-            Ok(vec!["id".into()])
+        async fn maintain(&self, _action: &Maintenance) -> Result<DatabaseInfo> {
+            Ok(DatabaseInfo {
+                page_count: 8,
+                ..DatabaseInfo::default()
+            })
         }
 
-        async fn rows(&self, _: &str, _: &str) -> Result<Vec<Vec<serde_json::Value>>> {
-            Ok(vec![vec![1.into()], vec![2.into()], vec![3.into()]])
+        async fn storage(&self) -> Result<Vec<Storage>> {
+            let storage = |name: &str, table: &str, index: bool, pages: u64| Storage {
+                name: name.into(),
+                table: table.into(),
+                index,
+                pages,
+                bytes: pages * 4096,
+                fragmented: 1,
+                ..Storage::default()
+            };
+            Ok(vec![
+                storage("test", "test", false, 3),
+                storage("test_id", "test", true, 2),
+                storage("test2", "test2", false, 1),
+            ])
         }
     }
 
@@ -520,7 +3171,7 @@ mod tests {
         assert_eq!(model.scroll_state(), &ScrollbarState::new(8));
         assert_eq!(model.colors(), &TableColors::new(&tailwind::TEAL));
         assert_eq!(model.longest_in_column(), 2);
-        model.switch_to_main_view().await.unwrap();
+        model.switch_to_main_view();
         assert_eq!(model.view_state(), ViewState::Main);
         assert_eq!(model.selected_table_id, 0);
         assert_eq!(model.state().selected(), Some(0));
@@ -577,7 +3228,7 @@ mod tests {
         assert!(model.initialize().await.is_ok());
         assert_eq!(
             model.get_info_text(),
-            "(Esc) quit | (↑) move up | (↓) move down | (⇧ S) toggle column select | (Space) toggle schema (→) table view"
+            "(Esc) quit | (↑) move up | (↓) move down | (PgUp/PgDn) page | (:) go to row | (e) edit query | (⇧ S) toggle column select | (Space) toggle schema (→) table view | (i) database info | (d) disk usage"
         );
     }
     #[tokio::test]
@@ -588,7 +3239,7 @@ mod tests {
         model.toggle_column();
         assert_eq!(
             model.get_info_text(),
-            "(Esc) quit | (↑) move up | (↓) move down | (⇧ S) toggle column select | (Space) toggle schema (→) table view | (⇧ ←) previous column | (⇧ →) next column"
+            "(Esc) quit | (↑) move up | (↓) move down | (PgUp/PgDn) page | (:) go to row | (e) edit query | (⇧ S) toggle column select | (Space) toggle schema (→) table view | (i) database info | (d) disk usage | (⇧ ←) previous column | (⇧ →) next column"
         );
     }
 
@@ -600,7 +3251,7 @@ mod tests {
         model.switch_to_table_view().await.unwrap();
         assert_eq!(
             model.get_info_text(),
-            "(Esc) quit | (↑) move up | (↓) move down | (⇧ S) toggle column select | (←) main view"
+            "(Esc) quit | (↑) move up | (↓) move down | (PgUp/PgDn) page | (:) go to row | (e) edit query | (⇧ S) toggle column select | (←) main view | (f) filter | (Enter) toggle record"
         );
     }
 
@@ -613,7 +3264,726 @@ mod tests {
         model.toggle_column();
         assert_eq!(
             model.get_info_text(),
-            "(Esc) quit | (↑) move up | (↓) move down | (⇧ S) toggle column select | (←) main view | (⇧ ←) previous column | (⇧ →) next column"
+            "(Esc) quit | (↑) move up | (↓) move down | (PgUp/PgDn) page | (:) go to row | (e) edit query | (⇧ S) toggle column select | (←) main view | (f) filter | (Enter) toggle record | (⇧ ←) previous column | (⇧ →) next column | (o) view JSON | (a) group by column | (p) column stats | (x) mark column | (⇧ I) create index"
+        );
+    }
+
+    #[tokio::test]
+    async fn sort_main_view() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.sort_by_column(1);
+        assert_eq!(model.sort(), Some((1, SortOrder::Ascending)));
+        assert_eq!(model.tables()[0].name(), "test");
+        model.sort_by_column(1);
+        assert_eq!(model.sort(), Some((1, SortOrder::Descending)));
+        assert_eq!(model.tables()[0].name(), "test2");
+        model.sort_by_column(9);
+        assert_eq!(model.sort(), Some((1, SortOrder::Descending)));
+    }
+
+    #[tokio::test]
+    async fn select_fraction_table_view() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.switch_to_table_view().await.unwrap();
+        model.select_fraction(5, 11);
+        assert_eq!(model.state().selected(), Some(1));
+        model.select_fraction(20, 11);
+        assert_eq!(model.state().selected(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn toggle_record_table_view() {
+        let db = SqliteDb::memory().await.unwrap();
+        db.query("CREATE TABLE users (id INTEGER, name TEXT, note TEXT)")
+            .await
+            .unwrap();
+        db.query("INSERT INTO users VALUES (1, 'alice', NULL), (2, 'bob', 'x')")
+            .await
+            .unwrap();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.toggle_record();
+        assert!(!model.is_record_enabled());
+        model.open_table("users").await.unwrap();
+        model.toggle_record();
+        assert!(model.is_record_enabled());
+        assert_eq!(
+            model.record(),
+            [("id", "1"), ("name", "alice"), ("note", "null")]
+        );
+        model.next();
+        assert_eq!(
+            model.record(),
+            [("id", "2"), ("name", "bob"), ("note", "x")]
+        );
+    }
+
+    #[tokio::test]
+    async fn table_view_without_wrap() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.switch_to_table_view().await.unwrap();
+        model.set_wrap(false);
+        model.previous();
+        assert_eq!(model.state().selected(), Some(0));
+        model.last();
+        model.next();
+        assert_eq!(model.state().selected(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn table_view_pages() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.switch_to_table_view().await.unwrap();
+        model.set_page_size(2);
+        model.next_page();
+        assert_eq!(model.state().selected(), Some(2));
+        model.next_page();
+        assert_eq!(model.state().selected(), Some(2));
+        model.previous_page();
+        assert_eq!(model.state().selected(), Some(0));
+        model.goto(2);
+        assert_eq!(model.state().selected(), Some(1));
+        model.goto(0);
+        assert_eq!(model.state().selected(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn table_view_is_cached() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.switch_to_table_view().await.unwrap();
+        assert!(model.tables()[0].is_loaded());
+        assert!(!model.tables()[1].is_loaded());
+        assert_eq!(model.selected_table(), Some("test"));
+
+        model.switch_to_main_view();
+        assert!(model.enter_table_view().is_none());
+        assert_eq!(model.view_state(), ViewState::Table);
+
+        model.switch_to_main_view();
+        model.invalidate(Some("test"));
+        assert!(!model.tables()[0].is_loaded());
+        assert!(model.enter_table_view().is_some());
+        assert_eq!(model.view_state(), ViewState::Main);
+    }
+
+    #[tokio::test]
+    async fn open_table_and_select_where() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        assert!(model.select_where("id = 3").await.is_err());
+        model.open_table("test2").await.unwrap();
+        assert_eq!(model.selected_table(), Some("test2"));
+        model.select_where("id = 3").await.unwrap();
+        assert_eq!(model.state().selected(), Some(2));
+        assert!(model.open_table("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn query_and_select_where() {
+        let db = SqliteDb::memory().await.unwrap();
+        db.query("CREATE TABLE users (id INTEGER, name TEXT)")
+            .await
+            .unwrap();
+        db.query("INSERT INTO users VALUES (1, 'alice'), (2, 'bob'), (3, 'carol')")
+            .await
+            .unwrap();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model
+            .run_query("SELECT name FROM users ORDER BY id DESC;")
+            .await
+            .unwrap();
+        model.select_where("name = 'alice'").await.unwrap();
+        assert_eq!(model.state().selected(), Some(2));
+        assert!(model.select_where("name = 'dave'").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn query_view() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.run_query("SELECT 1").await.unwrap();
+        assert_eq!(model.view_state(), ViewState::Query);
+        assert_eq!(model.query(), Some("SELECT 1"));
+        assert_eq!(model.item_count(), 0);
+        model.switch_to_main_view();
+        assert_eq!(model.view_state(), ViewState::Main);
+    }
+
+    #[tokio::test]
+    async fn complete_in_editor() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        let type_text = |model: &mut Model<MockDb>, text: &str| {
+            let editor = model.editor_mut().unwrap();
+            text.chars().for_each(|c| editor.insert(c));
+        };
+
+        model.open_editor();
+        type_text(&mut model, "SELECT * FROM t");
+        model.open_completion();
+        let completion = model.completion().unwrap();
+        assert_eq!(completion.items(), ["test", "test2"]);
+        model.completion_previous();
+        model.accept_completion();
+        assert!(model.completion().is_none());
+        assert_eq!(model.editor().unwrap().text(), "SELECT * FROM test2");
+
+        type_text(&mut model, " x WHERE x.");
+        model.open_completion();
+        assert!(model.completion().is_none());
+        assert_eq!(
+            model.editor().unwrap().text(),
+            "SELECT * FROM test2 x WHERE x.id"
+        );
+
+        type_text(&mut model, " = 1 AND i");
+        model.open_completion();
+        let items = model.completion().unwrap().items();
+        assert_eq!(items[..3], ["id", "IF", "IGNORE"]);
+        assert!(!items.contains(&"test".to_string()));
+        type_text(&mut model, "z");
+        model.update_completion();
+        assert!(model.completion().is_none());
+    }
+
+    #[tokio::test]
+    async fn info_view() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        let update = model.load_report(ReportKind::Info, |_| {}).await.unwrap();
+        model.apply(update);
+        assert_eq!(model.view_state(), ViewState::Report);
+        assert_eq!(model.report(), Some(&ReportKind::Info));
+        assert_eq!(model.get_table_columns(), ["property", "value"]);
+        assert_eq!(model.record()[1], ("value", "/"));
+        model.goto(7);
+        assert_eq!(model.record()[1], ("value", "enabled, no WAL file"));
+        assert_eq!(model.statement().unwrap(), None);
+        model.switch_to_main_view();
+        assert_eq!(model.view_state(), ViewState::Main);
+    }
+
+    #[tokio::test]
+    async fn check_report() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let kind = ReportKind::Check {
+            check: Check::Integrity,
+            table: Some("test".into()),
+        };
+        let done = model
+            .load_report(kind.clone(), progress_into(&updates))
+            .await
+            .unwrap();
+        for update in updates.lock().unwrap().drain(..) {
+            model.apply(update);
+        }
+        model.apply(done);
+        assert_eq!(model.view_state(), ViewState::Report);
+        assert_eq!(kind.title(), "integrity_check of test");
+        assert_eq!(model.get_table_columns(), ["#", "problem"]);
+        assert_eq!(model.item_count(), 2);
+        assert_eq!(
+            model.record()[1],
+            ("problem", "row 1 missing from index test_id")
+        );
+        assert_eq!(model.message(), Some("integrity_check: 2 problems"));
+
+        let kind = ReportKind::Check {
+            check: Check::ForeignKeys,
+            table: None,
+        };
+        let done = model
+            .load_report(kind, progress_into(&updates))
+            .await
+            .unwrap();
+        for update in updates.lock().unwrap().drain(..) {
+            model.apply(update);
+        }
+        model.apply(done);
+        assert_eq!(model.item_count(), 1);
+        assert_eq!(model.record()[1], ("problem", "ok"));
+    }
+
+    #[tokio::test]
+    async fn import_preview() {
+        let mut model = Model::new(MockDb::new());
+        assert!(model.initialize().await.is_ok());
+        let path =
+            std::env::temp_dir().join(format!("sqliters-preview-{}.csv", std::process::id()));
+        std::fs::write(&path, "id\n4\n5\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let kind = ReportKind::Import {
+            path: path.clone(),
+            table: "test".into(),
+        };
+        model.apply(model.load_report(kind, |_| {}).await.unwrap());
+        assert_eq!(model.view_state(), ViewState::Report);
+        assert_eq!(model.get_table_columns(), ["id INTEGER"]);
+        assert_eq!(model.item_count(), 2);
+        assert!(model
+            .get_info_text()
+            .contains("(w) append to test with 2 rows"));
+
+        let update = model.load_import_write().unwrap().await.unwrap();
+        model.apply(update);
+        assert_eq!(model.view_state(), ViewState::Main);
+        assert_eq!(model.message(), Some("imported 2 rows, appended to test"));
+        assert!(model.load_import_write().is_none());
+
+        let kind = ReportKind::Import {
+            path: path.clone(),
+            table: "fresh".into(),
+        };
+        model.apply(model.load_report(kind, |_| {}).await.unwrap());
+        let update = model.load_import_write().unwrap().await.unwrap();
+        model.apply(update);
+        assert_eq!(model.message(), Some("imported 2 rows, created fresh"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn schema_editor() {
+        let mut model = Model::new(MockDb::new());
+        assert!(model.initialize().await.is_ok());
+        let update = model.open_schema_editor().unwrap().await.unwrap();
+        model.apply(update);
+        let Some(SchemaView::Menu { table, .. }) = model.schema_editor() else {
+            panic!("expected the menu");
+        };
+        assert_eq!(table.as_ref().unwrap().name, "test");
+
+        model.schema_editor_next();
+        model.choose_schema_action();
+        model.review_schema_change();
+        assert_eq!(model.message(), Some("the column needs a name"));
+        let form = model.schema_form_mut().unwrap();
+        "name".chars().for_each(|c| form.push(c));
+        model.review_schema_change();
+        let Some(SchemaView::Review {
+            statements,
+            rebuild,
+            ..
+        }) = model.schema_editor()
+        else {
+            panic!("expected the review");
+        };
+        assert_eq!(statements, &[r#"ALTER TABLE "test" ADD COLUMN "name""#]);
+        assert!(!rebuild);
+        model.back_schema_editor();
+        assert!(model.schema_form_mut().is_some());
+        model.review_schema_change();
+
+        let update = model.load_schema_change().unwrap().await.unwrap();
+        model.apply(update);
+        assert!(model.schema_editor().is_none());
+        assert_eq!(model.message(), Some("Add column: ran 1 statements"));
+
+        // Dropping the table has no form and goes back to closed.
+        let update = model.open_schema_editor().unwrap().await.unwrap();
+        model.apply(update);
+        model.schema_editor_previous();
+        model.choose_schema_action();
+        assert!(matches!(
+            model.schema_editor(),
+            Some(SchemaView::Review { statements, .. }) if statements == &[r#"DROP TABLE "test""#]
+        ));
+        model.back_schema_editor();
+        assert!(model.schema_editor().is_none());
+
+        let mut model = Model::new(MockDb { read_only: true });
+        assert!(model.open_schema_editor().is_none());
+        assert!(model.message().unwrap().contains("read-only"));
+    }
+
+    #[tokio::test]
+    async fn create_index_preview() {
+        let mut model = Model::new(MockDb::new());
+        assert!(model.initialize().await.is_ok());
+        assert!(model.switch_to_table_view().await.is_ok());
+        assert!(model.open_index_form().is_none());
+        model.toggle_column();
+        model.toggle_mark_column();
+        assert_eq!(model.marked_columns(), [0]);
+        model.next();
+        let update = model.open_index_form().unwrap().await.unwrap();
+        model.apply(update);
+        assert_eq!(
+            model.schema_form_mut().unwrap().fields()[0].value(),
+            "test_id"
+        );
+
+        model.review_schema_change();
+        let update = model.load_index_plans().unwrap().await.unwrap();
+        model.apply(update);
+        let Some(SchemaView::Review {
+            statements, plans, ..
+        }) = model.schema_editor()
+        else {
+            panic!("expected the review");
+        };
+        assert_eq!(statements, &[r#"CREATE INDEX "test_id" ON "test" ("id")"#]);
+        assert_eq!(plans[0].before.sql, r#"SELECT * FROM "test""#);
+        assert_eq!(
+            plans[1].before.sql,
+            r#"SELECT * FROM "test" WHERE "id" = ?"#
+        );
+        assert!(plans[1].is_changed());
+
+        model.toggle_column();
+        assert!(model.marked_columns().is_empty());
+    }
+
+    #[tokio::test]
+    async fn group_by_column() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.switch_to_table_view().await.unwrap();
+        assert!(model.load_group().is_none());
+
+        model.toggle_column();
+        let update = model.load_group().unwrap().await.unwrap();
+        model.apply(update);
+        assert_eq!(model.view_state(), ViewState::Query);
+        assert!(model.grouping().is_some());
+        assert_eq!(
+            model.query(),
+            Some(
+                r#"SELECT "id", count(*) AS "count", round(100.0 * count(*) / sum(count(*)) OVER (), 2) AS "percent" FROM "test" GROUP BY "id" ORDER BY count(*) DESC, "id""#
+            )
+        );
+        assert!(model.get_info_text().ends_with(" | (Enter) rows of group"));
+
+        model.next();
+        let update = model.load_drill_down().unwrap().await.unwrap();
+        model.apply(update);
+        assert_eq!(
+            model.query(),
+            Some(r#"SELECT * FROM "test" WHERE "id" IS 1"#)
+        );
+        assert!(model.grouping().is_none());
+    }
+
+    #[tokio::test]
+    async fn filter_table() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.open_filter();
+        assert!(model.filter_form().is_none());
+
+        model.switch_to_table_view().await.unwrap();
+        model.open_filter();
+        assert!(model.apply_filter(false).is_err());
+        let form = model.filter_form_mut().unwrap();
+        form.next();
+        form.next();
+        form.push('2');
+        let update = model.apply_filter(true).unwrap().await.unwrap();
+        model.apply(update);
+        assert!(model.filter_form().is_none());
+        assert_eq!(
+            model.active_filter().unwrap().conditions[0].to_string(),
+            "id = 2"
+        );
+        assert_eq!(model.get_table_rows().len(), 1);
+        model.sort_by_column(0);
+        let sql = r#"SELECT * FROM "test" WHERE "id" = ? ORDER BY "id""#;
+        assert_eq!(
+            model.statement().unwrap(),
+            Some((sql.to_string(), vec![2.into()]))
+        );
+
+        model.open_filter();
+        model.filter_form_mut().unwrap().remove_condition();
+        let update = model.apply_filter(false).unwrap().await.unwrap();
+        model.apply(update);
+        assert!(model.active_filter().is_none());
+        assert_eq!(model.get_table_rows().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn column_stats_popup() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        assert!(model.load_column_stats().is_none());
+
+        model.switch_to_table_view().await.unwrap();
+        let update = model.load_column_stats().unwrap().await.unwrap();
+        model.apply(update);
+        let stats = model.column_stats().unwrap();
+        assert_eq!(
+            (stats.table.as_str(), stats.column.as_str()),
+            ("test", "id")
+        );
+        assert_eq!(stats.count, 2);
+
+        model.close_column_stats();
+        assert!(model.column_stats().is_none());
+    }
+
+    #[tokio::test]
+    async fn json_viewer() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        assert!(model.load_json().is_none());
+
+        model.switch_to_table_view().await.unwrap();
+        let update = model.load_json().unwrap().await.unwrap();
+        model.apply(update);
+        let view = model.json().unwrap();
+        assert_eq!((view.table(), view.column()), (Some("test"), "id"));
+        model.filter_json();
+        assert!(model.json().is_none());
+        let form = model.filter_form().unwrap();
+        assert_eq!(
+            form.conditions().last().unwrap().to_string(),
+            "id ->> '$' = 1"
+        );
+
+        model.close_filter();
+        let update = model.load_json().unwrap().await.unwrap();
+        model.apply(update);
+        let update = model.json_column_form().unwrap().await.unwrap();
+        model.apply(update);
+        let Some(SchemaView::Form(form)) = model.schema_editor() else {
+            panic!("expected the schema form");
+        };
+        assert_eq!(
+            form.change().unwrap().statements(form.table()).unwrap(),
+            [
+                r#"ALTER TABLE "test" ADD COLUMN "id" GENERATED ALWAYS AS (json_extract("id", '$')) VIRTUAL"#
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn time_formats() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        assert!(model
+            .set_time_format("id", Some(TimeFormat::Seconds))
+            .is_err());
+
+        model.switch_to_table_view().await.unwrap();
+        assert_eq!(model.time_formats(), [None]);
+        assert!(!model.get_info_text().contains("times"));
+        assert!(model.set_time_format("nope", None).is_err());
+        model
+            .set_time_format("id", Some(TimeFormat::Seconds))
+            .unwrap();
+        let formats = model.time_formats();
+        assert_eq!(formats, [Some(TimeFormat::Seconds)]);
+        assert!(model
+            .get_info_text()
+            .ends_with("(f) filter | (⇧ T) times: local | (Enter) toggle record"));
+
+        model.set_time_zone(Some(Zone::Utc));
+        assert_eq!(model.cell_text(&formats, 0, "1"), "1970-01-01 00:00:01 UTC");
+        assert_eq!(model.cell_text(&formats, 0, "null"), "null");
+        model.cycle_time_zone();
+        assert_eq!(model.cell_text(&formats, 0, "1"), "1");
+        model.cycle_time_zone();
+        assert!(model.get_info_text().contains("(⇧ T) times: local"));
+
+        model.set_time_format("id", Some(TimeFormat::Raw)).unwrap();
+        assert_eq!(model.time_formats(), [None]);
+        let rows = vec![vec![
+            Value::from("2024-03-01 12:00:00"),
+            Value::from("1709294400"),
+            Value::from("1709294400"),
+        ]];
+        let columns = ["at", "ts", "id"].map(String::from).to_vec();
+        let contents = Contents::new(columns, rows).with_types(vec![
+            "DATETIME".into(),
+            "INTEGER".into(),
+            "INTEGER".into(),
+        ]);
+        assert_eq!(
+            contents.times,
+            [Some(TimeFormat::Text), Some(TimeFormat::Seconds), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn one_overlay_at_a_time() {
+        let mut model = Model::new(MockDb::new());
+        assert!(model.initialize().await.is_ok());
+        model.open_editor();
+        model.open_picker();
+        assert!(matches!(model.overlay(), Some(Overlay::Picker { .. })));
+        assert!(model.editor().is_some());
+        model.close_picker();
+        assert!(matches!(model.overlay(), Some(Overlay::Editor { .. })));
+
+        model.open_maintenance();
+        assert!(model.editor().is_none());
+        assert!(model.maintenance().is_some());
+        model.close_maintenance();
+        assert!(model.overlay().is_none());
+    }
+
+    #[tokio::test]
+    async fn maintenance_popup() {
+        let mut model = Model::new(MockDb::new());
+        assert!(model.initialize().await.is_ok());
+        model.open_maintenance();
+        model.maintenance_previous();
+        assert!(matches!(
+            model.maintenance(),
+            Some(MaintenanceView::Menu { selected: 5 })
+        ));
+        model.maintenance_next();
+        model.maintenance_next();
+        model.choose_maintenance();
+        assert!(model.maintenance().is_none());
+        assert_eq!(model.prompt(), Some("vacuum into "));
+        model.close_prompt();
+
+        model.confirm_maintenance(Maintenance::Analyze);
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let load = model.load_maintenance(progress_into(&updates)).unwrap();
+        assert!(model.maintenance().is_none());
+        model.apply(load.await.unwrap());
+        let Some(MaintenanceView::Done(result)) = model.maintenance() else {
+            panic!("expected the result of the action");
+        };
+        assert_eq!(result.action, Maintenance::Analyze);
+        assert_eq!(result.after.page_count, 8);
+        assert!(model.load_maintenance(|_| {}).is_none());
+
+        let mut model = Model::new(MockDb { read_only: true });
+        model.open_maintenance();
+        model.confirm_maintenance(Maintenance::Vacuum);
+        assert!(model.maintenance().is_none());
+        assert!(model.message().unwrap().contains("read-only"));
+    }
+
+    #[tokio::test]
+    async fn watch_reloads_changed_view() {
+        let mut model = Model::new(MockDb::new());
+        assert!(model.initialize().await.is_ok());
+        let version = |data_version| Version {
+            data_version,
+            modified: None,
+        };
+        assert!(model.watch_version(version(1)).is_none());
+
+        model.set_watch(Some(Duration::from_secs(1)));
+        model.switch_to_table_view().await.unwrap();
+        model.next();
+        assert!(model.watch_version(version(1)).is_none());
+        assert!(model.watch_version(version(1)).is_none());
+        assert!(model.updated_ago().is_none());
+
+        let load = model.watch_version(version(2)).unwrap();
+        model.apply(load.await.unwrap());
+        assert_eq!(model.view_state(), ViewState::Table);
+        assert_eq!(model.state().selected(), Some(1));
+        assert!(model.updated_ago().is_some());
+        assert!(!model.tables()[1].is_loaded());
+
+        // The main list missed the change and is reloaded once shown.
+        model.switch_to_main_view();
+        assert!(model.watch_version(version(2)).is_some());
+        assert!(model.watch_version(version(2)).is_none());
+
+        model.set_watch(None);
+        assert!(model.watch_version(version(3)).is_none());
+    }
+
+    #[tokio::test]
+    async fn watch_skips_writing_statement() {
+        let mut model = Model::new(MockDb::new());
+        assert!(model.initialize().await.is_ok());
+        let version = |data_version| Version {
+            data_version,
+            modified: None,
+        };
+        model.set_watch(Some(Duration::from_secs(1)));
+        model.run_query("SELECT 1").await.unwrap();
+        assert!(model.watch_version(version(1)).is_none());
+        assert!(model.watch_version(version(2)).is_some());
+
+        // The app's own write is not a change, and the statement that made
+        // it is not run again on changes by others.
+        model.run_query("UPDATE t SET a = 1").await.unwrap();
+        assert_eq!(model.view_state(), ViewState::Query);
+        assert!(model.watch_version(version(3)).is_none());
+        assert!(model.watch_version(version(4)).is_none());
+        assert_eq!(model.query(), Some("UPDATE t SET a = 1"));
+    }
+
+    #[tokio::test]
+    async fn backup_progress() {
+        let mut model = Model::new(MockDb::new());
+        assert!(model.initialize().await.is_ok());
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let load = model.load_backup("main".into(), "copy.db".into(), progress_into(&updates));
+        model.apply(load.await.unwrap());
+        assert_eq!(
+            model.message(),
+            Some("backed up 4 pages of main to copy.db")
+        );
+        assert!(matches!(
+            updates.lock().unwrap().first(),
+            Some(Update::Status(status)) if status == "backup of main: 2 of 4 pages"
+        ));
+
+        let load = model.load_backup("aux".into(), "copy.db".into(), |_| {});
+        assert!(load.await.is_err());
+    }
+
+    fn progress_into(updates: &Arc<Mutex<Vec<Update>>>) -> impl Fn(Update) + Send + Sync {
+        let updates = updates.clone();
+        move |update| updates.lock().unwrap().push(update)
+    }
+
+    #[tokio::test]
+    async fn storage_columns() {
+        let db = MockDb::new();
+        let mut model = Model::new(db);
+        assert!(model.initialize().await.is_ok());
+        model.toggle_storage();
+        assert_eq!(model.get_table_columns().len(), 10);
+        let update = model.load_overview().await.unwrap();
+        model.apply(update);
+
+        let row: Vec<&str> = model.tables()[0]
+            .overview()
+            .iter()
+            .map(|v| v.as_str().unwrap())
+            .collect();
+        assert_eq!(
+            row,
+            ["1", "test", "1", "3", "3", "12288", "0", "0", "50.0", "8192"]
         );
+
+        model.sort_by_column(5);
+        assert_eq!(model.tables()[0].name(), "test2");
+        model.toggle_storage();
+        assert_eq!(model.get_table_columns().len(), 4);
     }
 }
@@ -0,0 +1,168 @@
+use std::io::Write;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::database::ResultSet;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns with a header
+    #[default]
+    Table,
+    /// Comma separated values with a header row
+    Csv,
+    /// Array of objects keyed by column name
+    Json,
+}
+
+pub fn write(out: &mut impl Write, result: &ResultSet, format: Format) -> Result<()> {
+    match format {
+        Format::Table => write_table(out, result),
+        Format::Csv => write_csv(out, result),
+        Format::Json => write_json(out, result),
+    }
+}
+
+fn text(value: &Value) -> String {
+    value
+        .as_str()
+        .map_or_else(|| value.to_string(), str::to_string)
+}
+
+fn write_table(out: &mut impl Write, result: &ResultSet) -> Result<()> {
+    let mut widths: Vec<usize> = result
+        .columns
+        .iter()
+        .map(|column| column.chars().count())
+        .collect();
+    for row in &result.rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(text(value).chars().count());
+        }
+    }
+
+    let line = |cells: Vec<String>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+
+    writeln!(out, "{}", line(result.columns.clone()))?;
+    writeln!(
+        out,
+        "{}",
+        widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>()
+            .join("-+-")
+    )?;
+    for row in &result.rows {
+        writeln!(out, "{}", line(row.iter().map(text).collect()))?;
+    }
+    Ok(())
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv(out: &mut impl Write, result: &ResultSet) -> Result<()> {
+    let header: Vec<String> = result.columns.iter().map(|c| csv_field(c)).collect();
+    writeln!(out, "{}", header.join(","))?;
+    for row in &result.rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                value => csv_field(&text(value)),
+            })
+            .collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+fn write_json(out: &mut impl Write, result: &ResultSet) -> Result<()> {
+    let objects: Vec<Value> = result
+        .rows
+        .iter()
+        .map(|row| {
+            Value::Object(
+                result
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().cloned())
+                    .collect::<Map<_, _>>(),
+            )
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &objects)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result() -> ResultSet {
+        ResultSet {
+            columns: vec!["id".into(), "name".into()],
+            rows: vec![
+                vec![json!(1), json!("alice")],
+                vec![json!(22.5), json!("b, \"bob\"")],
+                vec![json!(3), Value::Null],
+            ],
+            stats: None,
+        }
+    }
+
+    fn render(format: Format) -> String {
+        let mut out = Vec::new();
+        write(&mut out, &result(), format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn write_table() {
+        assert_eq!(
+            render(Format::Table),
+            "id   | name\n-----+---------\n1    | alice\n22.5 | b, \"bob\"\n3    | null\n"
+        );
+    }
+
+    #[test]
+    fn write_csv() {
+        assert_eq!(
+            render(Format::Csv),
+            "id,name\n1,alice\n22.5,\"b, \"\"bob\"\"\"\n3,\n"
+        );
+    }
+
+    #[test]
+    fn write_json() {
+        let value: Value = serde_json::from_str(&render(Format::Json)).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {"id": 1, "name": "alice"},
+                {"id": 22.5, "name": "b, \"bob\""},
+                {"id": 3, "name": null}
+            ])
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::database::ResultSet;
+
+/// How a plan step reads its table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    /// Reads every row of the table.
+    FullScan,
+    /// Uses an index, the rowid or the primary key.
+    Index,
+    /// Builds a temporary b-tree, e.g. for `ORDER BY` or `DISTINCT`.
+    TempTree,
+    Other,
+}
+
+/// One line of `EXPLAIN QUERY PLAN` output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub depth: usize,
+    pub detail: String,
+}
+
+impl Step {
+    pub fn access(&self) -> Access {
+        let detail = self.detail.as_str();
+        if detail.contains("TEMP B-TREE") {
+            Access::TempTree
+        } else if detail.contains(" USING ") {
+            Access::Index
+        } else if detail.starts_with("SCAN ") {
+            Access::FullScan
+        } else {
+            Access::Other
+        }
+    }
+}
+
+/// Query plan and bytecode of a statement.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub sql: String,
+    pub steps: Vec<Step>,
+    pub bytecode: ResultSet,
+}
+
+impl Plan {
+    /// Builds the plan from the `id`, `parent`, `notused` and `detail` rows
+    /// of `EXPLAIN QUERY PLAN`, which SQLite returns in tree order.
+    pub fn new(sql: String, plan: &ResultSet, bytecode: ResultSet) -> Self {
+        let mut depths: HashMap<&str, usize> = HashMap::new();
+        let steps = plan
+            .rows
+            .iter()
+            .map(|row| {
+                let id = row.first().and_then(Value::as_str).unwrap_or_default();
+                let parent = row.get(1).and_then(Value::as_str).unwrap_or_default();
+                let detail = row.get(3).and_then(Value::as_str).unwrap_or_default();
+                let depth = depths.get(parent).map_or(0, |depth| depth + 1);
+                depths.insert(id, depth);
+                Step {
+                    depth,
+                    detail: detail.to_string(),
+                }
+            })
+            .collect();
+
+        Self {
+            sql,
+            steps,
+            bytecode,
+        }
+    }
+}
+
+/// Plan of a statement before and after a schema change, e.g. a new index.
+#[derive(Debug, Clone)]
+pub struct PlanChange {
+    pub before: Plan,
+    pub after: Plan,
+}
+
+impl PlanChange {
+    pub fn is_changed(&self) -> bool {
+        self.before.steps != self.after.steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Database, SqliteDb};
+
+    #[test]
+    fn build_plan() {
+        let row = |id: &str, parent: &str, detail: &str| {
+            vec![id.into(), parent.into(), "0".into(), detail.into()]
+        };
+        let plan = ResultSet {
+            columns: vec![],
+            rows: vec![
+                row("3", "0", "SCAN users"),
+                row("5", "0", "CORRELATED SCALAR SUBQUERY 1"),
+                row(
+                    "9",
+                    "5",
+                    "SEARCH orders USING INDEX orders_user (user_id=?)",
+                ),
+                row("20", "0", "USE TEMP B-TREE FOR ORDER BY"),
+            ],
+            stats: None,
+        };
+        let plan = Plan::new("SELECT".into(), &plan, ResultSet::default());
+        let steps: Vec<(usize, Access)> = plan
+            .steps
+            .iter()
+            .map(|step| (step.depth, step.access()))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (0, Access::FullScan),
+                (0, Access::Other),
+                (1, Access::Index),
+                (0, Access::TempTree),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn explain_sqlite_plan() {
+        let db = SqliteDb::memory().await.unwrap();
+        db.query("CREATE TABLE t (a INTEGER, b TEXT)")
+            .await
+            .unwrap();
+        db.query("CREATE INDEX t_a ON t (a)").await.unwrap();
+
+        let explain = |sql: &'static str| {
+            let db = db.clone();
+            async move {
+                let plan = db
+                    .query(&format!("EXPLAIN QUERY PLAN {sql}"))
+                    .await
+                    .unwrap();
+                Plan::new(sql.into(), &plan, ResultSet::default())
+            }
+        };
+        let plan = explain("SELECT * FROM t WHERE b = 'x'").await;
+        assert_eq!(plan.steps[0].access(), Access::FullScan);
+        let plan = explain("SELECT * FROM t WHERE a = 1").await;
+        assert_eq!(plan.steps[0].access(), Access::Index);
+
+        // The index only exists for the plan.
+        let sql = "SELECT * FROM t WHERE b = 'x'";
+        let statements = ["CREATE INDEX t_b ON t (b)".to_string()];
+        let after = db.explain_with(&statements, sql, &[]).await.unwrap();
+        let change = PlanChange {
+            before: explain(sql).await,
+            after: Plan::new(sql.into(), &after, ResultSet::default()),
+        };
+        assert!(change.is_changed());
+        assert_eq!(change.after.steps[0].access(), Access::Index);
+        assert_eq!(explain(sql).await.steps[0].access(), Access::FullScan);
+
+        let sql = "SELECT * FROM t WHERE b = ? AND a IS NULL";
+        let params = [serde_json::json!("x")];
+        let after = db.explain_with(&statements, sql, &params).await.unwrap();
+        let plan = Plan::new(sql.into(), &after, ResultSet::default());
+        assert_eq!(plan.steps[0].access(), Access::Index);
+    }
+}
@@ -9,7 +9,7 @@ use ratatui::{
 #[derive(Debug, Default)]
 pub struct Popup {
     title: String,
-    content: String,
+    content: Text<'static>,
     border_style: Style,
     title_style: Style,
     style: Style,
@@ -20,8 +20,8 @@ impl Popup {
         self.title = title;
         self
     }
-    pub fn content(mut self, content: String) -> Self {
-        self.content = content;
+    pub fn content(mut self, content: impl Into<Text<'static>>) -> Self {
+        self.content = content.into();
         self
     }
     pub fn border_style(mut self, style: Style) -> Self {
@@ -0,0 +1,1081 @@
+//! Schema changes made through the schema editor and the DDL running them.
+
+use anyhow::{bail, Result};
+
+use crate::database::{ColumnInfo, Ident, TableSchema};
+
+/// Types offered for a column, the empty one declaring none.
+pub const TYPES: [&str; 6] = ["INTEGER", "REAL", "TEXT", "BLOB", "NUMERIC", ""];
+
+/// Definition of a new column, or the new definition of an existing one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub kind: String,
+    pub not_null: bool,
+    pub primary_key: bool,
+    /// Default value as SQL text, e.g. `0`, `'none'` or `CURRENT_TIMESTAMP`.
+    pub default: Option<String>,
+    /// Expression of a virtual generated column.
+    pub generated: Option<String>,
+}
+
+impl ColumnDef {
+    fn from_info(info: &ColumnInfo) -> Self {
+        Self {
+            name: info.name.clone(),
+            kind: info.kind.clone(),
+            not_null: info.not_null,
+            primary_key: info.primary_key > 0,
+            default: info.default.clone(),
+            generated: None,
+        }
+    }
+
+    /// The column definition of `CREATE TABLE`, with `PRIMARY KEY` only when
+    /// `primary_key` is inline rather than a table constraint.
+    fn sql(&self, primary_key: Option<&str>) -> String {
+        let mut sql = Ident::new(&self.name).to_string();
+        if !self.kind.is_empty() {
+            sql.push(' ');
+            sql.push_str(&self.kind);
+        }
+        if let Some(primary_key) = primary_key {
+            sql.push(' ');
+            sql.push_str(primary_key);
+        }
+        if self.not_null {
+            sql.push_str(" NOT NULL");
+        }
+        if let Some(default) = &self.default {
+            sql.push_str(" DEFAULT ");
+            sql.push_str(default);
+        }
+        if let Some(generated) = &self.generated {
+            sql.push_str(&format!(" GENERATED ALWAYS AS ({generated}) VIRTUAL"));
+        }
+        sql
+    }
+}
+
+/// A change to the schema, turned into statements by
+/// [`SchemaChange::statements`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaChange {
+    CreateTable {
+        table: String,
+        columns: Vec<ColumnDef>,
+    },
+    AddColumn {
+        table: String,
+        column: ColumnDef,
+    },
+    RenameColumn {
+        table: String,
+        from: String,
+        to: String,
+    },
+    /// Redefines the existing column of the same name, which always takes a
+    /// table rebuild.
+    ChangeColumn {
+        table: String,
+        column: ColumnDef,
+    },
+    DropColumn {
+        table: String,
+        column: String,
+    },
+    DropTable(String),
+    CreateIndex {
+        table: String,
+        name: String,
+        columns: Vec<String>,
+        unique: bool,
+        /// `WHERE` clause of a partial index.
+        condition: Option<String>,
+    },
+    DropIndex(String),
+}
+
+impl SchemaChange {
+    /// The statements making the change to `schema`, the current schema of
+    /// the table it alters. Changes `ALTER TABLE` cannot make rebuild the
+    /// table, see [`rebuild`].
+    pub fn statements(&self, schema: Option<&TableSchema>) -> Result<Vec<String>> {
+        let schema = || match schema {
+            Some(schema) => Ok(schema),
+            None => bail!("the schema of the table is not loaded"),
+        };
+        let statements = match self {
+            SchemaChange::CreateTable { table, columns } => {
+                let keys: Vec<&ColumnDef> = columns.iter().filter(|c| c.primary_key).collect();
+                vec![create_table(table, columns, &keys, "", &[], false)]
+            }
+            SchemaChange::AddColumn { table, column } => {
+                if self.rebuilds(schema()?) {
+                    let mut columns = existing(schema()?);
+                    columns.push((column.clone(), None));
+                    rebuild(schema()?, columns)?
+                } else {
+                    vec![format!(
+                        "ALTER TABLE {} ADD COLUMN {}",
+                        Ident::new(table),
+                        column.sql(None)
+                    )]
+                }
+            }
+            SchemaChange::RenameColumn { table, from, to } => vec![format!(
+                "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                Ident::new(table),
+                Ident::new(from),
+                Ident::new(to)
+            )],
+            SchemaChange::ChangeColumn { column, .. } => {
+                let columns = existing(schema()?)
+                    .into_iter()
+                    .map(|(def, source)| match def.name == column.name {
+                        true => (column.clone(), source),
+                        false => (def, source),
+                    })
+                    .collect();
+                rebuild(schema()?, columns)?
+            }
+            SchemaChange::DropColumn { table, column } => {
+                if self.rebuilds(schema()?) {
+                    let columns = existing(schema()?)
+                        .into_iter()
+                        .filter(|(def, _)| def.name != *column)
+                        .collect();
+                    rebuild(schema()?, columns)?
+                } else {
+                    vec![format!(
+                        "ALTER TABLE {} DROP COLUMN {}",
+                        Ident::new(table),
+                        Ident::new(column)
+                    )]
+                }
+            }
+            SchemaChange::DropTable(table) => vec![format!("DROP TABLE {}", Ident::new(table))],
+            SchemaChange::CreateIndex {
+                table,
+                name,
+                columns,
+                unique,
+                condition,
+            } => {
+                let mut sql = format!(
+                    "CREATE {}INDEX {} ON {} ({})",
+                    if *unique { "UNIQUE " } else { "" },
+                    Ident::new(name),
+                    Ident::new(table),
+                    names(columns)
+                );
+                if let Some(condition) = condition {
+                    sql.push_str(" WHERE ");
+                    sql.push_str(condition);
+                }
+                vec![sql]
+            }
+            SchemaChange::DropIndex(index) => vec![format!("DROP INDEX {}", Ident::new(index))],
+        };
+        Ok(statements)
+    }
+
+    /// Whether the change rebuilds the table: `ALTER TABLE` cannot add a
+    /// primary key column, drop a column that is part of a key or index, or
+    /// change a column.
+    pub fn rebuilds(&self, schema: &TableSchema) -> bool {
+        match self {
+            SchemaChange::AddColumn { column, .. } => column.primary_key,
+            SchemaChange::ChangeColumn { .. } => true,
+            SchemaChange::DropColumn { column, .. } => {
+                schema
+                    .columns
+                    .iter()
+                    .any(|c| c.name == *column && c.primary_key > 0)
+                    || schema.indexes.iter().any(|i| i.columns.contains(column))
+                    || schema.foreign_keys.iter().any(|k| k.from.contains(column))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The columns of `schema`, each copied from itself.
+fn existing(schema: &TableSchema) -> Vec<(ColumnDef, Option<String>)> {
+    schema
+        .columns
+        .iter()
+        .map(|info| (ColumnDef::from_info(info), Some(info.name.clone())))
+        .collect()
+}
+
+/// `CREATE TABLE` with `columns`, the primary key `keys` and the table
+/// `constraints`, followed by `options` such as `WITHOUT ROWID`. A single
+/// key column is declared inline, keeping an `INTEGER PRIMARY KEY` the
+/// rowid.
+fn create_table(
+    table: &str,
+    columns: &[ColumnDef],
+    keys: &[&ColumnDef],
+    options: &str,
+    constraints: &[String],
+    autoincrement: bool,
+) -> String {
+    let inline = match keys {
+        [key] if autoincrement => Some((key.name.as_str(), "PRIMARY KEY AUTOINCREMENT")),
+        [key] => Some((key.name.as_str(), "PRIMARY KEY")),
+        _ => None,
+    };
+    let mut definitions: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let primary_key = inline
+                .filter(|(name, _)| *name == column.name)
+                .map(|(_, sql)| sql);
+            column.sql(primary_key)
+        })
+        .collect();
+    if keys.len() > 1 {
+        definitions.push(format!(
+            "PRIMARY KEY ({})",
+            names(keys.iter().map(|c| &c.name))
+        ));
+    }
+    definitions.extend(constraints.iter().cloned());
+    let mut sql = format!(
+        "CREATE TABLE {} ({})",
+        Ident::new(table),
+        definitions.join(", ")
+    );
+    if !options.is_empty() {
+        sql.push(' ');
+        sql.push_str(options);
+    }
+    sql
+}
+
+/// SQLite's twelve step procedure for changes `ALTER TABLE` cannot make:
+/// creates the table anew with `columns`, each copied from its source column
+/// or left to its default, swaps it in and recreates the indexes and
+/// triggers. Unique constraints, indexes and foreign keys on dropped columns
+/// are dropped with them. `CHECK` constraints and collations are not carried
+/// over.
+fn rebuild(schema: &TableSchema, columns: Vec<(ColumnDef, Option<String>)>) -> Result<Vec<String>> {
+    if schema.columns.iter().any(|c| c.hidden) {
+        bail!(
+            "{} has generated columns and cannot be rebuilt",
+            schema.name
+        );
+    }
+    if columns.is_empty() {
+        bail!("{} would have no columns", schema.name);
+    }
+    let kept = |names: &[String]| {
+        names
+            .iter()
+            .all(|name| columns.iter().any(|(def, _)| def.name == *name))
+    };
+
+    // The existing key keeps its column order, new key columns follow.
+    let mut key_columns: Vec<&ColumnInfo> = schema
+        .columns
+        .iter()
+        .filter(|c| c.primary_key > 0)
+        .collect();
+    key_columns.sort_by_key(|c| c.primary_key);
+    let mut keys: Vec<&ColumnDef> = key_columns
+        .iter()
+        .filter_map(|info| {
+            columns
+                .iter()
+                .map(|(def, _)| def)
+                .find(|def| def.name == info.name && def.primary_key)
+        })
+        .collect();
+    for (def, _) in &columns {
+        if def.primary_key && !keys.iter().any(|key| key.name == def.name) {
+            keys.push(def);
+        }
+    }
+
+    let mut constraints = Vec::new();
+    for index in &schema.indexes {
+        if index.sql.is_none() && !index.primary_key && kept(&index.columns) {
+            constraints.push(format!("UNIQUE ({})", names(&index.columns)));
+        }
+    }
+    for key in &schema.foreign_keys {
+        if !kept(&key.from) {
+            continue;
+        }
+        let mut sql = format!(
+            "FOREIGN KEY ({}) REFERENCES {}",
+            names(&key.from),
+            Ident::new(&key.table)
+        );
+        if !key.to.is_empty() {
+            sql.push_str(&format!(" ({})", names(&key.to)));
+        }
+        for (event, action) in [("UPDATE", &key.on_update), ("DELETE", &key.on_delete)] {
+            if !action.is_empty() && action != "NO ACTION" {
+                sql.push_str(&format!(" ON {event} {action}"));
+            }
+        }
+        constraints.push(sql);
+    }
+
+    let options = schema.sql[schema.sql.rfind(')').map_or(0, |i| i + 1)..].trim();
+    let autoincrement = schema.sql.to_ascii_uppercase().contains("AUTOINCREMENT");
+    let temporary = format!("sqliters_new_{}", schema.name);
+    let defs: Vec<ColumnDef> = columns.iter().map(|(def, _)| def.clone()).collect();
+    let copied: Vec<(&String, &String)> = columns
+        .iter()
+        .filter_map(|(def, source)| source.as_ref().map(|source| (&def.name, source)))
+        .collect();
+
+    let mut statements = vec![create_table(
+        &temporary,
+        &defs,
+        &keys,
+        options,
+        &constraints,
+        autoincrement,
+    )];
+    if !copied.is_empty() {
+        statements.push(format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {}",
+            Ident::new(&temporary),
+            names(copied.iter().map(|(name, _)| *name)),
+            names(copied.iter().map(|(_, source)| *source)),
+            Ident::new(&schema.name)
+        ));
+    }
+    statements.push(format!("DROP TABLE {}", Ident::new(&schema.name)));
+    // Views on the table would fail the schema check of a modern rename.
+    statements.push("PRAGMA legacy_alter_table = ON".to_string());
+    statements.push(format!(
+        "ALTER TABLE {} RENAME TO {}",
+        Ident::new(&temporary),
+        Ident::new(&schema.name)
+    ));
+    statements.push("PRAGMA legacy_alter_table = OFF".to_string());
+    for index in &schema.indexes {
+        if let Some(sql) = &index.sql {
+            if kept(&index.columns) {
+                statements.push(sql.clone());
+            }
+        }
+    }
+    statements.extend(schema.triggers.iter().cloned());
+    Ok(statements)
+}
+
+/// Quoted, comma separated `names`.
+fn names<'a>(names: impl IntoIterator<Item = &'a String>) -> String {
+    names
+        .into_iter()
+        .map(|name| Ident::new(name).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Actions of the schema editor menu.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    CreateTable,
+    AddColumn,
+    AddGeneratedColumn,
+    RenameColumn,
+    ChangeColumn,
+    DropColumn,
+    CreateIndex,
+    DropIndex,
+    DropTable,
+}
+
+impl Action {
+    /// Actions available with the schema of the selected table, if any.
+    pub fn available(table: Option<&TableSchema>) -> Vec<Action> {
+        let Some(table) = table else {
+            return vec![Action::CreateTable];
+        };
+        let mut actions = vec![
+            Action::CreateTable,
+            Action::AddColumn,
+            Action::AddGeneratedColumn,
+            Action::RenameColumn,
+            Action::ChangeColumn,
+            Action::DropColumn,
+            Action::CreateIndex,
+        ];
+        if table.indexes.iter().any(|index| index.sql.is_some()) {
+            actions.push(Action::DropIndex);
+        }
+        actions.push(Action::DropTable);
+        actions
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::CreateTable => "Create table",
+            Action::AddColumn => "Add column",
+            Action::AddGeneratedColumn => "Add generated column",
+            Action::RenameColumn => "Rename column",
+            Action::ChangeColumn => "Change column",
+            Action::DropColumn => "Drop column",
+            Action::CreateIndex => "Create index",
+            Action::DropIndex => "Drop index",
+            Action::DropTable => "Drop table",
+        }
+    }
+}
+
+/// Input of a form field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Text(String),
+    /// One of `options`, cycled with the arrow keys.
+    Choice {
+        options: Vec<String>,
+        selected: usize,
+    },
+    Flag(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub label: String,
+    pub input: Input,
+}
+
+impl Field {
+    fn text(label: &str, value: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            input: Input::Text(value.to_string()),
+        }
+    }
+
+    fn choice(label: &str, options: Vec<String>, value: &str) -> Self {
+        let selected = options.iter().position(|o| o == value).unwrap_or(0);
+        Self {
+            label: label.to_string(),
+            input: Input::Choice { options, selected },
+        }
+    }
+
+    fn flag(label: &str, value: bool) -> Self {
+        Self {
+            label: label.to_string(),
+            input: Input::Flag(value),
+        }
+    }
+
+    /// Value shown in the form.
+    pub fn value(&self) -> String {
+        match &self.input {
+            Input::Text(text) => text.clone(),
+            Input::Choice { options, selected } => match options[*selected].as_str() {
+                "" => "(none)".to_string(),
+                option => option.to_string(),
+            },
+            Input::Flag(true) => "[x]".to_string(),
+            Input::Flag(false) => "[ ]".to_string(),
+        }
+    }
+}
+
+/// Fields per column of the create table form.
+const COLUMN_FIELDS: usize = 5;
+
+/// The form of a schema editor action, turned into a [`SchemaChange`] by
+/// [`SchemaForm::change`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaForm {
+    action: Action,
+    /// Schema of the altered table.
+    table: Option<TableSchema>,
+    fields: Vec<Field>,
+    selected: usize,
+}
+
+impl SchemaForm {
+    pub fn new(action: Action, table: Option<TableSchema>) -> Self {
+        let column_names = || -> Vec<String> {
+            table
+                .iter()
+                .flat_map(|t| &t.columns)
+                .map(|c| c.name.clone())
+                .collect()
+        };
+        let fields = match action {
+            Action::CreateTable => {
+                let mut fields = vec![Field::text("table", "")];
+                fields.extend(column_fields(1, &ColumnDef::default()));
+                fields
+            }
+            Action::AddColumn => column_fields(0, &ColumnDef::default()),
+            Action::AddGeneratedColumn => vec![
+                Field::text("name", ""),
+                Field::choice("type", TYPES.map(String::from).to_vec(), ""),
+                Field::text("expression", ""),
+            ],
+            Action::RenameColumn => vec![
+                Field::choice("column", column_names(), ""),
+                Field::text("new name", ""),
+            ],
+            Action::ChangeColumn => {
+                let first = table.as_ref().and_then(|t| t.columns.first());
+                let column = first.map(ColumnDef::from_info).unwrap_or_default();
+                let mut fields = column_fields(0, &column);
+                fields[0] = Field::choice("column", column_names(), &column.name);
+                fields
+            }
+            Action::DropColumn => vec![Field::choice("column", column_names(), "")],
+            Action::CreateIndex => vec![
+                Field::text("name", ""),
+                Field::text("columns", ""),
+                Field::flag("unique", false),
+                Field::text("where", ""),
+            ],
+            Action::DropIndex => {
+                let indexes = table
+                    .iter()
+                    .flat_map(|t| &t.indexes)
+                    .filter(|index| index.sql.is_some())
+                    .map(|index| index.name.clone())
+                    .collect();
+                vec![Field::choice("index", indexes, "")]
+            }
+            Action::DropTable => Vec::new(),
+        };
+        Self {
+            action,
+            table,
+            fields,
+            selected: 0,
+        }
+    }
+
+    /// The create index form for `columns` of `table`.
+    pub fn create_index(table: TableSchema, columns: &[String]) -> Self {
+        let mut form = Self::new(Action::CreateIndex, Some(table));
+        let name = form.table.as_ref().map_or("", |t| &t.name);
+        form.fields[0].input = Input::Text(index_name(name, columns));
+        form.fields[1].input = Input::Text(columns.join(", "));
+        form
+    }
+
+    /// The form adding a virtual column of `table` computed by
+    /// `expression`.
+    pub fn generated_column(table: TableSchema, name: &str, expression: &str) -> Self {
+        let mut form = Self::new(Action::AddGeneratedColumn, Some(table));
+        form.fields[0].input = Input::Text(name.to_string());
+        form.fields[2].input = Input::Text(expression.to_string());
+        form
+    }
+
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Schema of the altered table, `None` when creating one.
+    pub fn table(&self) -> Option<&TableSchema> {
+        self.table.as_ref()
+    }
+
+    pub fn next(&mut self) {
+        if !self.fields.is_empty() {
+            self.selected = (self.selected + 1) % self.fields.len();
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.fields.is_empty() {
+            self.selected = self
+                .selected
+                .checked_sub(1)
+                .unwrap_or(self.fields.len() - 1);
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        match self.fields.get_mut(self.selected).map(|f| &mut f.input) {
+            Some(Input::Text(text)) => text.push(c),
+            Some(Input::Flag(value)) if c == ' ' => *value = !*value,
+            _ => {}
+        }
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(Input::Text(text)) = self.fields.get_mut(self.selected).map(|f| &mut f.input) {
+            text.pop();
+        }
+    }
+
+    /// Moves a choice by `step` options, wrapping around.
+    pub fn cycle(&mut self, step: isize) {
+        let Some(Input::Choice { options, selected }) =
+            self.fields.get_mut(self.selected).map(|f| &mut f.input)
+        else {
+            return;
+        };
+        if options.is_empty() {
+            return;
+        }
+        *selected = (*selected as isize + step).rem_euclid(options.len() as isize) as usize;
+        // Changing a column starts from its current definition.
+        if self.action == Action::ChangeColumn && self.selected == 0 {
+            let name = options[*selected].clone();
+            let info = self
+                .table
+                .iter()
+                .flat_map(|t| &t.columns)
+                .find(|c| c.name == name);
+            if let Some(info) = info {
+                let mut fields = column_fields(0, &ColumnDef::from_info(info));
+                fields[0] = self.fields[0].clone();
+                self.fields = fields;
+            }
+        }
+    }
+
+    /// Adds a column to the create table form, selecting its name.
+    pub fn add_column(&mut self) {
+        if self.action == Action::CreateTable {
+            let n = (self.fields.len() - 1) / COLUMN_FIELDS + 1;
+            self.selected = self.fields.len();
+            self.fields.extend(column_fields(n, &ColumnDef::default()));
+        }
+    }
+
+    /// Removes the selected column of the create table form.
+    pub fn remove_column(&mut self) {
+        if self.action != Action::CreateTable || self.selected == 0 {
+            return;
+        }
+        if self.fields.len() <= 1 + COLUMN_FIELDS {
+            return;
+        }
+        let start = 1 + (self.selected - 1) / COLUMN_FIELDS * COLUMN_FIELDS;
+        self.fields.drain(start..start + COLUMN_FIELDS);
+        for (i, group) in self.fields[1..].chunks_mut(COLUMN_FIELDS).enumerate() {
+            for field in group {
+                let label = field.label.split_once(' ').map_or("", |(_, l)| l);
+                field.label = format!("{} {label}", i + 1);
+            }
+        }
+        self.selected = start.min(self.fields.len() - COLUMN_FIELDS);
+    }
+
+    /// The change described by the form.
+    pub fn change(&self) -> Result<SchemaChange> {
+        let table = || match &self.table {
+            Some(table) => Ok(table.name.clone()),
+            None => bail!("no table selected"),
+        };
+        let change = match self.action {
+            Action::CreateTable => {
+                let table = self.text(0);
+                if table.is_empty() {
+                    bail!("the table needs a name");
+                }
+                let columns: Vec<ColumnDef> = self.fields[1..]
+                    .chunks(COLUMN_FIELDS)
+                    .map(column_def)
+                    .filter(|column| !column.name.is_empty())
+                    .collect();
+                if columns.is_empty() {
+                    bail!("the table needs a column");
+                }
+                SchemaChange::CreateTable { table, columns }
+            }
+            Action::AddColumn => {
+                let column = column_def(&self.fields);
+                if column.name.is_empty() {
+                    bail!("the column needs a name");
+                }
+                SchemaChange::AddColumn {
+                    table: table()?,
+                    column,
+                }
+            }
+            Action::AddGeneratedColumn => {
+                let name = self.text(0);
+                if name.is_empty() {
+                    bail!("the column needs a name");
+                }
+                let expression = self.text(2);
+                if expression.is_empty() {
+                    bail!("the column needs an expression");
+                }
+                SchemaChange::AddColumn {
+                    table: table()?,
+                    column: ColumnDef {
+                        name,
+                        kind: self.choice(1)?,
+                        generated: Some(expression),
+                        ..ColumnDef::default()
+                    },
+                }
+            }
+            Action::RenameColumn => {
+                let to = self.text(1);
+                if to.is_empty() {
+                    bail!("the column needs a new name");
+                }
+                SchemaChange::RenameColumn {
+                    table: table()?,
+                    from: self.choice(0)?,
+                    to,
+                }
+            }
+            Action::ChangeColumn => SchemaChange::ChangeColumn {
+                table: table()?,
+                column: ColumnDef {
+                    name: self.choice(0)?,
+                    ..column_def(&self.fields)
+                },
+            },
+            Action::DropColumn => SchemaChange::DropColumn {
+                table: table()?,
+                column: self.choice(0)?,
+            },
+            Action::CreateIndex => {
+                let table = table()?;
+                let columns: Vec<String> = self
+                    .text(1)
+                    .split(',')
+                    .map(|column| column.trim().to_string())
+                    .filter(|column| !column.is_empty())
+                    .collect();
+                if columns.is_empty() {
+                    bail!("the index needs a column");
+                }
+                let known: Vec<&str> = self
+                    .table
+                    .iter()
+                    .flat_map(|t| &t.columns)
+                    .map(|c| c.name.as_str())
+                    .collect();
+                if let Some(missing) = columns.iter().find(|c| !known.contains(&c.as_str())) {
+                    bail!("{table} has no column {missing}");
+                }
+                let name = match self.text(0) {
+                    name if name.is_empty() => index_name(&table, &columns),
+                    name => name,
+                };
+                let condition = self.text(3);
+                SchemaChange::CreateIndex {
+                    table,
+                    name,
+                    columns,
+                    unique: matches!(self.fields[2].input, Input::Flag(true)),
+                    condition: (!condition.is_empty()).then_some(condition),
+                }
+            }
+            Action::DropIndex => SchemaChange::DropIndex(self.choice(0)?),
+            Action::DropTable => SchemaChange::DropTable(table()?),
+        };
+        Ok(change)
+    }
+
+    fn text(&self, field: usize) -> String {
+        match &self.fields[field].input {
+            Input::Text(text) => text.trim().to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn choice(&self, field: usize) -> Result<String> {
+        match &self.fields[field].input {
+            Input::Choice { options, selected } if !options.is_empty() => {
+                Ok(options[*selected].clone())
+            }
+            _ => bail!("nothing to choose from"),
+        }
+    }
+}
+
+/// Default name of an index on `columns` of `table`.
+fn index_name(table: &str, columns: &[String]) -> String {
+    let mut name = table.to_string();
+    for column in columns {
+        name.push('_');
+        name.push_str(column);
+    }
+    name
+}
+
+/// Fields of column `n` of the create table form, unnumbered for 0.
+fn column_fields(n: usize, column: &ColumnDef) -> Vec<Field> {
+    let label = |label: &str| match n {
+        0 => label.to_string(),
+        n => format!("{n} {label}"),
+    };
+    let types = TYPES.map(String::from).to_vec();
+    let mut kind = Field::choice(&label("type"), types.clone(), &column.kind);
+    if !column.kind.is_empty() && !types.contains(&column.kind) {
+        // Keep a declared type the list does not offer.
+        let mut options = types;
+        options.insert(0, column.kind.clone());
+        kind = Field::choice(&label("type"), options, &column.kind);
+    }
+    vec![
+        Field::text(&label("name"), &column.name),
+        kind,
+        Field::flag(&label("not null"), column.not_null),
+        Field::flag(&label("primary key"), column.primary_key),
+        Field::text(&label("default"), column.default.as_deref().unwrap_or("")),
+    ]
+}
+
+/// The column described by the fields of [`column_fields`].
+fn column_def(fields: &[Field]) -> ColumnDef {
+    let text = |i: usize| match &fields[i].input {
+        Input::Text(text) => text.trim().to_string(),
+        Input::Choice { options, selected } => options.get(*selected).cloned().unwrap_or_default(),
+        Input::Flag(_) => String::new(),
+    };
+    let flag = |i: usize| matches!(fields[i].input, Input::Flag(true));
+    let default = text(4);
+    ColumnDef {
+        name: text(0),
+        kind: text(1),
+        not_null: flag(2),
+        primary_key: flag(3),
+        default: (!default.is_empty()).then_some(default),
+        generated: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{ForeignKey, IndexInfo};
+
+    fn users() -> TableSchema {
+        let column = |name: &str, kind: &str, primary_key| ColumnInfo {
+            name: name.into(),
+            kind: kind.into(),
+            primary_key,
+            ..ColumnInfo::default()
+        };
+        TableSchema {
+            name: "users".into(),
+            sql: "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE, team INTEGER REFERENCES teams (id) ON DELETE CASCADE, age)".into(),
+            columns: vec![
+                column("id", "INTEGER", 1),
+                column("email", "TEXT", 0),
+                column("team", "INTEGER", 0),
+                column("age", "", 0),
+            ],
+            indexes: vec![
+                IndexInfo {
+                    name: "sqlite_autoindex_users_1".into(),
+                    columns: vec!["email".into()],
+                    unique: true,
+                    ..IndexInfo::default()
+                },
+                IndexInfo {
+                    name: "users_age".into(),
+                    columns: vec!["age".into()],
+                    sql: Some("CREATE INDEX users_age ON users (age)".into()),
+                    ..IndexInfo::default()
+                },
+            ],
+            foreign_keys: vec![ForeignKey {
+                table: "teams".into(),
+                from: vec!["team".into()],
+                to: vec!["id".into()],
+                on_update: "NO ACTION".into(),
+                on_delete: "CASCADE".into(),
+            }],
+            triggers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn alter_statements() {
+        let schema = users();
+        let change = SchemaChange::AddColumn {
+            table: "users".into(),
+            column: ColumnDef {
+                name: "name".into(),
+                kind: "TEXT".into(),
+                not_null: true,
+                default: Some("''".into()),
+                ..ColumnDef::default()
+            },
+        };
+        assert_eq!(
+            change.statements(Some(&schema)).unwrap(),
+            [r#"ALTER TABLE "users" ADD COLUMN "name" TEXT NOT NULL DEFAULT ''"#]
+        );
+
+        let drop = |column: &str| SchemaChange::DropColumn {
+            table: "users".into(),
+            column: column.into(),
+        };
+        assert!(!drop("team2").rebuilds(&schema));
+        assert!(drop("age").rebuilds(&schema));
+        assert!(drop("team").rebuilds(&schema));
+        assert_eq!(
+            drop("missing").statements(Some(&schema)).unwrap(),
+            [r#"ALTER TABLE "users" DROP COLUMN "missing""#]
+        );
+        assert!(drop("age").statements(None).is_err());
+    }
+
+    #[test]
+    fn rebuild_statements() {
+        let schema = users();
+        let change = SchemaChange::DropColumn {
+            table: "users".into(),
+            column: "age".into(),
+        };
+        assert_eq!(
+            change.statements(Some(&schema)).unwrap(),
+            [
+                r#"CREATE TABLE "sqliters_new_users" ("id" INTEGER PRIMARY KEY, "email" TEXT, "team" INTEGER, UNIQUE ("email"), FOREIGN KEY ("team") REFERENCES "teams" ("id") ON DELETE CASCADE)"#,
+                r#"INSERT INTO "sqliters_new_users" ("id", "email", "team") SELECT "id", "email", "team" FROM "users""#,
+                r#"DROP TABLE "users""#,
+                "PRAGMA legacy_alter_table = ON",
+                r#"ALTER TABLE "sqliters_new_users" RENAME TO "users""#,
+                "PRAGMA legacy_alter_table = OFF",
+            ]
+        );
+
+        let change = SchemaChange::ChangeColumn {
+            table: "users".into(),
+            column: ColumnDef {
+                name: "team".into(),
+                primary_key: true,
+                ..ColumnDef::default()
+            },
+        };
+        let statements = change.statements(Some(&schema)).unwrap();
+        assert!(statements[0].ends_with(
+            r#""team", "age", PRIMARY KEY ("id", "team"), UNIQUE ("email"), FOREIGN KEY ("team") REFERENCES "teams" ("id") ON DELETE CASCADE)"#
+        ));
+        assert_eq!(statements[6], "CREATE INDEX users_age ON users (age)");
+
+        let mut schema = users();
+        schema.sql.push_str(" WITHOUT ROWID");
+        schema.columns[3].hidden = true;
+        assert!(change.statements(Some(&schema)).is_err());
+        schema.columns[3].hidden = false;
+        assert!(change.statements(Some(&schema)).unwrap()[0].ends_with(") WITHOUT ROWID"));
+    }
+
+    #[test]
+    fn create_table_form() {
+        let mut form = SchemaForm::new(Action::CreateTable, None);
+        assert!(form.change().is_err());
+        "my table".chars().for_each(|c| form.push(c));
+        form.next();
+        "id".chars().for_each(|c| form.push(c));
+        form.next();
+        form.cycle(1);
+        form.next();
+        form.next();
+        form.push(' ');
+        form.add_column();
+        "name".chars().for_each(|c| form.push(c));
+        form.next();
+        form.cycle(3);
+        form.next();
+        form.push(' ');
+        form.add_column();
+        assert_eq!(form.fields()[form.selected()].label, "3 name");
+        form.remove_column();
+        assert_eq!(form.fields().len(), 11);
+
+        let change = form.change().unwrap();
+        assert_eq!(
+            change.statements(None).unwrap(),
+            [r#"CREATE TABLE "my table" ("id" INTEGER PRIMARY KEY, "name" TEXT NOT NULL)"#]
+        );
+    }
+
+    #[test]
+    fn create_index_form() {
+        let mut form = SchemaForm::create_index(users(), &["team".into(), "age".into()]);
+        assert_eq!(form.fields()[0].value(), "users_team_age");
+        form.next();
+        form.next();
+        form.push(' ');
+        form.next();
+        "age > 18".chars().for_each(|c| form.push(c));
+        assert_eq!(
+            form.change().unwrap().statements(None).unwrap(),
+            [r#"CREATE UNIQUE INDEX "users_team_age" ON "users" ("team", "age") WHERE age > 18"#]
+        );
+
+        let mut form = SchemaForm::new(Action::CreateIndex, Some(users()));
+        assert!(form.change().is_err());
+        form.next();
+        "email, nope".chars().for_each(|c| form.push(c));
+        assert_eq!(
+            form.change().unwrap_err().to_string(),
+            "users has no column nope"
+        );
+    }
+
+    #[test]
+    fn generated_column_form() {
+        let form =
+            SchemaForm::generated_column(users(), "city", "json_extract(\"data\", '$.city')");
+        assert_eq!(
+            form.change().unwrap().statements(Some(&users())).unwrap(),
+            [
+                r#"ALTER TABLE "users" ADD COLUMN "city" GENERATED ALWAYS AS (json_extract("data", '$.city')) VIRTUAL"#
+            ]
+        );
+
+        let mut form = SchemaForm::new(Action::AddGeneratedColumn, Some(users()));
+        "total".chars().for_each(|c| form.push(c));
+        assert_eq!(
+            form.change().unwrap_err().to_string(),
+            "the column needs an expression"
+        );
+    }
+
+    #[test]
+    fn change_column_form() {
+        let mut form = SchemaForm::new(Action::ChangeColumn, Some(users()));
+        assert_eq!(form.fields()[1].value(), "INTEGER");
+        assert_eq!(form.fields()[3].value(), "[x]");
+        form.cycle(-1);
+        assert_eq!(form.fields()[0].value(), "age");
+        assert_eq!(form.fields()[1].value(), "(none)");
+        form.next();
+        form.cycle(3);
+        assert_eq!(
+            form.change().unwrap(),
+            SchemaChange::ChangeColumn {
+                table: "users".into(),
+                column: ColumnDef {
+                    name: "age".into(),
+                    kind: "TEXT".into(),
+                    ..ColumnDef::default()
+                }
+            }
+        );
+
+        assert_eq!(
+            Action::available(Some(&users())).last(),
+            Some(&Action::DropTable)
+        );
+        assert_eq!(Action::available(None), [Action::CreateTable]);
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime::TimeFormat, filter::Filter, store::Store};
+
+const DIR_NAME: &str = "settings";
+
+/// Settings of the tables of one database file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    /// Saved filter per table.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    filters: BTreeMap<String, Filter>,
+    /// Time format set per table and column, overriding the detected one.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    time_formats: BTreeMap<String, BTreeMap<String, TimeFormat>>,
+}
+
+/// Filters and column display settings of the tables, persisted per
+/// database file in the `settings` directory of the data directory.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    store: Store,
+    entry: Entry,
+}
+
+impl Settings {
+    /// Loads the settings of the database at `database` from the data
+    /// directory.
+    pub fn load(database: &str) -> Result<Self> {
+        Self::load_from(Store::open(DIR_NAME, database))
+    }
+
+    fn load_from(store: Store) -> Result<Self> {
+        let entry = store.load()?;
+        Ok(Self { store, entry })
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.entry)
+    }
+
+    /// Saved filter of `table`.
+    pub fn filter(&self, table: &str) -> Option<&Filter> {
+        self.entry.filters.get(table)
+    }
+
+    /// Saves `filter` for `table`, removing the saved one when empty.
+    pub fn save_filter(&mut self, table: &str, filter: &Filter) -> Result<()> {
+        if filter.is_empty() {
+            self.entry.filters.remove(table);
+        } else {
+            self.entry.filters.insert(table.to_string(), filter.clone());
+        }
+        self.save()
+    }
+
+    /// Time format set for `column` of `table`.
+    pub fn time_format(&self, table: &str, column: &str) -> Option<TimeFormat> {
+        self.entry.time_formats.get(table)?.get(column).copied()
+    }
+
+    /// Saves the time format of `column` of `table`, or removes it for
+    /// `None` to detect it again.
+    pub fn save_time_format(
+        &mut self,
+        table: &str,
+        column: &str,
+        format: Option<TimeFormat>,
+    ) -> Result<()> {
+        let formats = self
+            .entry
+            .time_formats
+            .entry(table.to_string())
+            .or_default();
+        match format {
+            Some(format) => formats.insert(column.to_string(), format),
+            None => formats.remove(column),
+        };
+        if formats.is_empty() {
+            self.entry.time_formats.remove(table);
+        }
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filter::{Condition, Join, Operator},
+        store,
+    };
+
+    fn load(dir: &std::path::Path, database: &str) -> Settings {
+        Settings::load_from(Store::at(dir, database)).unwrap()
+    }
+
+    #[test]
+    fn persist_filters() {
+        let dir = store::temp_dir("filters", DIR_NAME);
+        let filter = Filter {
+            conditions: vec![Condition {
+                join: Join::And,
+                column: "city".into(),
+                path: None,
+                operator: Operator::Eq,
+                value: "Oslo".into(),
+            }],
+        };
+        let mut settings = load(&dir, "a.db");
+        settings.save_filter("users", &filter).unwrap();
+
+        let mut settings = load(&dir, "a.db");
+        assert_eq!(settings.filter("users"), Some(&filter));
+        assert_eq!(settings.filter("orders"), None);
+        assert_eq!(load(&dir, "b.db").filter("users"), None);
+        settings.save_filter("users", &Filter::default()).unwrap();
+        assert_eq!(load(&dir, "a.db").filter("users"), None);
+    }
+
+    #[test]
+    fn persist_time_formats() {
+        let dir = store::temp_dir("time_formats", DIR_NAME);
+        let mut settings = load(&dir, "a.db");
+        settings
+            .save_time_format("events", "at", Some(TimeFormat::Millis))
+            .unwrap();
+
+        let mut settings = load(&dir, "a.db");
+        assert_eq!(
+            settings.time_format("events", "at"),
+            Some(TimeFormat::Millis)
+        );
+        assert_eq!(settings.time_format("events", "id"), None);
+        settings.save_time_format("events", "at", None).unwrap();
+        assert_eq!(load(&dir, "a.db").time_format("events", "at"), None);
+    }
+}
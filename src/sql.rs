@@ -0,0 +1,508 @@
+use crate::database::Ident;
+
+/// SQLite keywords, sorted for binary search.
+const KEYWORDS: &[&str] = &[
+    "ABORT",
+    "ACTION",
+    "ADD",
+    "AFTER",
+    "ALL",
+    "ALTER",
+    "ALWAYS",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "ATTACH",
+    "AUTOINCREMENT",
+    "BEFORE",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASCADE",
+    "CASE",
+    "CAST",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "CONFLICT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "CURRENT",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "DATABASE",
+    "DEFAULT",
+    "DEFERRABLE",
+    "DEFERRED",
+    "DELETE",
+    "DESC",
+    "DETACH",
+    "DISTINCT",
+    "DO",
+    "DROP",
+    "EACH",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXCEPT",
+    "EXCLUDE",
+    "EXCLUSIVE",
+    "EXISTS",
+    "EXPLAIN",
+    "FAIL",
+    "FILTER",
+    "FIRST",
+    "FOLLOWING",
+    "FOR",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "GENERATED",
+    "GLOB",
+    "GROUP",
+    "GROUPS",
+    "HAVING",
+    "IF",
+    "IGNORE",
+    "IMMEDIATE",
+    "IN",
+    "INDEX",
+    "INDEXED",
+    "INITIALLY",
+    "INNER",
+    "INSERT",
+    "INSTEAD",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "ISNULL",
+    "JOIN",
+    "KEY",
+    "LAST",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "MATCH",
+    "MATERIALIZED",
+    "NATURAL",
+    "NO",
+    "NOT",
+    "NOTHING",
+    "NOTNULL",
+    "NULL",
+    "NULLS",
+    "OF",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OTHERS",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "PLAN",
+    "PRAGMA",
+    "PRECEDING",
+    "PRIMARY",
+    "QUERY",
+    "RAISE",
+    "RANGE",
+    "RECURSIVE",
+    "REFERENCES",
+    "REGEXP",
+    "REINDEX",
+    "RELEASE",
+    "RENAME",
+    "REPLACE",
+    "RESTRICT",
+    "RETURNING",
+    "RIGHT",
+    "ROLLBACK",
+    "ROW",
+    "ROWID",
+    "ROWS",
+    "SAVEPOINT",
+    "SELECT",
+    "SET",
+    "STORED",
+    "STRICT",
+    "TABLE",
+    "TEMP",
+    "TEMPORARY",
+    "THEN",
+    "TIES",
+    "TO",
+    "TRANSACTION",
+    "TRIGGER",
+    "UNBOUNDED",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VACUUM",
+    "VALUES",
+    "VIEW",
+    "VIRTUAL",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+    "WITHOUT",
+];
+
+/// Keywords after which a table name is expected.
+const TABLE_KEYWORDS: &[&str] = &["FROM", "INTO", "JOIN", "TABLE", "UPDATE"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    String,
+    Number,
+    Comment,
+    Operator,
+    Whitespace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+}
+
+impl Token<'_> {
+    /// Whether the token is the keyword `keyword`, compared case
+    /// insensitively.
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Keyword && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    /// Name of an identifier token without its quotes.
+    fn name(&self) -> String {
+        let text = self.text;
+        match text.chars().next() {
+            Some('"') => unquote(text, '"'),
+            Some('`') => unquote(text, '`'),
+            Some('[') => text
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            _ => text.to_string(),
+        }
+    }
+}
+
+fn unquote(text: &str, quote: char) -> String {
+    let inner = &text[1..];
+    let inner = inner.strip_suffix(quote).unwrap_or(inner);
+    inner.replace(&format!("{quote}{quote}"), &quote.to_string())
+}
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS
+        .binary_search(&word.to_ascii_uppercase().as_str())
+        .is_ok()
+}
+
+/// Splits `sql` into tokens. Concatenating the token texts gives back the
+/// input, so unterminated strings and comments run to the end.
+pub fn tokenize(sql: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        let (kind, len) = match c {
+            c if c.is_whitespace() => (
+                TokenKind::Whitespace,
+                rest.find(|c: char| !c.is_whitespace())
+                    .unwrap_or(rest.len()),
+            ),
+            '-' if rest.starts_with("--") => {
+                (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+            }
+            '/' if rest.starts_with("/*") => (
+                TokenKind::Comment,
+                rest[2..].find("*/").map_or(rest.len(), |i| i + 4),
+            ),
+            '\'' => (TokenKind::String, quoted_len(rest, '\'')),
+            '"' | '`' => (TokenKind::Identifier, quoted_len(rest, c)),
+            '[' => (
+                TokenKind::Identifier,
+                rest.find(']').map_or(rest.len(), |i| i + 1),
+            ),
+            c if c.is_ascii_digit()
+                || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) =>
+            {
+                (TokenKind::Number, number_len(rest))
+            }
+            c if is_word_char(c) => {
+                let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+                let kind = if is_keyword(&rest[..len]) {
+                    TokenKind::Keyword
+                } else {
+                    TokenKind::Identifier
+                };
+                (kind, len)
+            }
+            c => (TokenKind::Operator, c.len_utf8()),
+        };
+        tokens.push(Token {
+            kind,
+            text: &rest[..len],
+        });
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Length of a token quoted with `quote`, where a doubled quote escapes it.
+fn quoted_len(text: &str, quote: char) -> usize {
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            if chars.peek().is_some_and(|(_, c)| *c == quote) {
+                chars.next();
+            } else {
+                return i + 1;
+            }
+        }
+    }
+    text.len()
+}
+
+fn number_len(text: &str) -> usize {
+    if text.starts_with("0x") || text.starts_with("0X") {
+        return 2 + text[2..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(text.len() - 2);
+    }
+    let mut previous = ' ';
+    text.find(|c: char| {
+        let end = !(c.is_ascii_digit()
+            || c == '.'
+            || c == 'e'
+            || c == 'E'
+            || ((c == '+' || c == '-') && (previous == 'e' || previous == 'E')));
+        previous = c;
+        end
+    })
+    .unwrap_or(text.len())
+}
+
+/// What the word under the cursor should be completed with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Context {
+    Table,
+    /// Columns of the named table or alias, or of all tables the statement
+    /// refers to.
+    Column(Option<String>),
+}
+
+/// Completion context for the statement `before` the cursor, together with
+/// the partial word being completed.
+pub fn context(before: &str) -> (Context, &str) {
+    let tokens = tokenize(before);
+    let mut significant = tokens
+        .iter()
+        .filter(|t| !matches!(t.kind, TokenKind::Whitespace | TokenKind::Comment));
+
+    let prefix = match tokens.last() {
+        Some(t)
+            if matches!(t.kind, TokenKind::Identifier | TokenKind::Keyword)
+                && t.text.starts_with(is_word_char) =>
+        {
+            significant.next_back();
+            t.text
+        }
+        _ => "",
+    };
+
+    let mut significant = significant.rev();
+    if let Some(dot) = significant.next() {
+        if dot.text == "." {
+            let qualifier = significant
+                .next()
+                .filter(|t| t.kind == TokenKind::Identifier)
+                .map(Token::name);
+            return (Context::Column(qualifier), prefix);
+        }
+        if dot.kind == TokenKind::Keyword {
+            return (keyword_context(dot), prefix);
+        }
+    }
+    let keyword = significant.find(|t| t.kind == TokenKind::Keyword);
+    match keyword {
+        // `FROM a, b` continues the table list.
+        Some(k) if keyword_context(k) == Context::Table && before.trim_end().ends_with(',') => {
+            (Context::Table, prefix)
+        }
+        _ => (Context::Column(None), prefix),
+    }
+}
+
+fn keyword_context(token: &Token) -> Context {
+    if TABLE_KEYWORDS.iter().any(|k| token.is_keyword(k)) {
+        Context::Table
+    } else {
+        Context::Column(None)
+    }
+}
+
+/// Tables named in `FROM`, `JOIN`, `INTO` and `UPDATE` clauses of `sql`,
+/// each with its alias if it has one.
+pub fn referenced_tables(sql: &str) -> Vec<(String, Option<String>)> {
+    let tokens: Vec<Token> = tokenize(sql)
+        .into_iter()
+        .filter(|t| !matches!(t.kind, TokenKind::Whitespace | TokenKind::Comment))
+        .collect();
+    let mut tables = Vec::new();
+    let mut in_table_list = false;
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        i += 1;
+        if token.kind == TokenKind::Keyword {
+            in_table_list = TABLE_KEYWORDS.iter().any(|k| token.is_keyword(k));
+            continue;
+        }
+        if !in_table_list || token.kind != TokenKind::Identifier {
+            continue;
+        }
+        if tokens.get(i).is_some_and(|t| t.text == "(") {
+            // A function call like `json_each(...)`.
+            continue;
+        }
+        if tokens.get(i).is_some_and(|t| t.text == ".") {
+            // Schema qualified name, the table follows the dot.
+            continue;
+        }
+        if tokens.get(i).is_some_and(|t| t.is_keyword("AS")) {
+            i += 1;
+        }
+        let alias = tokens
+            .get(i)
+            .filter(|t| t.kind == TokenKind::Identifier)
+            .map(Token::name);
+        if alias.is_some() {
+            i += 1;
+        }
+        tables.push((token.name(), alias));
+        in_table_list = tokens.get(i).is_some_and(|t| t.text == ",");
+    }
+    tables
+}
+
+/// `name` as it has to be written in a statement.
+pub fn quote_identifier(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain && !is_keyword(name) {
+        name.to_string()
+    } else {
+        Ident::new(name).to_string()
+    }
+}
+
+/// Keywords starting with `prefix`, case insensitively.
+pub fn keywords(prefix: &str) -> impl Iterator<Item = &'static str> + '_ {
+    KEYWORDS
+        .iter()
+        .copied()
+        .filter(move |k| starts_with_ignore_case(k, prefix))
+}
+
+pub fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_are_sorted() {
+        assert!(KEYWORDS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn tokenize_statements() {
+        let sql = "SELECT \"a\"\"b\", 'it''s' -- note\nFROM t WHERE x >= 1.5e-3 /* c */";
+        let tokens: Vec<(TokenKind, &str)> = tokenize(sql)
+            .into_iter()
+            .filter(|t| t.kind != TokenKind::Whitespace)
+            .map(|t| (t.kind, t.text))
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Keyword, "SELECT"),
+                (TokenKind::Identifier, "\"a\"\"b\""),
+                (TokenKind::Operator, ","),
+                (TokenKind::String, "'it''s'"),
+                (TokenKind::Comment, "-- note"),
+                (TokenKind::Keyword, "FROM"),
+                (TokenKind::Identifier, "t"),
+                (TokenKind::Keyword, "WHERE"),
+                (TokenKind::Identifier, "x"),
+                (TokenKind::Operator, ">"),
+                (TokenKind::Operator, "="),
+                (TokenKind::Number, "1.5e-3"),
+                (TokenKind::Comment, "/* c */"),
+            ]
+        );
+        let unterminated = "SELECT 'abc";
+        let text: String = tokenize(unterminated).iter().map(|t| t.text).collect();
+        assert_eq!(text, unterminated);
+    }
+
+    #[test]
+    fn completion_context() {
+        assert_eq!(context("SELECT * FROM us"), (Context::Table, "us"));
+        assert_eq!(context("SELECT * FROM "), (Context::Table, ""));
+        assert_eq!(context("SELECT * FROM a, "), (Context::Table, ""));
+        assert_eq!(
+            context("SELECT * FROM a JOIN b ON a.i"),
+            (Context::Column(Some("a".into())), "i")
+        );
+        assert_eq!(
+            context("SELECT \"my t\"."),
+            (Context::Column(Some("my t".into())), "")
+        );
+        assert_eq!(
+            context("SELECT * FROM a WHERE na"),
+            (Context::Column(None), "na")
+        );
+        assert_eq!(context("SEL"), (Context::Column(None), "SEL"));
+    }
+
+    #[test]
+    fn find_referenced_tables() {
+        assert_eq!(
+            referenced_tables("SELECT * FROM users u, \"order items\" AS o JOIN tags ON 1"),
+            [
+                ("users".to_string(), Some("u".to_string())),
+                ("order items".to_string(), Some("o".to_string())),
+                ("tags".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            referenced_tables("SELECT * FROM main.users WHERE id = 1"),
+            [("users".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn quote_identifiers() {
+        assert_eq!(quote_identifier("users"), "users");
+        assert_eq!(quote_identifier("order"), "\"order\"");
+        assert_eq!(quote_identifier("my table"), "\"my table\"");
+    }
+}
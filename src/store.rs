@@ -0,0 +1,87 @@
+//! JSON files in the data directory holding the entry of one database file
+//! each, named after a hash of its canonical path.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+
+/// File holding the entry of one database.
+#[derive(Debug, Clone, Default)]
+pub struct Store {
+    /// Storage file, `None` keeps the entry in memory only.
+    file: Option<PathBuf>,
+}
+
+impl Store {
+    /// The entry of the database at `database` in the directory `name` of
+    /// the data directory.
+    pub fn open(name: &str, database: &str) -> Self {
+        match data_dir() {
+            Some(dir) => Self::at(&dir.join(name), database),
+            None => Self::default(),
+        }
+    }
+
+    /// The entry of the database at `database` in `dir`. Every database has
+    /// a file of its own, so that instances open on different databases
+    /// never overwrite each other's entries.
+    pub fn at(dir: &Path, database: &str) -> Self {
+        let database = fs::canonicalize(database)
+            .map_or_else(|_| database.to_string(), |p| p.display().to_string());
+        let name = hex::encode(Sha256::digest(database.as_bytes()));
+        Self {
+            file: Some(dir.join(name).with_extension("json")),
+        }
+    }
+
+    /// Reads the entry of this database, the default when there is none.
+    pub fn load<T: DeserializeOwned + Default>(&self) -> Result<T> {
+        let Some(file) = &self.file else {
+            return Ok(T::default());
+        };
+        match fs::read_to_string(file) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes this database's entry back.
+    pub fn save(&self, entry: &impl Serialize) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Written next to the file and renamed over it, so that a crash or
+        // another instance never sees it half written.
+        let temp = file.with_extension(format!("json.{}.tmp", std::process::id()));
+        fs::write(&temp, serde_json::to_string_pretty(entry)?)?;
+        if let Err(err) = fs::rename(&temp, file) {
+            let _ = fs::remove_file(&temp);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
+
+/// `sqliters` in the platform's data directory: `$XDG_DATA_HOME` or
+/// `~/.local/share` on Linux, `~/Library/Application Support` on macOS and
+/// the roaming `AppData` on Windows.
+fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("sqliters"))
+}
+
+/// Path of the directory `name` in a fresh temporary directory for tests.
+#[cfg(test)]
+pub fn temp_dir(name: &str, dir: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("sqliters-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    root.join(dir)
+}
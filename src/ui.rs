@@ -1,41 +1,138 @@
 use crate::{
-    database::Database,
-    model::{Model, ViewState, ITEM_HEIGHT, MAX_TABLE_ITEMS},
+    database::{ColumnStats, Database, DatabaseInfo, Maintenance},
+    filter::{Field, FilterForm},
+    json::JsonView,
+    model::{
+        MaintenanceResult, MaintenanceView, Model, SchemaView, SortOrder, ViewState, ITEM_HEIGHT,
+        MAX_TABLE_ITEMS,
+    },
+    output::{self, Format},
+    plan::{Access, Plan},
     popup::Popup,
+    schema::{self as schema_editor, SchemaForm},
+    sql::{self, TokenKind},
 };
 use ratatui::{
-    layout::{Constraint, Layout, Margin, Rect},
+    layout::{Constraint, Layout, Margin, Position, Rect},
     style::{Modifier, Style, Stylize},
-    text::Text,
+    text::{Line, Span, Text},
     widgets::{
-        Block, BorderType, Cell, HighlightSpacing, Paragraph, Row, Scrollbar, ScrollbarOrientation,
-        Table,
+        Block, BorderType, Cell, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph,
+        Row, Scrollbar, ScrollbarOrientation, Table,
     },
     Frame,
 };
+use serde_json::Value;
+use std::{borrow::Cow, time::Duration};
+
+const HIGHLIGHT_WIDTH: u16 = 3;
+/// How long the watch indicator stays highlighted after a change.
+const UPDATED_HIGHLIGHT: Duration = Duration::from_secs(5);
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Screen element under a mouse position, resolved against the last drawn
+/// frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hit {
+    Header(usize),
+    Cell { row: usize, column: usize },
+    Scrollbar { position: usize, length: usize },
+}
+
+/// Areas of the last drawn frame, kept around for mouse hit testing.
+#[derive(Debug, Default)]
+struct Geometry {
+    table: Rect,
+    scrollbar: Rect,
+    columns: Vec<Rect>,
+    first_row: usize,
+    row_count: usize,
+}
 
 #[derive(Debug, Default)]
-pub struct UserInterface {}
+pub struct UserInterface {
+    geometry: Geometry,
+}
 
 impl UserInterface {
     pub fn new() -> UserInterface {
-        UserInterface {}
+        UserInterface::default()
     }
 
-    pub fn run<D: Database>(&self, frame: &mut Frame, model: &Model<D>) {
-        let _ = self;
-
+    pub fn run<D: Database>(&mut self, frame: &mut Frame, model: &Model<D>) {
         let schema = model.is_schema_enabled();
-        let rects =
-            Layout::vertical([Constraint::Min(5), Constraint::Length(3)]).split(frame.area());
+        let filter_height = u16::from(model.active_filter().is_some());
+        let stats_height = u16::from(model.stats().is_some());
+        let rects = Layout::vertical([
+            Constraint::Length(filter_height),
+            Constraint::Min(5),
+            Constraint::Length(stats_height),
+            Constraint::Length(3),
+        ])
+        .split(frame.area());
+
+        Self::render_filter(frame, model, rects[0]);
 
-        Self::render_table(frame, model, rects[0]);
+        self.render_table(frame, model, rects[1]);
 
-        Self::render_scrollbar(frame, model, rects[0]);
+        self.render_scrollbar(frame, model, rects[1]);
 
-        Self::render_footer(frame, model, rects[1]);
+        Self::render_stats(frame, model, rects[2]);
+
+        if model.editor().is_some() {
+            Self::render_editor(frame, model, rects[3]);
+        } else {
+            Self::render_footer(frame, model, rects[3]);
+        }
 
         Self::render_popup(frame, model, schema);
+
+        Self::render_record(frame, model);
+
+        Self::render_picker(frame, model);
+
+        Self::render_plan(frame, model);
+
+        Self::render_column_stats(frame, model);
+
+        Self::render_json(frame, model);
+
+        Self::render_filter_form(frame, model);
+
+        Self::render_maintenance(frame, model);
+
+        Self::render_schema_editor(frame, model);
+    }
+
+    /// Number of rows that fit into the last drawn table area.
+    pub fn page_size(&self) -> usize {
+        usize::from(self.geometry.table.height.saturating_sub(1) / ITEM_HEIGHT).max(1)
+    }
+
+    pub fn hit_test(&self, column: u16, row: u16) -> Option<Hit> {
+        let position = Position::new(column, row);
+        let geometry = &self.geometry;
+
+        if geometry.scrollbar.contains(position) {
+            return Some(Hit::Scrollbar {
+                position: usize::from(row - geometry.scrollbar.y),
+                length: usize::from(geometry.scrollbar.height),
+            });
+        }
+        if !geometry.table.contains(position) {
+            return None;
+        }
+
+        let column = geometry
+            .columns
+            .iter()
+            .position(|rect| column >= rect.x && column < rect.right())?;
+        if row == geometry.table.y {
+            return Some(Hit::Header(column));
+        }
+
+        let row = geometry.first_row + usize::from((row - geometry.table.y - 1) / ITEM_HEIGHT);
+        (row < geometry.row_count).then_some(Hit::Cell { row, column })
     }
 
     fn visible_range(index: usize, total: usize) -> (usize, usize) {
@@ -45,7 +142,7 @@ impl UserInterface {
         (start, end)
     }
 
-    fn render_table<D: Database>(frame: &mut Frame, model: &Model<D>, area: Rect) {
+    fn render_table<D: Database>(&mut self, frame: &mut Frame, model: &Model<D>, area: Rect) {
         let header_style = Style::default().bg(model.colors().header_bg);
         let selected_style = Style::default()
             .add_modifier(Modifier::REVERSED)
@@ -65,24 +162,35 @@ impl UserInterface {
                     Style::default().fg(model.colors().selected_style_fg)
                 };
 
-                Cell::from(Text::from(header.clone()).centered()).style(header_style)
+                let mut title = match model.sort() {
+                    Some((column, SortOrder::Ascending)) if column == i => format!("{header} ▲"),
+                    Some((column, SortOrder::Descending)) if column == i => format!("{header} ▼"),
+                    _ => header.clone(),
+                };
+                if let Some(n) = model.marked_columns().iter().position(|&c| c == i) {
+                    title = format!("[{}] {title}", n + 1);
+                }
+
+                Cell::from(Text::from(title).centered()).style(header_style)
             });
         let header = Row::new(cells).style(header_style).height(1);
 
         let mut table_state = *model.state();
         let index = model.state().selected().unwrap_or(0);
-        let local_index = match model.view_state() {
-            ViewState::Main => index,
-            ViewState::Table => {
-                let (start, _) = Self::visible_range(index, model.get_table_rows().len());
-                index - start
+        let start = match model.view_state() {
+            ViewState::Main => 0,
+            ViewState::Table | ViewState::Query | ViewState::Report => {
+                Self::visible_range(index, model.get_table_rows().len()).0
             }
         };
+        let local_index = index - start;
 
         table_state.select(Some(local_index));
         let rows = match model.view_state() {
             ViewState::Main => Self::render_main_state(model, highlight_column_style),
-            ViewState::Table => Self::render_table_state(model, highlight_column_style),
+            ViewState::Table | ViewState::Query | ViewState::Report => {
+                Self::render_table_state(model, highlight_column_style)
+            }
         };
 
         let constraints: Vec<_> = (0..model.get_table_columns().len())
@@ -94,6 +202,17 @@ impl UserInterface {
                 }
             })
             .collect();
+
+        // Mirrors the column layout ratatui computes for the table so clicks
+        // can be mapped back to columns.
+        let [_, columns_area] =
+            Layout::horizontal([Constraint::Length(HIGHLIGHT_WIDTH), Constraint::Fill(0)])
+                .areas(area);
+        let columns = Layout::horizontal(&constraints)
+            .spacing(1)
+            .split(columns_area)
+            .to_vec();
+
         let bar = " █ ";
         let t = Table::new(rows, constraints)
             .header(header)
@@ -107,6 +226,11 @@ impl UserInterface {
             .bg(model.colors().buffer_bg)
             .highlight_spacing(HighlightSpacing::Always);
         frame.render_stateful_widget(t, area, &mut table_state);
+
+        self.geometry.table = area;
+        self.geometry.columns = columns;
+        self.geometry.first_row = start + table_state.offset();
+        self.geometry.row_count = model.item_count();
     }
 
     fn render_main_state<D: Database>(
@@ -158,6 +282,7 @@ impl UserInterface {
         let rows = model.get_table_rows();
         let total_rows = rows.len();
         let (start, end) = Self::visible_range(index, total_rows);
+        let formats = model.time_formats();
 
         rows.iter()
             .enumerate()
@@ -177,10 +302,8 @@ impl UserInterface {
                         Style::default().fg(model.colors().row_fg).bg(color)
                     };
 
-                    Cell::from(
-                        Text::from(format!("\n{}\n", cell.as_str().unwrap_or("NULL"))).centered(),
-                    )
-                    .style(cell_style)
+                    let text = model.cell_text(&formats, i, cell.as_str().unwrap_or("NULL"));
+                    Cell::from(Text::from(format!("\n{text}\n")).centered()).style(cell_style)
                 });
 
                 Row::new(cells)
@@ -190,33 +313,114 @@ impl UserInterface {
             .collect()
     }
 
-    fn render_scrollbar<D: Database>(frame: &mut Frame, model: &Model<D>, area: Rect) {
+    fn render_scrollbar<D: Database>(&mut self, frame: &mut Frame, model: &Model<D>, area: Rect) {
+        let area = area.inner(Margin {
+            vertical: 1,
+            horizontal: 1,
+        });
         frame.render_stateful_widget(
             Scrollbar::default()
                 .orientation(ScrollbarOrientation::VerticalRight)
                 .begin_symbol(None)
                 .end_symbol(None),
-            area.inner(Margin {
-                vertical: 1,
-                horizontal: 1,
-            }),
+            area,
             &mut model.scroll_state().clone(),
         );
+
+        self.geometry.scrollbar = Rect {
+            x: area.right().saturating_sub(1),
+            width: area.width.min(1),
+            ..area
+        };
+    }
+
+    /// Conditions of the open table's filter as chips above the rows.
+    fn render_filter<D: Database>(frame: &mut Frame, model: &Model<D>, area: Rect) {
+        let Some(filter) = model.active_filter() else {
+            return;
+        };
+        let chip = Style::new()
+            .fg(model.colors().header_fg)
+            .bg(model.colors().header_bg);
+        let mut spans = vec![Span::raw(" filter: ").bold()];
+        for (i, condition) in filter.conditions.iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::raw(format!(" {} ", condition.join.keyword())).dark_gray());
+            }
+            spans.push(Span::styled(format!(" {condition} "), chip));
+        }
+        frame.render_widget(
+            Paragraph::new(Line::from(spans)).style(Style::new().bg(model.colors().buffer_bg)),
+            area,
+        );
+    }
+
+    /// Timing and SQLite counters of the statement behind the shown rows.
+    fn render_stats<D: Database>(frame: &mut Frame, model: &Model<D>, area: Rect) {
+        let Some(stats) = model.stats() else {
+            return;
+        };
+        let text = format!(
+            "{:.1} ms | {} rows returned | {} changed | {} VM steps | {} full scan steps | {} sorts | cache {} hits / {} misses",
+            stats.elapsed.as_secs_f64() * 1000.0,
+            stats.rows_returned,
+            stats.rows_changed,
+            stats.vm_steps,
+            stats.fullscan_steps,
+            stats.sorts,
+            stats.cache_hits,
+            stats.cache_misses,
+        );
+        frame.render_widget(
+            Paragraph::new(text).centered().style(
+                Style::new()
+                    .fg(model.colors().row_fg)
+                    .bg(model.colors().header_bg),
+            ),
+            area,
+        );
     }
 
     fn render_footer<D: Database>(frame: &mut Frame, model: &Model<D>, area: Rect) {
-        let info_footer = Paragraph::new(model.get_info_text())
+        let paragraph = match (model.prompt(), model.message()) {
+            _ if model.is_loading() => {
+                let elapsed = model.loading_elapsed().unwrap_or_default();
+                let frame = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+                Paragraph::new(format!(
+                    "{frame} {} {:.1}s | (Esc) cancel",
+                    model.status().unwrap_or("loading…"),
+                    elapsed.as_secs_f64()
+                ))
+                .centered()
+            }
+            (Some(prompt), _) => Paragraph::new(format!(":{prompt}█")).left_aligned(),
+            (None, Some(message)) => Paragraph::new(message.to_string()).centered(),
+            (None, None) => Paragraph::new(model.get_info_text()).centered(),
+        };
+        let mut block = Block::bordered()
+            .border_type(BorderType::Double)
+            .border_style(Style::new().fg(model.colors().footer_border_color));
+        if let (ViewState::Query, Some(sql)) = (model.view_state(), model.query()) {
+            block = block.title(format!(" {sql} "));
+        } else if let Some(report) = model.report() {
+            block = block.title(format!(" {} ", report.title()));
+        }
+        if let Some(interval) = model.watch_interval() {
+            let watching = format!(" watching every {}s ", interval.as_secs_f64());
+            let indicator = match model.updated_ago() {
+                Some(ago) if ago < UPDATED_HIGHLIGHT => Line::from(" ● updated ").green().bold(),
+                Some(ago) => Line::from(format!("{watching}· updated {}s ago ", ago.as_secs())),
+                None => Line::from(watching),
+            };
+            block = block.title_top(indicator.right_aligned());
+        }
+        let info_footer = paragraph
             .style(
                 Style::new()
                     .fg(model.colors().row_fg)
                     .bg(model.colors().buffer_bg),
             )
-            .centered()
-            .block(
-                Block::bordered()
-                    .border_type(BorderType::Double)
-                    .border_style(Style::new().fg(model.colors().footer_border_color)),
-            );
+            .block(block);
         frame.render_widget(info_footer, area);
     }
 
@@ -233,11 +437,718 @@ impl UserInterface {
         };
 
         let popup = Popup::default()
-            .content((*model.table_schema().as_ref().unwrap()).to_string())
+            .content(highlight(model.table_schema().as_ref().unwrap()))
             .style(Style::new().yellow())
             .title(String::from("SCHEMA"))
             .title_style(Style::new().white().bold())
             .border_style(Style::new().red());
         frame.render_widget(popup, popup_area);
     }
+
+    fn render_record<D: Database>(frame: &mut Frame, model: &Model<D>) {
+        if !model.is_record_enabled() {
+            return;
+        }
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 4,
+            y: area.height / 4,
+            width: area.width / 2,
+            height: area.height / 2,
+        };
+
+        let formats = model.time_formats();
+        let content = model
+            .record()
+            .iter()
+            .enumerate()
+            .map(
+                |(i, (column, value))| match model.cell_text(&formats, i, value) {
+                    Cow::Owned(time) => format!("{column}: {time} ({value})"),
+                    Cow::Borrowed(value) => format!("{column}: {value}"),
+                },
+            )
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let popup = Popup::default()
+            .content(content)
+            .style(Style::new().yellow())
+            .title(String::from("RECORD"))
+            .title_style(Style::new().white().bold())
+            .border_style(Style::new().red());
+        frame.render_widget(popup, popup_area);
+    }
+
+    fn render_editor<D: Database>(frame: &mut Frame, model: &Model<D>, area: Rect) {
+        let Some(editor) = model.editor() else {
+            return;
+        };
+        let block = Block::bordered()
+            .title(" SQL | (Enter) run | (Tab) complete | (↑/↓) history | (Ctrl R) search | (Esc) close ")
+            .border_type(BorderType::Double)
+            .border_style(Style::new().fg(model.colors().footer_border_color));
+        let inner = block.inner(area);
+
+        // Scrolls horizontally so the cursor stays visible.
+        let width = usize::from(inner.width.max(1));
+        let offset = editor.cursor().saturating_sub(width - 1);
+
+        frame.render_widget(
+            Paragraph::new(highlight(editor.text()))
+                .scroll((0, u16::try_from(offset).unwrap_or(u16::MAX)))
+                .style(
+                    Style::new()
+                        .fg(model.colors().row_fg)
+                        .bg(model.colors().buffer_bg),
+                )
+                .block(block),
+            area,
+        );
+        let cursor = u16::try_from(editor.cursor() - offset).unwrap_or(0);
+        frame.set_cursor_position(Position::new(inner.x + cursor, inner.y));
+
+        Self::render_completion(frame, model, Position::new(inner.x + cursor, area.y));
+    }
+
+    /// Draws the completion candidates above the editor, starting at the
+    /// cursor column.
+    fn render_completion<D: Database>(frame: &mut Frame, model: &Model<D>, anchor: Position) {
+        let Some(completion) = model.completion() else {
+            return;
+        };
+        let items = completion.items();
+        let width = items
+            .iter()
+            .map(|item| item.chars().count())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let height = items.len().min(8) + 2;
+        let area = frame.area();
+        let width = u16::try_from(width).unwrap_or(u16::MAX).min(area.width);
+        let height = u16::try_from(height).unwrap_or(u16::MAX).min(anchor.y);
+        let popup_area = Rect {
+            x: anchor.x.min(area.width - width),
+            y: anchor.y - height,
+            width,
+            height,
+        };
+
+        let list = List::new(items.iter().map(|item| ListItem::new(item.as_str())))
+            .block(Block::bordered().border_style(Style::new().red()))
+            .style(Style::new().yellow().bg(model.colors().buffer_bg))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_stateful_widget(
+            list,
+            popup_area,
+            &mut ListState::default().with_selected(Some(completion.selected())),
+        );
+    }
+
+    fn render_picker<D: Database>(frame: &mut Frame, model: &Model<D>) {
+        let Some(picker) = model.picker() else {
+            return;
+        };
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 6,
+            y: area.height / 6,
+            width: area.width * 2 / 3,
+            height: area.height * 2 / 3,
+        };
+
+        let items: Vec<ListItem> = model
+            .picker_matches()
+            .into_iter()
+            .map(|sql| ListItem::new(sql.to_string()))
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(Line::from(format!(" HISTORY: {}█ ", picker.filter())).centered())
+                    .title_style(Style::new().white().bold())
+                    .border_style(Style::new().red()),
+            )
+            .style(Style::new().yellow().bg(model.colors().buffer_bg))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_stateful_widget(
+            list,
+            popup_area,
+            &mut ListState::default().with_selected(Some(picker.selected())),
+        );
+    }
+
+    fn render_maintenance<D: Database>(frame: &mut Frame, model: &Model<D>) {
+        let Some(view) = model.maintenance() else {
+            return;
+        };
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 8,
+            y: area.height / 4,
+            width: area.width * 3 / 4,
+            height: area.height / 2,
+        };
+        let (title, content) = maintenance(view);
+
+        let popup = Popup::default()
+            .content(content)
+            .style(Style::new().yellow())
+            .title(title)
+            .title_style(Style::new().white().bold())
+            .border_style(Style::new().red());
+        frame.render_widget(popup, popup_area);
+    }
+
+    fn render_schema_editor<D: Database>(frame: &mut Frame, model: &Model<D>) {
+        let Some(view) = model.schema_editor() else {
+            return;
+        };
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 8,
+            y: area.height / 8,
+            width: area.width * 3 / 4,
+            height: area.height * 3 / 4,
+        };
+        let (title, content) = schema_editor(view);
+
+        let popup = Popup::default()
+            .content(content)
+            .style(Style::new().yellow())
+            .title(title)
+            .title_style(Style::new().white().bold())
+            .border_style(Style::new().red());
+        frame.render_widget(popup, popup_area);
+    }
+
+    fn render_column_stats<D: Database>(frame: &mut Frame, model: &Model<D>) {
+        let Some(stats) = model.column_stats() else {
+            return;
+        };
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 4,
+            y: area.height / 8,
+            width: area.width / 2,
+            height: area.height * 3 / 4,
+        };
+
+        let popup = Popup::default()
+            .content(column_stats(stats))
+            .style(Style::new().yellow())
+            .title(format!(" {}.{} | (Esc) close ", stats.table, stats.column))
+            .title_style(Style::new().white().bold())
+            .border_style(Style::new().red());
+        frame.render_widget(popup, popup_area);
+    }
+
+    fn render_json<D: Database>(frame: &mut Frame, model: &Model<D>) {
+        let Some(view) = model.json() else {
+            return;
+        };
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 8,
+            y: area.height / 8,
+            width: area.width * 3 / 4,
+            height: area.height * 3 / 4,
+        };
+
+        let path = view.selected_node().map_or("", |node| node.path.as_str());
+        let list = List::new(json_tree(view))
+            .block(
+                Block::bordered()
+                    .title(Line::from(format!(" JSON {}: {path} ", view.column())).centered())
+                    .title_bottom(
+                        Line::from(
+                            " (Enter) fold | (y) copy path | (f) filter | (v) virtual column | (Esc) close ",
+                        )
+                        .centered(),
+                    )
+                    .title_style(Style::new().white().bold())
+                    .border_style(Style::new().red()),
+            )
+            .style(Style::new().yellow().bg(model.colors().buffer_bg))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_stateful_widget(
+            list,
+            popup_area,
+            &mut ListState::default().with_selected(Some(view.selected())),
+        );
+    }
+
+    fn render_filter_form<D: Database>(frame: &mut Frame, model: &Model<D>) {
+        let Some(form) = model.filter_form() else {
+            return;
+        };
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 8,
+            y: area.height / 4,
+            width: area.width * 3 / 4,
+            height: area.height / 2,
+        };
+
+        let popup = Popup::default()
+            .content(filter_form(form))
+            .style(Style::new().yellow())
+            .title(format!(
+                " FILTER {} | (Enter) apply | (Ctrl S) apply and save | (Esc) close ",
+                form.table()
+            ))
+            .title_style(Style::new().white().bold())
+            .border_style(Style::new().red());
+        frame.render_widget(popup, popup_area);
+    }
+
+    fn render_plan<D: Database>(frame: &mut Frame, model: &Model<D>) {
+        let Some(view) = model.plan() else {
+            return;
+        };
+        let area = frame.area();
+        let popup_area = Rect {
+            x: area.width / 8,
+            y: area.height / 8,
+            width: area.width * 3 / 4,
+            height: area.height * 3 / 4,
+        };
+
+        let (title, text) = if view.is_bytecode() {
+            (
+                " EXPLAIN | (Tab) query plan | (Esc) close ",
+                bytecode(view.plan()),
+            )
+        } else {
+            (
+                " QUERY PLAN | (Tab) bytecode | (Esc) close ",
+                plan_tree(view.plan()),
+            )
+        };
+        let paragraph = Paragraph::new(text)
+            .scroll((u16::try_from(view.scroll()).unwrap_or(u16::MAX), 0))
+            .block(
+                Block::bordered()
+                    .title(Line::from(title).centered())
+                    .title_style(Style::new().white().bold())
+                    .border_style(Style::new().red()),
+            )
+            .style(Style::new().yellow().bg(model.colors().buffer_bg));
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(paragraph, popup_area);
+    }
+}
+
+/// Lines of the visible elements of a JSON document, indented by depth,
+/// with their values colored by type.
+fn json_tree(view: &JsonView) -> Vec<Line<'static>> {
+    view.visible()
+        .into_iter()
+        .map(|(depth, node)| {
+            let mut spans = vec![Span::raw("  ".repeat(depth))];
+            spans.push(Span::raw(
+                match (node.is_container(), view.is_collapsed(node)) {
+                    (true, true) => "▸ ",
+                    (true, false) => "▾ ",
+                    (false, _) => "  ",
+                },
+            ));
+            if let Some(key) = &node.key {
+                spans.push(Span::styled(key.clone(), Style::new().cyan()));
+                spans.push(Span::raw(": "));
+            }
+            let value = node.value.clone().unwrap_or_default();
+            spans.push(match node.kind.as_str() {
+                // A folded container previews its text, an open one its type.
+                _ if node.is_container() && view.is_collapsed(node) => {
+                    Span::styled(value, Style::new().dark_gray())
+                }
+                "object" | "array" => Span::styled(node.kind.clone(), Style::new().dark_gray()),
+                "text" => Span::styled(Value::from(value).to_string(), Style::new().green()),
+                "integer" | "real" => Span::styled(value, Style::new().magenta()),
+                kind => Span::styled(kind.to_string(), Style::new().blue()),
+            });
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Lines of the maintenance popup and its title.
+fn maintenance(view: &MaintenanceView) -> (String, Text<'static>) {
+    match view {
+        MaintenanceView::Menu { selected } => {
+            let lines = Maintenance::all()
+                .into_iter()
+                .enumerate()
+                .map(|(i, action)| {
+                    let style = if i == *selected {
+                        Style::new().reversed()
+                    } else {
+                        Style::new()
+                    };
+                    Line::from(vec![
+                        Span::styled(format!("{:<32}", action.name()), style.bold()),
+                        Span::styled(action.description(), style),
+                    ])
+                });
+            (
+                " MAINTENANCE | (Enter) choose | (Esc) close ".to_string(),
+                Text::from_iter(lines),
+            )
+        }
+        MaintenanceView::Confirm(action) => {
+            let mut text = highlight(&action.sql());
+            text.push_line(Line::default());
+            text.push_line(Line::from(format!("This will {}.", action.description())));
+            text.push_line(Line::from(
+                "Other connections may be blocked while it runs.",
+            ));
+            (format!(" {} | (y) run | (n) cancel ", action.name()), text)
+        }
+        MaintenanceView::Done(result) => {
+            let MaintenanceResult {
+                action,
+                before,
+                after,
+            } = result.as_ref();
+            let copy = matches!(action, Maintenance::VacuumInto(_));
+            let wal = |info: &DatabaseInfo| {
+                info.wal_size
+                    .map_or_else(|| "none".to_string(), |size| format!("{size} bytes"))
+            };
+            let mut rows = vec![
+                (
+                    "",
+                    "before".to_string(),
+                    if copy { "copy" } else { "after" }.to_string(),
+                ),
+                (
+                    "file size",
+                    format!("{} bytes", before.file_size),
+                    format!("{} bytes", after.file_size),
+                ),
+                (
+                    "pages",
+                    before.page_count.to_string(),
+                    after.page_count.to_string(),
+                ),
+                (
+                    "free pages",
+                    before.freelist_count.to_string(),
+                    after.freelist_count.to_string(),
+                ),
+            ];
+            if !copy {
+                rows.push(("WAL", wal(before), wal(after)));
+            }
+            let mut text = Text::from(format!("{} finished", action.sql()));
+            text.push_line(Line::default());
+            for (i, (label, before, after)) in rows.into_iter().enumerate() {
+                let line = Line::from(format!("{label:<12}{before:>16}{after:>16}"));
+                text.push_line(if i == 0 { line.bold() } else { line });
+            }
+            (format!(" {} | (Esc) close ", action.name()), text)
+        }
+    }
+}
+
+/// Lines of the schema editor popup and its title.
+fn schema_editor(view: &SchemaView) -> (String, Text<'static>) {
+    match view {
+        SchemaView::Menu { table, selected } => {
+            let actions = schema_editor::Action::available(table.as_deref());
+            let lines = actions.into_iter().enumerate().map(|(i, action)| {
+                let style = if i == *selected {
+                    Style::new().reversed()
+                } else {
+                    Style::new()
+                };
+                Line::styled(action.name(), style.bold())
+            });
+            let mut text = Text::from_iter(lines);
+            let indexes = table.iter().flat_map(|table| &table.indexes);
+            for (i, index) in indexes.enumerate() {
+                if i == 0 {
+                    text.push_line(Line::default());
+                    text.push_line(Line::from("Indexes").bold());
+                }
+                let mut kind = Vec::new();
+                if index.primary_key {
+                    kind.push("primary key");
+                } else if index.unique {
+                    kind.push("unique");
+                }
+                if index.partial {
+                    kind.push("partial");
+                }
+                if index.sql.is_none() {
+                    kind.push("automatic");
+                }
+                text.push_line(Line::from(vec![
+                    Span::styled(format!("{:<32}", index.name), Style::new().bold()),
+                    Span::raw(format!(
+                        "({}) {}",
+                        index.columns.join(", "),
+                        kind.join(", ")
+                    )),
+                ]));
+            }
+            let title = match table {
+                Some(table) => format!(" SCHEMA of {} | (Enter) choose | (Esc) close ", table.name),
+                None => " SCHEMA | (Enter) choose | (Esc) close ".to_string(),
+            };
+            (title, text)
+        }
+        SchemaView::Form(form) => {
+            let mut title = format!(" {}", form.action().name());
+            if let Some(table) = form.table() {
+                title.push_str(&format!(" of {}", table.name));
+            }
+            title.push_str(" | (Tab) next | (←/→) choose | (Space) toggle");
+            if form.action() == schema_editor::Action::CreateTable {
+                title.push_str(" | (^N) add column | (^D) remove column");
+            }
+            title.push_str(" | (Enter) review | (Esc) close ");
+            (title, schema_form(form))
+        }
+        SchemaView::Review {
+            form,
+            statements,
+            rebuild,
+            plans,
+        } => {
+            let mut text = Text::default();
+            for statement in statements {
+                text.extend(highlight(&format!("{statement};")));
+            }
+            text.push_line(Line::default());
+            text.push_line(Line::from("The statements run in one transaction."));
+            if *rebuild {
+                text.push_line(Line::from(
+                    "ALTER TABLE cannot make this change, the table is rebuilt and its rows copied. \
+                     CHECK constraints and collations are not carried over.",
+                ));
+            }
+            if form.action() == schema_editor::Action::CreateIndex && plans.is_empty() {
+                text.push_line(Line::from("Building the index to compare query plans…"));
+            }
+            for change in plans {
+                text.push_line(Line::default());
+                text.extend(highlight(&change.before.sql));
+                let (label, style) = if change.is_changed() {
+                    ("plan with the index:", Style::new().green().bold())
+                } else {
+                    ("the index does not change the plan", Style::new().bold())
+                };
+                text.extend(plan_steps(&change.before));
+                text.push_line(Line::styled(label, style));
+                if change.is_changed() {
+                    text.extend(plan_steps(&change.after));
+                }
+            }
+            (
+                format!(" {} | (y) run | (n) back ", form.action().name()),
+                text,
+            )
+        }
+    }
+}
+
+fn schema_form(form: &SchemaForm) -> Text<'static> {
+    let lines = form.fields().iter().enumerate().map(|(i, field)| {
+        let style = if i == form.selected() {
+            Style::new().reversed()
+        } else {
+            Style::new()
+        };
+        Line::from(vec![
+            Span::styled(format!("{:<16}", field.label), Style::new().bold()),
+            Span::styled(field.value(), style),
+        ])
+    });
+    let mut text = Text::from_iter(lines);
+    if form.fields().is_empty() {
+        text.push_line(Line::from("Nothing to fill in."));
+    }
+    text
+}
+
+/// The statement followed by its plan as an indented tree, with full scans
+/// in red and index lookups in green.
+/// Lines of the filter panel: a row per condition with the focused field
+/// highlighted, and the resulting `WHERE` clause.
+fn filter_form(form: &FilterForm) -> Text<'static> {
+    let (selected, focus) = form.focus();
+    let mut text = Text::default();
+    for (i, condition) in form.conditions().iter().enumerate() {
+        let style = |field: Field| {
+            if i == selected && field == focus {
+                Style::new().reversed()
+            } else {
+                Style::new()
+            }
+        };
+        let join = if i == 0 {
+            "WHERE"
+        } else {
+            condition.join.keyword()
+        };
+        let mut spans = vec![
+            Span::raw(if i == selected { "> " } else { "  " }),
+            Span::styled(format!("{join:<5}"), style(Field::Join)),
+            Span::raw(" "),
+            Span::styled(condition.column.clone(), style(Field::Column).bold()),
+            Span::raw(" "),
+            Span::styled(condition.operator.symbol(), style(Field::Operator)),
+        ];
+        if condition.operator.has_value() {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(
+                format!("{}_", condition.value),
+                style(Field::Value),
+            ));
+        }
+        text.push_line(Line::from(spans));
+    }
+    text.push_line(Line::default());
+    match form.filter().to_where() {
+        Ok(Some(clause)) => text.extend(highlight(&format!("WHERE {}", clause.sql))),
+        Ok(None) => text.push_line(Line::from("no conditions, all rows are shown")),
+        Err(err) => text.push_line(Line::from(err.to_string()).red()),
+    }
+    text.push_line(Line::default());
+    text.push_line(Line::from(
+        "(Tab) next field | (↑/↓) condition | (←/→) change | (Ctrl N) add | (Ctrl D) remove",
+    ));
+    text
+}
+
+/// Lines of the column statistics popup.
+fn column_stats(stats: &ColumnStats) -> Text<'static> {
+    let share = |n: u64| {
+        if stats.count == 0 {
+            n.to_string()
+        } else {
+            format!("{n} ({:.1}%)", 100.0 * n as f64 / stats.count as f64)
+        }
+    };
+    let mut rows = vec![
+        ("rows", stats.count.to_string()),
+        ("distinct", share(stats.distinct)),
+        ("null", share(stats.nulls)),
+    ];
+    rows.extend(stats.min.clone().map(|min| ("min", min)));
+    rows.extend(stats.max.clone().map(|max| ("max", max)));
+    rows.extend(
+        stats
+            .average
+            .map(|average| ("average", format!("{average:.4}"))),
+    );
+
+    let mut text = Text::default();
+    for (label, value) in rows {
+        text.push_line(Line::from(vec![
+            Span::raw(format!("{label:<12}")).bold(),
+            Span::raw(value),
+        ]));
+    }
+    for (label, histogram) in [("values", &stats.values), ("text length", &stats.lengths)] {
+        if let Some(histogram) = histogram {
+            text.push_line(Line::from(vec![
+                Span::raw(format!("{label:<12}")).bold(),
+                Span::raw(format!("{} ", histogram.low)),
+                Span::raw(sparkline(&histogram.buckets)).green(),
+                Span::raw(format!(" {}", histogram.high)),
+            ]));
+        }
+    }
+    if !stats.top.is_empty() {
+        text.push_line(Line::default());
+        text.push_line(Line::from("most frequent").bold());
+        for (value, count) in &stats.top {
+            text.push_line(Line::from(format!("{:>10}  {value}", share(*count))));
+        }
+    }
+    text
+}
+
+/// Bar per bucket, scaled to the largest one.
+fn sparkline(buckets: &[u64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = buckets.iter().copied().max().unwrap_or(0).max(1);
+    buckets
+        .iter()
+        .map(|&count| match count {
+            0 => ' ',
+            count => BARS[((count * 8 - 1) / max) as usize],
+        })
+        .collect()
+}
+
+fn plan_tree(plan: &Plan) -> Text<'static> {
+    let mut text = highlight(&plan.sql);
+    text.push_line(Line::default());
+    text.extend(plan_steps(plan));
+    text
+}
+
+fn plan_steps(plan: &Plan) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    for step in &plan.steps {
+        let style = match step.access() {
+            Access::FullScan => Style::new().red().bold(),
+            Access::Index => Style::new().green(),
+            Access::TempTree => Style::new().magenta(),
+            Access::Other => Style::new(),
+        };
+        lines.push(Line::from(vec![
+            Span::raw(format!("{}└─ ", "   ".repeat(step.depth))),
+            Span::styled(step.detail.clone(), style),
+        ]));
+    }
+    lines
+}
+
+fn bytecode(plan: &Plan) -> Text<'static> {
+    let mut out = Vec::new();
+    match output::write(&mut out, &plan.bytecode, Format::Table) {
+        Ok(()) => Text::from(String::from_utf8_lossy(&out).into_owned()),
+        Err(err) => Text::from(err.to_string()),
+    }
+}
+
+fn token_style(kind: TokenKind) -> Style {
+    match kind {
+        TokenKind::Keyword => Style::new().light_blue().bold(),
+        TokenKind::Identifier => Style::new().light_cyan(),
+        TokenKind::String => Style::new().green(),
+        TokenKind::Number => Style::new().magenta(),
+        TokenKind::Comment => Style::new().dark_gray().italic(),
+        TokenKind::Operator | TokenKind::Whitespace => Style::new(),
+    }
+}
+
+/// Colors `sql` by token, keeping its line breaks.
+fn highlight(sql: &str) -> Text<'static> {
+    let mut lines = vec![Line::default()];
+    for token in sql::tokenize(sql) {
+        for (i, part) in token.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Line::default());
+            }
+            if !part.is_empty() {
+                let line = lines.last_mut().expect("at least one line");
+                line.push_span(Span::styled(part.to_string(), token_style(token.kind)));
+            }
+        }
+    }
+    Text::from(lines)
 }